@@ -0,0 +1,65 @@
+// Answers the second login step with a passkey. The button carries the
+// pending MFA token; /login/passkey checks the assertion and starts the session.
+(function () {
+  var button = document.getElementById("use-passkey");
+  if (!button || !window.PublicKeyCredential) return;
+  button.hidden = false;
+
+  var csrf = document.querySelector('meta[name="csrf-token"]').content;
+  var status = document.getElementById("passkey-status");
+
+  function decode(value) {
+    var base64 = value.replace(/-/g, "+").replace(/_/g, "/");
+    return Uint8Array.from(atob(base64), function (c) { return c.charCodeAt(0); });
+  }
+
+  function encode(buffer) {
+    var binary = String.fromCharCode.apply(null, new Uint8Array(buffer));
+    return btoa(binary).replace(/\+/g, "-").replace(/\//g, "_").replace(/=+$/, "");
+  }
+
+  function post(url, body) {
+    return fetch(url, {
+      method: "POST",
+      headers: { "Content-Type": "application/json", "X-CSRF-Token": csrf },
+      body: JSON.stringify(body),
+    });
+  }
+
+  async function signIn() {
+    var start = await post("/api/v1/auth/mfa/passkey", { mfa_token: button.dataset.mfaToken });
+    if (!start.ok) throw new Error("start");
+    var challenge = await start.json();
+
+    var options = challenge.options.publicKey;
+    options.challenge = decode(options.challenge);
+    (options.allowCredentials || []).forEach(function (c) { c.id = decode(c.id); });
+    var credential = await navigator.credentials.get({ publicKey: options });
+
+    var finish = await post("/login/passkey", {
+      challenge_id: challenge.challenge_id,
+      remember_me: button.dataset.rememberMe === "true",
+      credential: {
+        id: credential.id,
+        rawId: encode(credential.rawId),
+        type: credential.type,
+        response: {
+          authenticatorData: encode(credential.response.authenticatorData),
+          clientDataJSON: encode(credential.response.clientDataJSON),
+          signature: encode(credential.response.signature),
+          userHandle: credential.response.userHandle ? encode(credential.response.userHandle) : null,
+        },
+        extensions: credential.getClientExtensionResults(),
+      },
+    });
+    if (!finish.ok) throw new Error("finish");
+    window.location = finish.url;
+  }
+
+  button.addEventListener("click", function () {
+    status.textContent = "";
+    signIn().catch(function () {
+      status.textContent = "Passkey sign-in failed. Try again or use a code.";
+    });
+  });
+})();
@@ -0,0 +1,8 @@
+-- Pending follow requests have no meaning once privacy is gone
+DELETE FROM relationships WHERE status = 'pending';
+
+DROP INDEX IF EXISTS idx_relationships_followed_status;
+ALTER TABLE relationships DROP CONSTRAINT IF EXISTS chk_relationship_status;
+ALTER TABLE relationships DROP COLUMN IF EXISTS accepted_at;
+ALTER TABLE relationships DROP COLUMN IF EXISTS status;
+ALTER TABLE users DROP COLUMN IF EXISTS is_private;
@@ -0,0 +1,14 @@
+-- Add private account flag to users
+ALTER TABLE users ADD COLUMN is_private BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Track follow request state on relationships
+ALTER TABLE relationships ADD COLUMN status VARCHAR(20) NOT NULL DEFAULT 'accepted';
+ALTER TABLE relationships ADD COLUMN accepted_at TIMESTAMP WITH TIME ZONE;
+
+UPDATE relationships SET accepted_at = created_at WHERE status = 'accepted';
+
+-- Create indexes
+CREATE INDEX idx_relationships_followed_status ON relationships(followed_id, status);
+
+-- Add constraint for valid statuses
+ALTER TABLE relationships ADD CONSTRAINT chk_relationship_status CHECK (status IN ('pending', 'accepted'));
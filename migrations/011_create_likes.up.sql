@@ -0,0 +1,13 @@
+-- Create likes table
+CREATE TABLE likes (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    micropost_id UUID NOT NULL REFERENCES microposts(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Create indexes
+CREATE INDEX idx_likes_micropost_id ON likes(micropost_id);
+
+-- One like per user per micropost
+ALTER TABLE likes ADD CONSTRAINT unique_likes UNIQUE (user_id, micropost_id);
@@ -0,0 +1,30 @@
+-- Make email uniqueness case-insensitive
+-- Accounts whose emails differ only by case must be merged or renamed by hand
+-- first; `cargo run -- user duplicate-emails` lists them.
+DO $$
+DECLARE
+    conflicts TEXT;
+BEGIN
+    SELECT string_agg(format('%s (%s)', lower(email), ids), '; ')
+    INTO conflicts
+    FROM (
+        SELECT lower(email) AS email, string_agg(id::TEXT, ', ' ORDER BY created_at) AS ids
+        FROM users
+        GROUP BY lower(email)
+        HAVING COUNT(*) > 1
+    ) duplicates;
+    
+    IF conflicts IS NOT NULL THEN
+        RAISE EXCEPTION 'Users with emails differing only by case: %', conflicts
+            USING HINT = 'Merge or rename these accounts, then run the migrations again';
+    END IF;
+END $$;
+
+-- Normalize rows written before emails were lowercased on every path
+UPDATE users SET email = lower(email) WHERE email <> lower(email);
+
+ALTER TABLE users DROP CONSTRAINT users_email_key;
+DROP INDEX idx_users_email;
+
+-- Create indexes
+CREATE UNIQUE INDEX users_email_lower_key ON users(lower(email));
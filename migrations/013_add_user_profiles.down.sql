@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS username_redirects;
+DROP INDEX IF EXISTS users_username_lower_key;
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_username_format;
+ALTER TABLE users DROP COLUMN IF EXISTS banner;
+ALTER TABLE users DROP COLUMN IF EXISTS website;
+ALTER TABLE users DROP COLUMN IF EXISTS location;
+ALTER TABLE users DROP COLUMN IF EXISTS bio;
+ALTER TABLE users DROP COLUMN IF EXISTS username;
@@ -0,0 +1,20 @@
+-- Add profile fields and a username handle
+ALTER TABLE users ADD COLUMN username VARCHAR(30);
+ALTER TABLE users ADD COLUMN bio VARCHAR(160);
+ALTER TABLE users ADD COLUMN location VARCHAR(100);
+ALTER TABLE users ADD COLUMN website VARCHAR(255);
+ALTER TABLE users ADD COLUMN banner VARCHAR(255);
+
+ALTER TABLE users ADD CONSTRAINT users_username_format CHECK (username ~ '^[A-Za-z0-9_]{3,30}$');
+
+-- Previous handles keep redirecting to their owner until someone claims them
+CREATE TABLE username_redirects (
+    username VARCHAR(30) NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- Create indexes
+CREATE UNIQUE INDEX users_username_lower_key ON users(lower(username));
+CREATE UNIQUE INDEX username_redirects_username_lower_key ON username_redirects(lower(username));
+CREATE INDEX idx_username_redirects_user_id ON username_redirects(user_id);
@@ -0,0 +1,2 @@
+-- Access, refresh and session tokens issued before this instant are rejected
+ALTER TABLE users ADD COLUMN tokens_valid_after TIMESTAMP WITH TIME ZONE;
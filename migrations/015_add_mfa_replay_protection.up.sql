@@ -0,0 +1,2 @@
+-- The last TOTP time step accepted for each user, so a code can't be replayed
+ALTER TABLE users ADD COLUMN mfa_last_used_step BIGINT;
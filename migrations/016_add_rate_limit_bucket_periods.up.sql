@@ -0,0 +1,4 @@
+-- Each bucket's quota period, so idle buckets are pruned once they have refilled
+-- Existing buckets keep the old one-hour horizon
+ALTER TABLE rate_limit_buckets ADD COLUMN period_secs BIGINT NOT NULL DEFAULT 3600;
+ALTER TABLE rate_limit_buckets ALTER COLUMN period_secs DROP DEFAULT;
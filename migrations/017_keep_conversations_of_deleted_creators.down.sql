@@ -0,0 +1,5 @@
+DELETE FROM conversations WHERE created_by IS NULL;
+ALTER TABLE conversations DROP CONSTRAINT conversations_created_by_fkey;
+ALTER TABLE conversations ADD CONSTRAINT conversations_created_by_fkey
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE CASCADE;
+ALTER TABLE conversations ALTER COLUMN created_by SET NOT NULL;
@@ -0,0 +1,5 @@
+-- Deleting an account no longer deletes the conversations it started
+ALTER TABLE conversations ALTER COLUMN created_by DROP NOT NULL;
+ALTER TABLE conversations DROP CONSTRAINT conversations_created_by_fkey;
+ALTER TABLE conversations ADD CONSTRAINT conversations_created_by_fkey
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL;
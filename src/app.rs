@@ -1,14 +1,37 @@
+use std::sync::{atomic::AtomicBool, Arc};
+use metrics_exporter_prometheus::PrometheusHandle;
 use sqlx::PgPool;
-use crate::config::Config;
+use crate::{auth::{OidcMetadataCache, SlidingWindowLimiter}, config::Config, error::AppError, graphql::{self, AppSchema}, rate_limit::RateLimiter, shutdown::Shutdown, telemetry};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: PgPool,
     pub config: Config,
+    pub login_limiter: Arc<SlidingWindowLimiter>,
+    pub oidc_metadata: Arc<OidcMetadataCache>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub shutdown: Shutdown,
+    pub metrics: PrometheusHandle,
+    pub graphql: AppSchema,
+    /// Set by readiness once the pool's database has every migration applied.
+    pub migrations_applied: Arc<AtomicBool>,
 }
 
 impl AppState {
-    pub fn new(db: PgPool, config: Config) -> Self {
-        Self { db, config }
+    pub fn new(db: PgPool, config: Config) -> Result<Self, AppError> {
+        let rate_limiter = Arc::new(RateLimiter::new(config.rate_limit.backend, db.clone()));
+        let graphql = graphql::build_schema(db.clone());
+        
+        Ok(Self {
+            db,
+            config,
+            login_limiter: Arc::new(SlidingWindowLimiter::new()),
+            oidc_metadata: Arc::new(OidcMetadataCache::new()),
+            rate_limiter,
+            shutdown: Shutdown::new(),
+            metrics: telemetry::prometheus_handle()?,
+            graphql,
+            migrations_applied: Arc::new(AtomicBool::new(false)),
+        })
     }
 }
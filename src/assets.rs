@@ -0,0 +1,171 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::Path,
+    sync::OnceLock,
+};
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, StatusCode, Uri},
+    middleware::{self, Next},
+    response::Response,
+    Router,
+};
+use sha2::{Digest, Sha256};
+use tower::ServiceBuilder;
+use tower_http::services::ServeDir;
+use crate::app::AppState;
+
+pub const MOUNT: &str = "/assets";
+
+/// Fingerprinted URLs change whenever the file does, so they can be cached forever.
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+/// Plain URLs may be cached but must be revalidated (`ServeDir` answers with `Last-Modified`).
+const REVALIDATE: &str = "public, no-cache";
+
+static MANIFEST: OnceLock<AssetManifest> = OnceLock::new();
+
+/// Maps `css/app.css` to `css/app.3f2a1b9c0d4e5f61.css` and back.
+#[derive(Debug, Default)]
+pub struct AssetManifest {
+    fingerprinted: HashMap<String, String>,
+    originals: HashMap<String, String>,
+}
+
+impl AssetManifest {
+    pub fn build(dir: &Path) -> io::Result<Self> {
+        let mut manifest = Self::default();
+        if dir.is_dir() {
+            manifest.scan(dir, dir)?;
+        }
+        Ok(manifest)
+    }
+    
+    fn scan(&mut self, root: &Path, dir: &Path) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.scan(root, &path)?;
+                continue;
+            }
+            
+            // Precompressed siblings are served for their original's URL
+            if matches!(path.extension().and_then(|e| e.to_str()), Some("br" | "gz")) {
+                continue;
+            }
+            
+            let Some(logical) = path.strip_prefix(root).ok().and_then(|p| p.to_str()) else {
+                continue;
+            };
+            let logical = logical.replace('\\', "/");
+            let digest = format!("{:x}", Sha256::digest(fs::read(&path)?));
+            let fingerprinted = fingerprint(&logical, &digest[..16]);
+            
+            self.originals.insert(fingerprinted.clone(), logical.clone());
+            self.fingerprinted.insert(logical, fingerprinted);
+        }
+        Ok(())
+    }
+}
+
+fn fingerprint(logical: &str, hash: &str) -> String {
+    let file_start = logical.rfind('/').map_or(0, |i| i + 1);
+    match logical[file_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let (stem, ext) = logical.split_at(file_start + dot);
+            format!("{}.{}{}", stem, hash, ext)
+        }
+        _ => format!("{}.{}", logical, hash),
+    }
+}
+
+/// Fingerprints every file under `assets_dir`; call once before serving.
+pub fn init(assets_dir: &str) -> io::Result<()> {
+    let manifest = AssetManifest::build(Path::new(assets_dir))?;
+    tracing::info!(assets = manifest.fingerprinted.len(), "Fingerprinted static assets");
+    let _ = MANIFEST.set(manifest);
+    Ok(())
+}
+
+/// Public URL for an asset, fingerprinted when the file is known.
+pub fn asset_path(logical: &str) -> String {
+    let logical = logical.trim_start_matches('/');
+    let path = MANIFEST
+        .get()
+        .and_then(|manifest| manifest.fingerprinted.get(logical))
+        .map_or(logical, String::as_str);
+    format!("{}/{}", MOUNT, path)
+}
+
+/// Template helper, in scope for every Askama template:
+/// `<link rel="stylesheet" href="{{ self.asset_path("application.css") }}">`.
+pub trait AssetHelper {
+    fn asset_path(&self, logical: &str) -> String {
+        asset_path(logical)
+    }
+}
+
+impl<T: askama::Template> AssetHelper for T {}
+
+/// Serves `/assets`, preferring `.br`/`.gz` siblings the client accepts.
+/// Fingerprinted names are mapped back to the file on disk.
+pub fn routes(assets_dir: &str) -> Router<AppState> {
+    let files = ServeDir::new(assets_dir).precompressed_br().precompressed_gzip();
+    
+    Router::new().nest_service(
+        MOUNT,
+        ServiceBuilder::new()
+            .layer(middleware::from_fn(resolve_fingerprint))
+            .service(files),
+    )
+}
+
+async fn resolve_fingerprint(mut request: Request, next: Next) -> Response {
+    let original = MANIFEST
+        .get()
+        .and_then(|manifest| manifest.originals.get(request.uri().path().trim_start_matches('/')))
+        .and_then(|original| format!("/{}", original).parse::<Uri>().ok());
+    
+    let immutable = original.is_some();
+    if let Some(uri) = original {
+        *request.uri_mut() = uri;
+    }
+    
+    let mut response = next.run(request).await;
+    if response.status() == StatusCode::OK || response.status() == StatusCode::NOT_MODIFIED {
+        let policy = if immutable { IMMUTABLE } else { REVALIDATE };
+        response.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static(policy));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use sqlx::PgPool;
+    use crate::test_support::*;
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn layout_stylesheet_is_fingerprinted_and_immutable(pool: PgPool) {
+        super::init(&config().storage.assets_dir).unwrap();
+        let app = app(pool);
+        
+        let response = send(&app, Request::get("/").body(Body::empty()).unwrap()).await;
+        let html = body_text(response).await;
+        let href = html
+            .split("<link rel=\"stylesheet\" href=\"")
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap()
+            .to_string();
+        assert!(href.starts_with("/assets/application.") && href != "/assets/application.css", "{}", href);
+        
+        let response = send(&app, Request::get(&href).body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], super::IMMUTABLE);
+        assert!(response.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/css"));
+    }
+}
@@ -0,0 +1,78 @@
+use axum::http::{header::COOKIE, HeaderMap, HeaderValue};
+use crate::config::{Config, Environment};
+
+/// SameSite policy for cookies we set. `Lax` keeps top-level navigation from
+/// other sites working while withholding the cookie from cross-site POSTs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+}
+
+impl SameSite {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+        }
+    }
+}
+
+/// Defaults for every cookie the app sets: HttpOnly, `SameSite=Lax`, `Path=/`,
+/// and `Secure` outside development and test.
+#[derive(Debug, Clone, Copy)]
+pub struct CookieOptions {
+    pub same_site: SameSite,
+    pub http_only: bool,
+    pub secure: bool,
+    pub max_age_secs: Option<u64>,
+}
+
+impl CookieOptions {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            same_site: SameSite::Lax,
+            http_only: true,
+            secure: config.environment == Environment::Production,
+            max_age_secs: None,
+        }
+    }
+    
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        self
+    }
+    
+    pub fn max_age(mut self, secs: u64) -> Self {
+        self.max_age_secs = Some(secs);
+        self
+    }
+    
+    /// Builds a `Set-Cookie` header value. Names and values must be cookie-safe.
+    pub fn build(&self, name: &str, value: &str) -> HeaderValue {
+        let mut cookie = format!("{}={}; Path=/; SameSite={}", name, value, self.same_site.as_str());
+        if let Some(max_age) = self.max_age_secs {
+            cookie.push_str(&format!("; Max-Age={}", max_age));
+        }
+        if self.http_only {
+            cookie.push_str("; HttpOnly");
+        }
+        if self.secure {
+            cookie.push_str("; Secure");
+        }
+        
+        HeaderValue::from_str(&cookie).expect("cookie names and values are header-safe")
+    }
+}
+
+/// Reads a cookie sent by the client.
+pub fn read_cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
@@ -0,0 +1,192 @@
+use axum::{
+    extract::{Request, State},
+    http::{header::SET_COOKIE, Method},
+    middleware::Next,
+    response::Response,
+};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::Sha256;
+use crate::{
+    app::AppState,
+    auth::{is_api_request, read_cookie, session_claims, CookieOptions},
+    error::AppError,
+    forms::{buffer_body, content_type, form_field},
+};
+
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+/// Hidden form field name, as in Rails.
+pub const CSRF_FIELD: &str = "authenticity_token";
+
+tokio::task_local! {
+    static CURRENT_TOKEN: String;
+}
+
+/// The current request's CSRF token, also available to handlers as an extension.
+#[derive(Debug, Clone)]
+pub struct CsrfToken(pub String);
+
+/// Template helper, in scope for every Askama template:
+/// `<form method="post">{{ self.csrf_field()|safe }}...</form>`.
+pub trait CsrfHelper {
+    fn csrf_token(&self) -> String {
+        current_token().unwrap_or_default()
+    }
+    
+    fn csrf_field(&self) -> String {
+        format!(r#"<input type="hidden" name="{}" value="{}">"#, CSRF_FIELD, self.csrf_token())
+    }
+    
+    fn csrf_meta_tag(&self) -> String {
+        format!(r#"<meta name="csrf-token" content="{}">"#, self.csrf_token())
+    }
+}
+
+impl<T: askama::Template> CsrfHelper for T {}
+
+pub fn current_token() -> Option<String> {
+    CURRENT_TOKEN.try_with(|token| token.clone()).ok()
+}
+
+/// Signed double-submit protection for browser routes. The token lives in a
+/// `SameSite=Lax` cookie and must come back in the `authenticity_token` form field
+/// or the `X-CSRF-Token` header on every unsafe request. API routes authenticate
+/// with bearer tokens, which browsers never attach on their own, so they're exempt.
+/// Tokens are bound to the signed-in user, so a new one is issued when the session
+/// changes and one planted before sign-in stops working.
+pub async fn csrf_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if is_api_request(&request) {
+        return Ok(next.run(request).await);
+    }
+    
+    let secret = state.config.auth.jwt_secret.as_bytes();
+    let session = session_claims(&state.config, request.headers()).map(|claims| claims.sub).unwrap_or_default();
+    let existing = read_cookie(request.headers(), CSRF_COOKIE)
+        .filter(|token| verify(secret, &session, token))
+        .map(str::to_string);
+    
+    let request = if is_safe(request.method()) {
+        request
+    } else {
+        let expected = existing
+            .as_deref()
+            .ok_or_else(|| AppError::Forbidden("Invalid authenticity token".to_string()))?;
+        let (request, submitted) = submitted_token(request, state.config.storage.max_upload_bytes).await?;
+        
+        if !submitted.map_or(false, |submitted| constant_time_eq(submitted.as_bytes(), expected.as_bytes())) {
+            tracing::warn!("Rejected request with a missing or invalid CSRF token");
+            return Err(AppError::Forbidden("Invalid authenticity token".to_string()));
+        }
+        request
+    };
+    
+    let token = existing.clone().unwrap_or_else(|| generate(secret, &session));
+    let mut request = request;
+    request.extensions_mut().insert(CsrfToken(token.clone()));
+    
+    let mut response = CURRENT_TOKEN.scope(token.clone(), next.run(request)).await;
+    
+    if existing.is_none() {
+        let cookie = CookieOptions::new(&state.config).build(CSRF_COOKIE, &token);
+        response.headers_mut().append(SET_COOKIE, cookie);
+    }
+    
+    Ok(response)
+}
+
+fn is_safe(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE)
+}
+
+/// Finds the submitted token in the header or form body, then rebuilds the request.
+async fn submitted_token(request: Request, limit: usize) -> Result<(Request, Option<String>), AppError> {
+    if let Some(token) = request.headers().get(CSRF_HEADER).and_then(|v| v.to_str().ok()) {
+        let token = token.to_string();
+        return Ok((request, Some(token)));
+    }
+    
+    let content_type = content_type(&request);
+    let (request, bytes) = buffer_body(request, limit).await?;
+    let token = form_field(&content_type, &bytes, CSRF_FIELD);
+    
+    Ok((request, token))
+}
+
+/// `<random>.<hmac>`, signed over the session's user id (empty when signed out),
+/// so a cookie planted from a sibling subdomain or another session isn't accepted.
+fn generate(secret: &[u8], session: &str) -> String {
+    let nonce: String = rand::thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect();
+    format!("{}.{}", nonce, sign(secret, session, &nonce))
+}
+
+fn verify(secret: &[u8], session: &str, token: &str) -> bool {
+    token
+        .split_once('.')
+        .map_or(false, |(nonce, signature)| constant_time_eq(sign(secret, session, nonce).as_bytes(), signature.as_bytes()))
+}
+
+fn sign(secret: &[u8], session: &str, nonce: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(b"csrf:");
+    mac.update(session.as_bytes());
+    mac.update(b":");
+    mac.update(nonce.as_bytes());
+    mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use sqlx::PgPool;
+    use crate::{app::AppState, auth::CSRF_HEADER, test_support::*};
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn tokens_are_bound_to_the_session(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let session = session_cookie(alice.id);
+        
+        // A token issued before sign-in doesn't carry over into the session
+        let anonymous = form_post(&app, "/microposts", "").await.body(Body::empty()).unwrap();
+        let (parts, _) = anonymous.into_parts();
+        let cookies = format!("{}; {}", parts.headers[header::COOKIE].to_str().unwrap(), session);
+        let request = Request::post("/microposts")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::COOKIE, cookies)
+            .header(CSRF_HEADER, parts.headers[CSRF_HEADER].clone())
+            .body(Body::from("content=Hello"))
+            .unwrap();
+        assert_eq!(send(&app, request).await.status(), StatusCode::FORBIDDEN);
+        
+        let request = form_post(&app, "/microposts", &session).await.body(Body::from("content=Hello")).unwrap();
+        assert_eq!(send(&app, request).await.status(), StatusCode::SEE_OTHER);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn oversized_forms_are_rejected_with_413(pool: PgPool) {
+        let mut config = config();
+        config.storage.max_upload_bytes = 1024;
+        let app = crate::create_app(AppState::new(pool, config).unwrap());
+        
+        // Without the header the token has to be read from the body
+        let (parts, _) = form_post(&app, "/login", "").await.body(Body::empty()).unwrap().into_parts();
+        let request = Request::post("/login")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::COOKIE, parts.headers[header::COOKIE].clone())
+            .body(Body::from(format!("email={}", "a".repeat(2048))))
+            .unwrap();
+        assert_eq!(send(&app, request).await.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -9,7 +9,7 @@ pub struct Claims {
     pub sub: String, // User ID
     pub exp: i64,    // Expiration time
     pub iat: i64,    // Issued at
-    pub token_type: String, // "access" or "refresh"
+    pub token_type: String, // "access", "refresh", "mfa_pending" or "session"
 }
 
 pub struct JwtService {
@@ -51,6 +51,36 @@ impl JwtService {
             .map_err(|e| AppError::Internal(format!("Failed to generate refresh token: {}", e)))
     }
     
+    /// Short-lived token proving the password step passed; only exchangeable for
+    /// an access token together with a valid TOTP or recovery code.
+    pub fn generate_mfa_pending_token(&self, user_id: Uuid) -> Result<String, AppError> {
+        let now = Utc::now();
+        let claims = Claims {
+            sub: user_id.to_string(),
+            exp: (now + Duration::minutes(5)).timestamp(),
+            iat: now.timestamp(),
+            token_type: "mfa_pending".to_string(),
+        };
+        
+        encode(&Header::default(), &claims, &self.encoding_key)
+            .map_err(|e| AppError::Internal(format!("Failed to generate MFA token: {}", e)))
+    }
+    
+    /// Browser sign-in carried in the `session` cookie; "remember me" keeps it for 30 days.
+    pub fn generate_session_token(&self, user_id: Uuid, remember: bool) -> Result<String, AppError> {
+        let now = Utc::now();
+        let lifetime = if remember { Duration::days(30) } else { Duration::days(1) };
+        let claims = Claims {
+            sub: user_id.to_string(),
+            exp: (now + lifetime).timestamp(),
+            iat: now.timestamp(),
+            token_type: "session".to_string(),
+        };
+        
+        encode(&Header::default(), &claims, &self.encoding_key)
+            .map_err(|e| AppError::Internal(format!("Failed to generate session token: {}", e)))
+    }
+    
     pub fn verify_token(&self, token: &str) -> Result<Claims, AppError> {
         decode::<Claims>(token, &self.decoding_key, &Validation::default())
             .map(|data| data.claims)
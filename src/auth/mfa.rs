@@ -0,0 +1,89 @@
+use std::io::Cursor;
+use std::time::{SystemTime, UNIX_EPOCH};
+use image::{DynamicImage, ImageOutputFormat, Luma};
+use qrcode::QrCode;
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+use totp_rs::{Algorithm, Secret, TOTP};
+use crate::error::AppError;
+use super::csrf::constant_time_eq;
+
+const ISSUER: &str = "Sample App";
+const RECOVERY_CODE_COUNT: usize = 10;
+
+pub struct MfaService;
+
+impl MfaService {
+    pub fn generate_secret() -> String {
+        Secret::generate_secret().to_encoded().to_string()
+    }
+    
+    fn totp(secret: &str, account_name: &str) -> Result<TOTP, AppError> {
+        let bytes = Secret::Encoded(secret.to_string())
+            .to_bytes()
+            .map_err(|e| AppError::Internal(format!("Invalid MFA secret: {:?}", e)))?;
+        
+        TOTP::new(Algorithm::SHA1, 6, 1, 30, bytes, Some(ISSUER.to_string()), account_name.to_string())
+            .map_err(|e| AppError::Internal(format!("Failed to build TOTP: {}", e)))
+    }
+    
+    pub fn otpauth_uri(secret: &str, account_name: &str) -> Result<String, AppError> {
+        Ok(Self::totp(secret, account_name)?.get_url())
+    }
+    
+    /// Returns the time step `code` belongs to, if valid. Steps at or before
+    /// `last_used_step` are refused so an accepted code can't be replayed;
+    /// callers record the returned step with `MfaRepository::record_step`.
+    pub fn verify_code(secret: &str, account_name: &str, code: &str, last_used_step: Option<i64>) -> Result<Option<i64>, AppError> {
+        let totp = Self::totp(secret, account_name)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| AppError::Internal(format!("System clock error: {}", e)))?
+            .as_secs();
+        let current = (now / totp.step) as i64;
+        
+        // One step of skew either side tolerates clock drift on the authenticator
+        Ok((current - 1..=current + 1)
+            .filter(|step| last_used_step.map_or(true, |last| *step > last))
+            .find(|step| constant_time_eq(totp.generate(*step as u64 * totp.step).as_bytes(), code.trim().as_bytes())))
+    }
+    
+    /// Renders the otpauth URI as a PNG so no third-party QR service sees the secret.
+    pub fn qr_code_png(uri: &str) -> Result<Vec<u8>, AppError> {
+        let code = QrCode::new(uri.as_bytes())
+            .map_err(|e| AppError::Internal(format!("Failed to build QR code: {}", e)))?;
+        let image = code.render::<Luma<u8>>().min_dimensions(200, 200).build();
+        
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::ImageLuma8(image)
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .map_err(|e| AppError::Internal(format!("Failed to encode QR code: {}", e)))?;
+        
+        Ok(png.into_inner())
+    }
+    
+    pub fn generate_recovery_codes() -> Vec<String> {
+        let mut rng = rand::thread_rng();
+        (0..RECOVERY_CODE_COUNT)
+            .map(|_| {
+                let code: String = (&mut rng)
+                    .sample_iter(&Alphanumeric)
+                    .take(10)
+                    .map(|c| (c as char).to_ascii_lowercase())
+                    .collect();
+                format!("{}-{}", &code[..5], &code[5..])
+            })
+            .collect()
+    }
+    
+    /// Recovery codes are high-entropy, so a fast digest is sufficient.
+    pub fn hash_recovery_code(code: &str) -> String {
+        let normalized: String = code
+            .trim()
+            .to_ascii_lowercase()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+        format!("{:x}", Sha256::digest(normalized.as_bytes()))
+    }
+}
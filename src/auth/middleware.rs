@@ -1,77 +1,84 @@
 use axum::{
     extract::{Request, State},
-    http::{header::AUTHORIZATION, StatusCode},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
     middleware::Next,
     response::Response,
 };
-use crate::{app::AppState, auth::JwtService, database::users::UserRepository, error::AppError};
+use uuid::Uuid;
+use crate::{
+    app::AppState,
+    auth::{is_api_request, session_claims, JwtService},
+    database::{follow_requests::FollowRequestRepository, microposts::MicropostRepository, users::UserRepository},
+    error::AppError,
+    extract::Path,
+    models::User,
+    telemetry,
+};
 
-pub async fn auth_middleware(
-    State(state): State<AppState>,
-    mut request: Request,
-    next: Next,
-) -> Result<Response, AppError> {
-    let auth_header = request
-        .headers()
+/// Bearer access tokens everywhere; browser routes also accept the `session` cookie.
+/// `Ok(None)` means no credentials were presented at all.
+async fn authenticate(state: &AppState, headers: &HeaderMap, is_api: bool) -> Result<Option<User>, AppError> {
+    let bearer = headers
         .get(AUTHORIZATION)
         .and_then(|header| header.to_str().ok())
         .and_then(|header| header.strip_prefix("Bearer "));
     
-    if let Some(token) = auth_header {
-        let jwt_service = JwtService::new(&state.config.jwt_secret);
-        
-        match jwt_service.verify_token(token) {
-            Ok(claims) => {
-                if claims.token_type == "access" {
-                    let user_id = claims.sub.parse().map_err(|_| AppError::Unauthorized("Invalid user ID".to_string()))?;
-                    
-                    if let Ok(Some(user)) = UserRepository::find_by_id(&state.db, user_id).await {
-                        if user.activated {
-                            request.extensions_mut().insert(user);
-                        } else {
-                            return Err(AppError::Unauthorized("Account not activated".to_string()));
-                        }
-                    } else {
-                        return Err(AppError::Unauthorized("User not found".to_string()));
-                    }
-                } else {
-                    return Err(AppError::Unauthorized("Invalid token type".to_string()));
-                }
+    let claims = match bearer {
+        Some(token) => {
+            let claims = JwtService::new(&state.config.auth.jwt_secret).verify_token(token)?;
+            if claims.token_type != "access" {
+                return Err(AppError::Unauthorized("Invalid token type".to_string()));
             }
-            Err(_) => return Err(AppError::Unauthorized("Invalid token".to_string())),
+            claims
         }
-    } else {
-        return Err(AppError::Unauthorized("Missing authorization header".to_string()));
+        None if !is_api => match session_claims(&state.config, headers) {
+            Some(claims) => claims,
+            None => return Ok(None),
+        },
+        None => return Ok(None),
+    };
+    
+    let user_id = claims.sub.parse().map_err(|_| AppError::Unauthorized("Invalid user ID".to_string()))?;
+    let user = UserRepository::find_by_id(&state.db, user_id)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("User not found".to_string()))?;
+    
+    if !user.activated {
+        return Err(AppError::Unauthorized("Account not activated".to_string()));
     }
+    if user.tokens_revoked(claims.iat) {
+        return Err(AppError::Unauthorized("Token has been revoked".to_string()));
+    }
+    
+    Ok(Some(user))
+}
+
+pub async fn auth_middleware(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(user) = authenticate(&state, request.headers(), is_api_request(&request)).await? else {
+        let message = if is_api_request(&request) { "Missing authorization header" } else { "Please log in" };
+        return Err(AppError::Unauthorized(message.to_string()));
+    };
+    
+    telemetry::record_user_id(user.id);
+    request.extensions_mut().insert(user);
     
     Ok(next.run(request).await)
 }
 
+/// Like `auth_middleware`, but anonymous or invalid credentials just leave the
+/// request without a `User`.
 pub async fn optional_auth_middleware(
     State(state): State<AppState>,
     mut request: Request,
     next: Next,
 ) -> Response {
-    let auth_header = request
-        .headers()
-        .get(AUTHORIZATION)
-        .and_then(|header| header.to_str().ok())
-        .and_then(|header| header.strip_prefix("Bearer "));
-    
-    if let Some(token) = auth_header {
-        let jwt_service = JwtService::new(&state.config.jwt_secret);
-        
-        if let Ok(claims) = jwt_service.verify_token(token) {
-            if claims.token_type == "access" {
-                if let Ok(user_id) = claims.sub.parse() {
-                    if let Ok(Some(user)) = UserRepository::find_by_id(&state.db, user_id).await {
-                        if user.activated {
-                            request.extensions_mut().insert(user);
-                        }
-                    }
-                }
-            }
-        }
+    if let Ok(Some(user)) = authenticate(&state, request.headers(), is_api_request(&request)).await {
+        telemetry::record_user_id(user.id);
+        request.extensions_mut().insert(user);
     }
     
     next.run(request).await
@@ -81,7 +88,7 @@ pub async fn admin_middleware(
     request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    if let Some(user) = request.extensions().get::<crate::models::User>() {
+    if let Some(user) = request.extensions().get::<User>() {
         if user.admin {
             Ok(next.run(request).await)
         } else {
@@ -91,3 +98,39 @@ pub async fn admin_middleware(
         Err(StatusCode::UNAUTHORIZED)
     }
 }
+
+pub async fn private_profile_middleware(
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let owner = UserRepository::find_by_id(&state.db, user_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    
+    let viewer = request.extensions().get::<User>();
+    FollowRequestRepository::ensure_can_view(&state.db, viewer, &owner).await?;
+    
+    Ok(next.run(request).await)
+}
+
+pub async fn private_micropost_middleware(
+    State(state): State<AppState>,
+    Path(micropost_id): Path<Uuid>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let owner_id = MicropostRepository::author_id(&state.db, micropost_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Micropost not found".to_string()))?;
+    
+    let owner = UserRepository::find_by_id(&state.db, owner_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    
+    let viewer = request.extensions().get::<User>();
+    FollowRequestRepository::ensure_can_view(&state.db, viewer, &owner).await?;
+    
+    Ok(next.run(request).await)
+}
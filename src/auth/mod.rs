@@ -1,5 +1,23 @@
+pub mod cookies;
+pub mod csrf;
 pub mod jwt;
+pub mod mfa;
 pub mod middleware;
+pub mod oidc;
+pub mod password;
+pub mod session;
+pub mod throttle;
+pub mod tokens;
+pub mod webauthn;
 
+pub use cookies::*;
+pub use csrf::*;
 pub use jwt::*;
+pub use mfa::*;
 pub use middleware::*;
+pub use oidc::*;
+pub use password::*;
+pub use session::*;
+pub use throttle::*;
+pub use tokens::*;
+pub use webauthn::*;
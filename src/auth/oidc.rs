@@ -0,0 +1,142 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use openidconnect::{
+    core::{CoreAuthenticationFlow, CoreClient, CoreProviderMetadata},
+    reqwest::async_http_client,
+    AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl, Nonce, PkceCodeChallenge,
+    PkceCodeVerifier, RedirectUrl, Scope, TokenResponse,
+};
+use crate::{config::{Config, OidcProviderConfig}, error::AppError};
+
+/// Claims taken from a validated ID token.
+#[derive(Debug)]
+pub struct OidcIdentity {
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
+
+pub struct OidcAuthorization {
+    pub url: String,
+    pub state: String,
+    pub pkce_verifier: String,
+    pub nonce: String,
+}
+
+/// How long a provider's discovery document and signing keys are reused.
+const METADATA_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Discovery documents by provider name, so authorize and callback don't
+/// fetch the provider's metadata and JWKS on every request.
+#[derive(Default)]
+pub struct OidcMetadataCache {
+    entries: Mutex<HashMap<String, (Instant, CoreProviderMetadata)>>,
+}
+
+impl OidcMetadataCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    fn get(&self, provider: &str) -> Option<CoreProviderMetadata> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(provider)
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < METADATA_TTL)
+            .map(|(_, metadata)| metadata.clone())
+    }
+    
+    fn insert(&self, provider: &str, metadata: CoreProviderMetadata) {
+        self.entries.lock().unwrap().insert(provider.to_string(), (Instant::now(), metadata));
+    }
+}
+
+pub struct OidcService {
+    client: CoreClient,
+}
+
+impl OidcService {
+    /// Builds a client from the provider's discovery document, fetched at most
+    /// once per `METADATA_TTL`.
+    pub async fn discover(config: &Config, provider: &OidcProviderConfig, cache: &OidcMetadataCache) -> Result<Self, AppError> {
+        let metadata = match cache.get(&provider.name) {
+            Some(metadata) => metadata,
+            None => {
+                let issuer_url = IssuerUrl::new(provider.issuer_url.clone())
+                    .map_err(|_| AppError::Config(format!("Invalid issuer URL for {}", provider.name)))?;
+                let metadata = CoreProviderMetadata::discover_async(issuer_url, async_http_client)
+                    .await
+                    .map_err(|e| AppError::Internal(format!("OIDC discovery failed for {}: {}", provider.name, e)))?;
+                cache.insert(&provider.name, metadata.clone());
+                metadata
+            }
+        };
+        
+        let client_secret = Some(provider.client_secret.clone())
+            .filter(|secret| !secret.is_empty())
+            .map(ClientSecret::new);
+        
+        let redirect_url = RedirectUrl::new(Self::redirect_url(config, &provider.name))
+            .map_err(|_| AppError::Config("Invalid OIDC redirect URL".to_string()))?;
+        
+        let client = CoreClient::from_provider_metadata(metadata, ClientId::new(provider.client_id.clone()), client_secret)
+            .set_redirect_uri(redirect_url);
+        
+        Ok(Self { client })
+    }
+    
+    pub fn redirect_url(config: &Config, provider: &str) -> String {
+        format!("{}/api/v1/auth/oidc/{}/callback", config.server.frontend_url.trim_end_matches('/'), provider)
+    }
+    
+    /// Authorization code request with PKCE, a CSRF state and a nonce bound to the ID token.
+    pub fn authorize(&self) -> OidcAuthorization {
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        
+        let (url, state, nonce) = self
+            .client
+            .authorize_url(CoreAuthenticationFlow::AuthorizationCode, CsrfToken::new_random, Nonce::new_random)
+            .add_scope(Scope::new("email".to_string()))
+            .add_scope(Scope::new("profile".to_string()))
+            .set_pkce_challenge(pkce_challenge)
+            .url();
+        
+        OidcAuthorization {
+            url: url.to_string(),
+            state: state.secret().clone(),
+            pkce_verifier: pkce_verifier.secret().clone(),
+            nonce: nonce.secret().clone(),
+        }
+    }
+    
+    /// Exchanges the code and validates the ID token's signature, issuer, audience,
+    /// expiry and nonce.
+    pub async fn exchange(&self, code: &str, pkce_verifier: &str, nonce: &str) -> Result<OidcIdentity, AppError> {
+        let token_response = self
+            .client
+            .exchange_code(AuthorizationCode::new(code.to_string()))
+            .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier.to_string()))
+            .request_async(async_http_client)
+            .await
+            .map_err(|e| AppError::Unauthorized(format!("OIDC code exchange failed: {}", e)))?;
+        
+        let id_token = token_response
+            .id_token()
+            .ok_or_else(|| AppError::Unauthorized("Provider did not return an ID token".to_string()))?;
+        
+        let claims = id_token
+            .claims(&self.client.id_token_verifier(), &Nonce::new(nonce.to_string()))
+            .map_err(|e| AppError::Unauthorized(format!("Invalid ID token: {}", e)))?;
+        
+        Ok(OidcIdentity {
+            subject: claims.subject().to_string(),
+            email: claims.email().map(|email| email.to_string()),
+            email_verified: claims.email_verified().unwrap_or(false),
+            name: claims.name().and_then(|name| name.get(None)).map(|name| name.to_string()),
+        })
+    }
+}
@@ -0,0 +1,10 @@
+use crate::error::AppError;
+
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|e| AppError::Internal(format!("Failed to hash password: {}", e)))
+}
+
+/// A malformed stored digest counts as a mismatch rather than an error.
+pub fn verify_password(password: &str, digest: &str) -> bool {
+    bcrypt::verify(password, digest).unwrap_or(false)
+}
@@ -0,0 +1,36 @@
+use axum::http::{HeaderMap, HeaderValue};
+use uuid::Uuid;
+use crate::{
+    auth::{read_cookie, Claims, CookieOptions, JwtService},
+    config::Config,
+    error::AppError,
+};
+
+/// Browser routes sign in with a JWT in this cookie instead of a bearer token.
+pub const SESSION_COOKIE: &str = "session";
+const REMEMBER_ME_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// `Set-Cookie` value signing `user_id` in. Without "remember me" the cookie
+/// ends with the browser session, and the token itself expires after a day.
+pub fn start_session(config: &Config, user_id: Uuid, remember: bool) -> Result<HeaderValue, AppError> {
+    let token = JwtService::new(&config.auth.jwt_secret).generate_session_token(user_id, remember)?;
+    let options = CookieOptions::new(config);
+    let options = if remember { options.max_age(REMEMBER_ME_SECS) } else { options };
+    
+    Ok(options.build(SESSION_COOKIE, &token))
+}
+
+/// `Set-Cookie` value that removes the session cookie.
+pub fn end_session(config: &Config) -> HeaderValue {
+    CookieOptions::new(config).max_age(0).build(SESSION_COOKIE, "")
+}
+
+/// Verified claims of the request's session cookie. Says nothing about whether
+/// the user still exists; `authenticate` checks that.
+pub fn session_claims(config: &Config, headers: &HeaderMap) -> Option<Claims> {
+    let token = read_cookie(headers, SESSION_COOKIE).filter(|token| !token.is_empty())?;
+    JwtService::new(&config.auth.jwt_secret)
+        .verify_token(token)
+        .ok()
+        .filter(|claims| claims.token_type == "session")
+}
@@ -0,0 +1,313 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, OriginalUri, Request, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::{
+    app::AppState,
+    auth::JwtService,
+    database::{login_attempts::LoginAttemptRepository, users::UserRepository},
+    error::AppError,
+    telemetry,
+};
+
+const IP_LIMIT: usize = 20;
+const IP_WINDOW: Duration = Duration::from_secs(5 * 60);
+const ACCOUNT_LIMIT: usize = 10;
+const ACCOUNT_WINDOW: Duration = Duration::from_secs(15 * 60);
+const MAX_CONSECUTIVE_FAILURES: i32 = 5;
+const LOCKOUT_MINUTES: i64 = 15;
+const MAX_LOGIN_BODY: usize = 64 * 1024;
+
+/// In-memory sliding-window log limiter: each key keeps the instants of its recent
+/// hits, and a hit is refused once `limit` of them fall inside the window.
+#[derive(Default)]
+pub struct SlidingWindowLimiter {
+    entries: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl SlidingWindowLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Records a hit for `key`, or returns the seconds until one would be allowed.
+    pub fn check(&self, key: &str, limit: usize, window: Duration) -> Result<(), u64> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        
+        // Keep memory bounded by occasionally dropping idle keys
+        if entries.len() > 10_000 {
+            entries.retain(|_, hits| hits.back().map_or(false, |last| now.duration_since(*last) < window));
+        }
+        
+        let hits = entries.entry(key.to_string()).or_default();
+        while hits.front().map_or(false, |first| now.duration_since(*first) >= window) {
+            hits.pop_front();
+        }
+        
+        if hits.len() >= limit {
+            let oldest = *hits.front().unwrap();
+            let retry_after = window.saturating_sub(now.duration_since(oldest)).as_secs().max(1);
+            return Err(retry_after);
+        }
+        
+        hits.push_back(now);
+        Ok(())
+    }
+    
+    pub fn reset(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+/// Password logins name the account by email; second-factor steps by the
+/// pending MFA token.
+#[derive(Deserialize)]
+struct LoginIdentifier {
+    email: Option<String>,
+    mfa_token: Option<String>,
+}
+
+/// The client address from `trusted_header` (`server.client_ip_header`) when
+/// configured, else the peer address from `ConnectInfo`. For a list such as
+/// `X-Forwarded-For` the last entry is used: the one the proxy appended.
+pub fn client_ip(request: &Request, trusted_header: Option<&str>) -> String {
+    let forwarded = trusted_header
+        .and_then(|name| request.headers().get(name))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse::<IpAddr>().ok());
+    
+    forwarded
+        .or_else(|| request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip()))
+        .map_or_else(|| "unknown".to_string(), |ip| ip.to_string())
+}
+
+/// Whether a login response was a success, a failure, or neither: server
+/// errors say nothing about the credentials. HTML logins redirect on success,
+/// or show the second-factor form.
+fn login_outcome(status: StatusCode, is_api: bool) -> Option<bool> {
+    if status.is_success() || (!is_api && status.is_redirection()) {
+        Some(true)
+    } else if status.is_client_error() {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Nested routers see the URI with their prefix stripped, so check the original.
+/// `/graphql` shares the JSON API's bearer-token auth and error format.
+pub fn is_api_request(request: &Request) -> bool {
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or(request.uri().path(), |uri| uri.path());
+    
+    path.starts_with("/api/") || path == "/graphql"
+}
+
+/// Guards password and second-factor endpoints before any bcrypt work happens:
+/// per-IP and per-account sliding windows, temporary lockout after repeated
+/// failures, and an audit row for every attempt.
+pub async fn login_throttle_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let ip = client_ip(&request, state.config.server.client_ip_header.as_deref());
+    let is_api = is_api_request(&request);
+    let method = if request.uri().path().ends_with("/mfa") { "mfa" } else { "password" };
+    
+    state
+        .login_limiter
+        .check(&format!("ip:{}", ip), IP_LIMIT, IP_WINDOW)
+        .map_err(AppError::TooManyRequests)?;
+    
+    let (parts, body) = request.into_parts();
+    let bytes = to_bytes(body, MAX_LOGIN_BODY)
+        .await
+        .map_err(|_| AppError::BadRequest("Request body too large".to_string()))?;
+    
+    let is_json = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| value.starts_with("application/json"));
+    let identifier: Option<LoginIdentifier> = if is_json {
+        serde_json::from_slice(&bytes).ok()
+    } else {
+        serde_qs::from_bytes(&bytes).ok()
+    };
+    let (email, mfa_token) = identifier.map_or((None, None), |i| (i.email, i.mfa_token));
+    let user = match (&email, &mfa_token) {
+        (Some(email), _) => UserRepository::find_by_email(&state.db, email.trim()).await?,
+        (None, Some(mfa_token)) => match pending_user_id(&state, mfa_token) {
+            Some(user_id) => UserRepository::find_by_id(&state.db, user_id).await?,
+            None => None,
+        },
+        (None, None) => None,
+    };
+    // Both steps count against the same account; unknown emails still get a window
+    let email = user
+        .as_ref()
+        .map(|u| u.email.to_lowercase())
+        .or_else(|| email.map(|e| e.trim().to_lowercase()));
+    
+    if let Some(email) = &email {
+        if let Err(retry_after) = state.login_limiter.check(&format!("account:{}", email), ACCOUNT_LIMIT, ACCOUNT_WINDOW) {
+            audit(&state, user.as_ref().map(|u| u.id), Some(email), &ip, false, Some("rate_limited")).await;
+            return Err(AppError::TooManyRequests(retry_after));
+        }
+    }
+    
+    if let Some(locked_until) = user.as_ref().and_then(|u| u.locked_until).filter(|until| *until > Utc::now()) {
+        audit(&state, user.as_ref().map(|u| u.id), email.as_deref(), &ip, false, Some("locked")).await;
+        let retry_after = (locked_until - Utc::now()).num_seconds().max(1) as u64;
+        return Err(AppError::TooManyRequests(retry_after));
+    }
+    
+    let response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    
+    // A password accepted for a TOTP account only leads to the code step, so it
+    // must not clear the failures that step has been collecting
+    let completed = !(method == "password" && user.as_ref().map_or(false, |u| u.mfa_enabled));
+    
+    match login_outcome(response.status(), is_api) {
+        Some(true) => {
+            telemetry::record_login(method, true);
+            audit(&state, user.as_ref().map(|u| u.id), email.as_deref(), &ip, true, None).await;
+            if completed {
+                if let Some(user) = &user {
+                    if let Err(e) = LoginAttemptRepository::reset_failures(&state.db, user.id).await {
+                        tracing::error!("Failed to reset login failures: {}", e);
+                    }
+                }
+                if let Some(email) = &email {
+                    state.login_limiter.reset(&format!("account:{}", email));
+                }
+            }
+        }
+        Some(false) => {
+            telemetry::record_login(method, false);
+            audit(&state, user.as_ref().map(|u| u.id), email.as_deref(), &ip, false, Some("invalid_credentials")).await;
+            if let Some(user) = &user {
+                match LoginAttemptRepository::register_failure(
+                    &state.db,
+                    user.id,
+                    MAX_CONSECUTIVE_FAILURES,
+                    chrono::Duration::minutes(LOCKOUT_MINUTES),
+                )
+                .await
+                {
+                    Ok(Some(_)) => tracing::warn!("Account {} locked after repeated failed logins", user.id),
+                    Ok(None) => {}
+                    Err(e) => tracing::error!("Failed to record login failure: {}", e),
+                }
+            }
+        }
+        None => {}
+    }
+    
+    Ok(response)
+}
+
+/// The user behind a pending MFA token, if it is one and still valid.
+fn pending_user_id(state: &AppState, mfa_token: &str) -> Option<Uuid> {
+    JwtService::new(&state.config.auth.jwt_secret)
+        .verify_token(mfa_token)
+        .ok()
+        .filter(|claims| claims.token_type == "mfa_pending")
+        .and_then(|claims| claims.sub.parse().ok())
+}
+
+/// Audit failures are logged rather than surfaced so they never block a login.
+async fn audit(
+    state: &AppState,
+    user_id: Option<uuid::Uuid>,
+    email: Option<&str>,
+    ip: &str,
+    success: bool,
+    reason: Option<&str>,
+) {
+    if let Err(e) = LoginAttemptRepository::record(&state.db, user_id, email, ip, success, reason).await {
+        tracing::error!("Failed to record login attempt: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use axum::{
+        body::Body,
+        extract::{ConnectInfo, Request},
+        http::StatusCode,
+    };
+    use serde_json::json;
+    use sqlx::PgPool;
+    use crate::test_support::*;
+    use super::{client_ip, login_outcome};
+    
+    fn request(header: Option<&str>) -> Request {
+        let mut request = Request::get("/login");
+        if let Some(value) = header {
+            request = request.header("x-forwarded-for", value);
+        }
+        let mut request = request.body(Body::empty()).unwrap();
+        request.extensions_mut().insert(ConnectInfo("10.0.0.1:4000".parse::<SocketAddr>().unwrap()));
+        request
+    }
+    
+    #[test]
+    fn client_ip_only_trusts_the_configured_header() {
+        assert_eq!(client_ip(&request(Some("203.0.113.9")), None), "10.0.0.1");
+        assert_eq!(client_ip(&request(Some("203.0.113.9")), Some("x-forwarded-for")), "203.0.113.9");
+        // The proxy appends the address it saw; earlier entries came from the client
+        assert_eq!(client_ip(&request(Some("1.2.3.4, 203.0.113.9")), Some("x-forwarded-for")), "203.0.113.9");
+        assert_eq!(client_ip(&request(Some("garbage")), Some("x-forwarded-for")), "10.0.0.1");
+        assert_eq!(client_ip(&request(None), Some("x-forwarded-for")), "10.0.0.1");
+    }
+    
+    #[test]
+    fn server_errors_are_neither_success_nor_failure() {
+        assert_eq!(login_outcome(StatusCode::OK, true), Some(true));
+        assert_eq!(login_outcome(StatusCode::SEE_OTHER, false), Some(true));
+        assert_eq!(login_outcome(StatusCode::UNAUTHORIZED, false), Some(false));
+        assert_eq!(login_outcome(StatusCode::INTERNAL_SERVER_ERROR, false), None);
+        assert_eq!(login_outcome(StatusCode::SERVICE_UNAVAILABLE, true), None);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn wrong_codes_lock_the_account_even_across_password_logins(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let secret = enable_mfa(&pool, &alice).await;
+        let login = json!({ "email": alice.email, "password": PASSWORD });
+        
+        let mfa_token = post_json(&app, "/api/v1/auth/login", login.clone()).await.1["mfa_token"].clone();
+        for _ in 0..4 {
+            let (status, _) = post_json(&app, "/api/v1/auth/mfa", json!({ "mfa_token": mfa_token, "code": "000000" })).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+        
+        let mfa_token = post_json(&app, "/api/v1/auth/login", login).await.1["mfa_token"].clone();
+        let (status, _) = post_json(&app, "/api/v1/auth/mfa", json!({ "mfa_token": mfa_token, "code": "000000" })).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        
+        let code = totp_code(&secret, &alice);
+        let (status, _) = post_json(&app, "/api/v1/auth/mfa", json!({ "mfa_token": mfa_token, "code": code })).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -0,0 +1,82 @@
+use webauthn_rs::prelude::*;
+use crate::{config::Config, error::AppError, models::User};
+
+pub struct WebauthnService {
+    webauthn: Webauthn,
+}
+
+impl WebauthnService {
+    pub fn new(config: &Config) -> Result<Self, AppError> {
+        let origin = Url::parse(&config.auth.webauthn_rp_origin)
+            .map_err(|_| AppError::Config("Invalid auth.webauthn_rp_origin".to_string()))?;
+        
+        let webauthn = WebauthnBuilder::new(&config.auth.webauthn_rp_id, &origin)
+            .and_then(|builder| builder.rp_name("Sample App").build())
+            .map_err(|e| AppError::Config(format!("Invalid WebAuthn configuration: {}", e)))?;
+        
+        Ok(Self { webauthn })
+    }
+    
+    pub fn start_registration(
+        &self,
+        user: &User,
+        existing: &[Passkey],
+    ) -> Result<(CreationChallengeResponse, PasskeyRegistration), AppError> {
+        let exclude: Vec<CredentialID> = existing.iter().map(|p| p.cred_id().clone()).collect();
+        
+        self.webauthn
+            .start_passkey_registration(user.id, &user.email, &user.name, Some(exclude))
+            .map_err(|e| AppError::BadRequest(format!("Passkey registration failed: {}", e)))
+    }
+    
+    pub fn finish_registration(
+        &self,
+        credential: &RegisterPublicKeyCredential,
+        state: &PasskeyRegistration,
+    ) -> Result<Passkey, AppError> {
+        self.webauthn
+            .finish_passkey_registration(credential, state)
+            .map_err(|e| AppError::BadRequest(format!("Passkey registration failed: {}", e)))
+    }
+    
+    pub fn start_authentication(
+        &self,
+        passkeys: &[Passkey],
+    ) -> Result<(RequestChallengeResponse, PasskeyAuthentication), AppError> {
+        self.webauthn
+            .start_passkey_authentication(passkeys)
+            .map_err(|e| AppError::BadRequest(format!("Passkey authentication failed: {}", e)))
+    }
+    
+    pub fn finish_authentication(
+        &self,
+        credential: &PublicKeyCredential,
+        state: &PasskeyAuthentication,
+    ) -> Result<AuthenticationResult, AppError> {
+        self.webauthn
+            .finish_passkey_authentication(credential, state)
+            .map_err(|_| AppError::Unauthorized("Passkey authentication failed".to_string()))
+    }
+    
+    /// Authenticators that keep a signature counter must always move it forward;
+    /// a counter that goes backwards suggests a cloned credential. Synced passkeys
+    /// present 0 and are exempt.
+    pub fn check_sign_count(stored: i64, result: &AuthenticationResult) -> Result<(), AppError> {
+        let counter = i64::from(result.counter());
+        
+        if counter != 0 && counter <= stored {
+            tracing::warn!("WebAuthn sign counter regression: stored {}, presented {}", stored, counter);
+            return Err(AppError::Unauthorized("Passkey authentication failed".to_string()));
+        }
+        
+        Ok(())
+    }
+    
+    /// Stable text key for a credential id, matching its base64url JSON encoding.
+    pub fn credential_key(cred_id: &CredentialID) -> Result<String, AppError> {
+        serde_json::to_value(cred_id)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .ok_or_else(|| AppError::Internal("Failed to encode credential id".to_string()))
+    }
+}
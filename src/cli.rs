@@ -0,0 +1,164 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use uuid::Uuid;
+use crate::{config::ConfigOverrides, error::AppError};
+
+#[derive(Debug, Parser)]
+#[command(name = "rust-boilerplate", version, about = "Sample App server and management commands")]
+pub struct Cli {
+    /// Environment whose config file to load: development, test or production
+    #[arg(long = "env", global = true)]
+    pub environment: Option<String>,
+    
+    /// Directory containing default.toml and <environment>.toml
+    #[arg(long, global = true)]
+    pub config_dir: Option<String>,
+    
+    /// Address to bind, overriding server.host
+    #[arg(long, global = true)]
+    pub host: Option<String>,
+    
+    /// Port to bind, overriding server.port
+    #[arg(long, global = true)]
+    pub port: Option<u16>,
+    
+    /// Database URL, overriding database.url
+    #[arg(long, global = true)]
+    pub database_url: Option<String>,
+    
+    /// Defaults to `serve` when omitted
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    pub fn config_overrides(&self) -> Result<ConfigOverrides, AppError> {
+        Ok(ConfigOverrides {
+            environment: self.environment.as_deref().map(str::parse).transpose()?,
+            config_dir: self.config_dir.clone(),
+            host: self.host.clone(),
+            port: self.port,
+            database_url: self.database_url.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Apply pending migrations and start the HTTP server
+    Serve(ServeArgs),
+    
+    #[command(flatten)]
+    Manage(ManageCommand),
+}
+
+/// Management commands, which run against the database and exit.
+#[derive(Debug, Subcommand)]
+pub enum ManageCommand {
+    /// Apply, revert or inspect database migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateCommand,
+    },
+    
+    /// Fill the database with sample users, microposts and relationships
+    Seed(SeedArgs),
+    
+    /// Manage user accounts
+    User {
+        #[command(subcommand)]
+        action: UserCommand,
+    },
+    
+    /// Issue tokens for debugging
+    Token {
+        #[command(subcommand)]
+        action: TokenCommand,
+    },
+}
+
+#[derive(Debug, Default, Args)]
+pub struct ServeArgs {
+    /// Start without applying pending migrations
+    #[arg(long)]
+    pub skip_migrations: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateCommand {
+    /// Apply every pending migration
+    Up,
+    
+    /// Revert the most recently applied migrations
+    Down {
+        #[arg(long, default_value_t = 1)]
+        steps: usize,
+    },
+    
+    /// List migrations and whether they have been applied
+    Status,
+}
+
+#[derive(Debug, Args)]
+pub struct SeedArgs {
+    /// Number of users, including the admin example user
+    #[arg(long, default_value_t = 100)]
+    pub users: usize,
+    
+    /// Microposts created for each of the first six users
+    #[arg(long, default_value_t = 50)]
+    pub microposts_per_user: usize,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UserCommand {
+    /// Create an activated admin account
+    CreateAdmin {
+        #[arg(long)]
+        name: String,
+        
+        #[arg(long)]
+        email: String,
+        
+        /// Generated and printed when omitted
+        #[arg(long)]
+        password: Option<String>,
+    },
+    
+    /// Activate an account without the activation email
+    Activate {
+        email: String,
+    },
+    
+    /// Set a new password and clear any lockout
+    ResetPassword {
+        email: String,
+        
+        /// Generated and printed when omitted
+        #[arg(long)]
+        password: Option<String>,
+    },
+    
+    /// List accounts whose emails differ only by case, which block migration 012
+    DuplicateEmails,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TokenCommand {
+    /// Print a signed JWT for a user
+    Issue {
+        #[arg(long, required_unless_present = "user_id", conflicts_with = "user_id")]
+        email: Option<String>,
+        
+        #[arg(long)]
+        user_id: Option<Uuid>,
+        
+        #[arg(long = "type", value_enum, default_value_t = TokenKind::Access)]
+        kind: TokenKind,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum TokenKind {
+    Access,
+    Refresh,
+}
@@ -0,0 +1,58 @@
+use sqlx::{migrate::Migrate, PgPool};
+use crate::{cli::MigrateCommand, database::MIGRATOR, error::AppError};
+
+pub async fn run(action: MigrateCommand, pool: &PgPool) -> Result<(), AppError> {
+    match action {
+        MigrateCommand::Up => {
+            MIGRATOR.run(pool).await.map_err(sqlx::Error::from)?;
+            println!("Migrations are up to date");
+        }
+        MigrateCommand::Down { steps } => {
+            let mut applied = applied_versions(pool).await?;
+            applied.sort_unstable_by(|a, b| b.cmp(a));
+            
+            if applied.is_empty() {
+                println!("No migrations to revert");
+                return Ok(());
+            }
+            
+            // Everything newer than the target version is reverted, unless a
+            // migration has no down script or a revert fails partway
+            let target = applied.get(steps).copied().unwrap_or(0);
+            let undone = MIGRATOR.undo(pool, target).await.map_err(sqlx::Error::from);
+            
+            let remaining = applied_versions(pool).await?;
+            for version in applied.iter().take(steps).filter(|v| !remaining.contains(v)) {
+                println!("Reverted {}", version);
+            }
+            if let Some(version) = applied.iter().take(steps).find(|v| remaining.contains(v)) {
+                println!("Stopped at {}, which is still applied", version);
+            }
+            undone?;
+        }
+        MigrateCommand::Status => {
+            let mut conn = pool.acquire().await?;
+            conn.ensure_migrations_table().await.map_err(sqlx::Error::from)?;
+            let applied = conn.list_applied_migrations().await.map_err(sqlx::Error::from)?;
+            
+            for migration in MIGRATOR.iter().filter(|m| !m.migration_type.is_down_migration()) {
+                let status = match applied.iter().find(|a| a.version == migration.version) {
+                    Some(a) if a.checksum != migration.checksum => "changed since applied",
+                    Some(_) => "applied",
+                    None => "pending",
+                };
+                println!("{:>4}  {:<40} {}", migration.version, migration.description, status);
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+async fn applied_versions(pool: &PgPool) -> Result<Vec<i64>, AppError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await.map_err(sqlx::Error::from)?;
+    let applied = conn.list_applied_migrations().await.map_err(sqlx::Error::from)?;
+    
+    Ok(applied.into_iter().map(|m| m.version).collect())
+}
@@ -0,0 +1,17 @@
+use sqlx::PgPool;
+use crate::{cli::ManageCommand, config::Config, error::AppError};
+
+pub mod migrate;
+pub mod seed;
+pub mod token;
+pub mod user;
+
+/// Runs a management command; `serve` lives in `main`.
+pub async fn run(command: ManageCommand, pool: &PgPool, config: &Config) -> Result<(), AppError> {
+    match command {
+        ManageCommand::Migrate { action } => migrate::run(action, pool).await,
+        ManageCommand::Seed(args) => seed::run(args, pool).await,
+        ManageCommand::User { action } => user::run(action, pool).await,
+        ManageCommand::Token { action } => token::run(action, pool, config).await,
+    }
+}
@@ -0,0 +1,23 @@
+use sqlx::PgPool;
+use crate::{
+    cli::SeedArgs,
+    database::seeds::{self, SeedOptions},
+    error::AppError,
+};
+
+pub async fn run(args: SeedArgs, pool: &PgPool) -> Result<(), AppError> {
+    let options = SeedOptions {
+        users: args.users,
+        microposts_per_user: args.microposts_per_user,
+        ..SeedOptions::default()
+    };
+    
+    let summary = seeds::run(pool, options).await?;
+    println!(
+        "Seeded {} users, {} microposts and {} relationships",
+        summary.users, summary.microposts, summary.relationships
+    );
+    println!("Log in as {} with password \"foobar\"", seeds::EXAMPLE_EMAIL);
+    
+    Ok(())
+}
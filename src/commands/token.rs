@@ -0,0 +1,32 @@
+use sqlx::PgPool;
+use crate::{
+    auth::JwtService,
+    cli::{TokenCommand, TokenKind},
+    config::Config,
+    database::users::UserRepository,
+    error::AppError,
+};
+
+pub async fn run(action: TokenCommand, pool: &PgPool, config: &Config) -> Result<(), AppError> {
+    match action {
+        TokenCommand::Issue { email, user_id, kind } => {
+            let user = match (email, user_id) {
+                (Some(email), _) => UserRepository::find_by_email(pool, &email).await?,
+                (None, Some(user_id)) => UserRepository::find_by_id(pool, user_id).await?,
+                (None, None) => None,
+            }
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+            
+            let jwt_service = JwtService::new(&config.auth.jwt_secret);
+            let token = match kind {
+                TokenKind::Access => jwt_service.generate_access_token(user.id)?,
+                TokenKind::Refresh => jwt_service.generate_refresh_token(user.id)?,
+            };
+            
+            eprintln!("{:?} token for {} ({})", kind, user.email, user.id);
+            println!("{}", token);
+        }
+    }
+    
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::PgPool;
+use crate::{
+    cli::UserCommand,
+    database::{login_attempts::LoginAttemptRepository, users::UserRepository},
+    error::AppError,
+    models::User,
+};
+
+const MIN_PASSWORD_LENGTH: usize = 6;
+
+pub async fn run(action: UserCommand, pool: &PgPool) -> Result<(), AppError> {
+    match action {
+        UserCommand::CreateAdmin { name, email, password } => {
+            if name.is_empty() || name.chars().count() > 50 {
+                return Err(AppError::Validation("Name must be between 1 and 50 characters".to_string()));
+            }
+            if !email.contains('@') {
+                return Err(AppError::Validation("Email is invalid".to_string()));
+            }
+            if UserRepository::find_by_email(pool, &email).await?.is_some() {
+                return Err(AppError::Conflict(format!("A user with email {} already exists", email)));
+            }
+            
+            let (password, generated) = password_or_generate(password)?;
+            let user = UserRepository::create_external(pool, &name, &email, &hash(&password)?).await?;
+            let user = UserRepository::set_admin(pool, user.id, true).await?;
+            
+            println!("Created admin {} <{}> ({})", user.name, user.email, user.id);
+            if generated {
+                println!("Password: {}", password);
+            }
+        }
+        UserCommand::Activate { email } => {
+            let user = find_user(pool, &email).await?;
+            if user.activated {
+                println!("{} is already activated", user.email);
+            } else {
+                UserRepository::activate(pool, user.id).await?;
+                println!("Activated {}", user.email);
+            }
+        }
+        UserCommand::ResetPassword { email, password } => {
+            let user = find_user(pool, &email).await?;
+            let (password, generated) = password_or_generate(password)?;
+            
+            UserRepository::set_password(pool, user.id, &hash(&password)?).await?;
+            LoginAttemptRepository::reset_failures(pool, user.id).await?;
+            
+            println!("Password reset for {}", user.email);
+            if generated {
+                println!("Password: {}", password);
+            }
+        }
+        UserCommand::DuplicateEmails => {
+            let groups = UserRepository::duplicate_emails(pool).await?;
+            if groups.is_empty() {
+                println!("No duplicate emails");
+            }
+            for (email, users) in &groups {
+                println!("{} ({} accounts)", email, users.len());
+                for user in users {
+                    println!(
+                        "  {}  {:<30}  {:<20}  created {}{}",
+                        user.id,
+                        user.email,
+                        user.name,
+                        user.created_at.format("%Y-%m-%d"),
+                        if user.activated { "" } else { "  (not activated)" }
+                    );
+                }
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+async fn find_user(pool: &PgPool, email: &str) -> Result<User, AppError> {
+    UserRepository::find_by_email(pool, email)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No user with email {}", email)))
+}
+
+/// Uses the given password, or generates one so it never has to appear in shell history.
+fn password_or_generate(password: Option<String>) -> Result<(String, bool), AppError> {
+    match password {
+        Some(password) if password.chars().count() < MIN_PASSWORD_LENGTH => Err(AppError::Validation(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        ))),
+        Some(password) => Ok((password, false)),
+        None => Ok((rand::thread_rng().sample_iter(&Alphanumeric).take(20).map(char::from).collect(), true)),
+    }
+}
+
+fn hash(password: &str) -> Result<String, AppError> {
+    bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|e| AppError::Internal(format!("Failed to hash password: {}", e)))
+}
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+use axum::http::{header, Extensions, HeaderMap, StatusCode, Version};
+use tower_http::compression::{
+    predicate::{NotForContentType, Predicate, SizeAbove},
+    CompressionLayer,
+};
+use crate::config::Config;
+
+/// gzip, brotli or zstd, whichever the client prefers, for responses at least
+/// `compression.min_size_bytes` long whose type starts with one of
+/// `compression.content_types`. Already-encoded responses (precompressed
+/// assets) pass through untouched, and event streams are never buffered.
+pub fn compression_layer(config: &Config) -> CompressionLayer<impl Predicate> {
+    let content_types: Arc<[String]> = config.compression.content_types.clone().into();
+    let compressible = move |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| {
+        headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map_or(false, |value| content_types.iter().any(|prefix| value.starts_with(prefix.as_str())))
+    };
+    
+    CompressionLayer::new()
+        .gzip(true)
+        .br(true)
+        .zstd(true)
+        .compress_when(
+            SizeAbove::new(config.compression.min_size_bytes)
+                .and(NotForContentType::SSE)
+                .and(compressible),
+        )
+}
@@ -1,46 +1,517 @@
-use serde::Deserialize;
-use crate::error::AppError;
+use std::fmt;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize};
+use crate::{error::AppError, versioning::ApiVersion};
 
+const REDACTED: &str = "[REDACTED]";
+
+/// Application configuration, layered from lowest to highest precedence:
+/// `config/default.toml`, `config/<environment>.toml`, `APP_*` environment
+/// variables (sections separated by `__`, e.g. `APP_SERVER__PORT`), then CLI flags.
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
-    pub database_url: String,
-    pub jwt_secret: String,
+    pub environment: Environment,
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub auth: AuthConfig,
+    pub mail: MailConfig,
+    pub storage: StorageConfig,
+    pub rate_limit: RateLimitConfig,
+    pub telemetry: TelemetryConfig,
+    pub cors: CorsConfig,
+    pub security_headers: SecurityHeadersConfig,
+    pub api: ApiConfig,
+    pub compression: CompressionConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    Development,
+    Test,
+    Production,
+}
+
+impl Environment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Environment::Development => "development",
+            Environment::Test => "test",
+            Environment::Production => "production",
+        }
+    }
+}
+
+impl std::str::FromStr for Environment {
+    type Err = AppError;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "development" => Ok(Environment::Development),
+            "test" => Ok(Environment::Test),
+            "production" => Ok(Environment::Production),
+            other => Err(AppError::Config(format!("Unknown environment '{}'", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerConfig {
+    pub host: String,
     pub port: u16,
     pub frontend_url: String,
+    /// How long to wait for in-flight requests and background workers on shutdown.
+    pub shutdown_timeout_secs: u64,
+    /// How long readiness fails before the listener closes on shutdown, so load
+    /// balancers stop routing here first.
+    pub drain_delay_secs: u64,
+    /// Always answer errors with `application/problem+json`, not only when requested.
+    #[serde(default)]
+    pub problem_details: bool,
+    /// Header a trusted reverse proxy sets to the client address, such as
+    /// `X-Real-IP`. Only set it behind such a proxy: clients can send it too.
+    #[serde(default)]
+    pub client_ip_header: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct AuthConfig {
+    pub jwt_secret: String,
+    pub webauthn_rp_id: String,
+    pub webauthn_rp_origin: String,
+    #[serde(default)]
+    pub oidc_providers: Vec<OidcProviderConfig>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct OidcProviderConfig {
+    pub name: String,
+    pub display_name: String,
+    pub issuer_url: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: String,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct MailConfig {
     pub smtp_host: String,
     pub smtp_port: u16,
+    #[serde(default)]
     pub smtp_username: String,
+    #[serde(default)]
     pub smtp_password: String,
     pub from_email: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct StorageConfig {
+    pub assets_dir: String,
+    pub uploads_dir: String,
+    pub max_upload_bytes: usize,
+}
+
+/// Dynamic responses only; static assets are precompressed on disk.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompressionConfig {
+    /// Smaller bodies go out uncompressed; the encoding overhead isn't worth it.
+    pub min_size_bytes: u16,
+    /// `Content-Type` prefixes worth compressing, e.g. `text/` or `application/json`.
+    pub content_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimitConfig {
+    /// Where the microposts and relationships buckets live.
+    pub backend: RateLimitBackendKind,
+    /// Checked on every API request, so always kept in memory per instance.
+    pub api: RateLimitQuota,
+    pub microposts: RateLimitQuota,
+    pub relationships: RateLimitQuota,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitBackendKind {
+    Memory,
+    Postgres,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CorsConfig {
+    /// Exact origins allowed to call the API; `server.frontend_url` when empty.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    #[serde(default)]
+    pub exposed_headers: Vec<String>,
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight response.
+    pub max_age_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SecurityHeadersConfig {
+    pub content_security_policy: String,
+    /// `Strict-Transport-Security` max-age; the header is omitted when 0.
+    pub hsts_max_age_secs: u64,
+    pub frame_options: String,
+    pub referrer_policy: String,
+    pub permissions_policy: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiConfig {
+    /// Version served for unversioned `/api/...` paths without a vendor `Accept` type.
+    pub default_version: ApiVersion,
+    #[serde(default)]
+    pub deprecations: Vec<ApiDeprecation>,
+}
+
+/// Announced through `Deprecation`, plus `Sunset` once a removal date is known.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiDeprecation {
+    pub version: ApiVersion,
+    pub deprecated_at: DateTime<Utc>,
+    pub sunset_at: Option<DateTime<Utc>>,
+}
+
+impl ApiConfig {
+    pub fn deprecation(&self, version: ApiVersion) -> Option<&ApiDeprecation> {
+        self.deprecations.iter().find(|d| d.version == version)
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct TelemetryConfig {
+    pub log_format: LogFormat,
+    /// OTLP gRPC endpoint such as `http://localhost:4317`; traces are not exported when empty.
+    #[serde(default)]
+    pub otlp_endpoint: String,
+    pub service_name: String,
+    /// Bearer token scrapers must send to `/metrics`; the endpoint is off when empty.
+    #[serde(default)]
+    pub metrics_token: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+/// Token bucket quota: up to `burst` requests at once, refilled to `burst` every `period_secs`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RateLimitQuota {
+    pub burst: u32,
+    pub period_secs: u64,
+}
+
+impl RateLimitQuota {
+    pub fn refill_per_sec(&self) -> f64 {
+        f64::from(self.burst) / self.period_secs as f64
+    }
+}
+
+/// Values supplied on the command line; they win over every other layer.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    pub environment: Option<Environment>,
+    pub config_dir: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub database_url: Option<String>,
+}
+
 impl Config {
-    pub fn from_env() -> Result<Self, AppError> {
-        dotenvy::dotenv().ok();
-        
-        Ok(Self {
-            database_url: std::env::var("DATABASE_URL")
-                .map_err(|_| AppError::Config("DATABASE_URL must be set".to_string()))?,
-            jwt_secret: std::env::var("JWT_SECRET")
-                .map_err(|_| AppError::Config("JWT_SECRET must be set".to_string()))?,
-            port: std::env::var("PORT")
-                .unwrap_or_else(|_| "3000".to_string())
-                .parse()
-                .map_err(|_| AppError::Config("Invalid PORT".to_string()))?,
-            frontend_url: std::env::var("FRONTEND_URL")
-                .unwrap_or_else(|_| "http://localhost:3000".to_string()),
-            smtp_host: std::env::var("SMTP_HOST")
-                .unwrap_or_else(|_| "localhost".to_string()),
-            smtp_port: std::env::var("SMTP_PORT")
-                .unwrap_or_else(|_| "587".to_string())
-                .parse()
-                .map_err(|_| AppError::Config("Invalid SMTP_PORT".to_string()))?,
-            smtp_username: std::env::var("SMTP_USERNAME")
-                .unwrap_or_else(|_| "".to_string()),
-            smtp_password: std::env::var("SMTP_PASSWORD")
-                .unwrap_or_else(|_| "".to_string()),
-            from_email: std::env::var("FROM_EMAIL")
-                .unwrap_or_else(|_| "noreply@example.com".to_string()),
-        })
+    pub fn load(overrides: &ConfigOverrides) -> Result<Self, AppError> {
+        let environment = match overrides.environment {
+            Some(environment) => environment,
+            None => std::env::var("APP_ENV")
+                .unwrap_or_else(|_| "development".to_string())
+                .parse()?,
+        };
+        let config_dir = overrides.config_dir.as_deref().unwrap_or("config");
+        
+        let builder = config_rs::Config::builder()
+            .set_override("environment", environment.as_str())
+            .and_then(|b| b.set_override_option("server.host", overrides.host.clone()))
+            .and_then(|b| b.set_override_option("server.port", overrides.port))
+            .and_then(|b| b.set_override_option("database.url", overrides.database_url.clone()))
+            .map_err(|e| AppError::Config(e.to_string()))?;
+        
+        // Overrides set on the builder always take precedence over its sources
+        let source = builder
+            .add_source(config_rs::File::with_name(&format!("{}/default", config_dir)).required(false))
+            .add_source(config_rs::File::with_name(&format!("{}/{}", config_dir, environment.as_str())).required(false))
+            .add_source(config_rs::Environment::with_prefix("APP").prefix_separator("_").separator("__").try_parsing(true))
+            .build()
+            .map_err(|e| AppError::Config(e.to_string()))?;
+        let config: Config = source.clone().try_deserialize().map_err(|e| section_errors(&source, e))?;
+        
+        config.validate()?;
+        
+        Ok(config)
+    }
+    
+    /// Checks the whole configuration and reports every problem at once.
+    pub fn validate(&self) -> Result<(), AppError> {
+        let mut errors = Vec::new();
+        let production = self.environment == Environment::Production;
+        
+        if self.server.port == 0 {
+            errors.push("server.port must not be 0".to_string());
+        }
+        if self.server.shutdown_timeout_secs == 0 {
+            errors.push("server.shutdown_timeout_secs must be greater than 0".to_string());
+        }
+        if !is_http_url(&self.server.frontend_url) {
+            errors.push("server.frontend_url must be an http(s) URL".to_string());
+        }
+        if let Some(header) = &self.server.client_ip_header {
+            if header.parse::<axum::http::HeaderName>().is_err() {
+                errors.push("server.client_ip_header must be a valid header name".to_string());
+            }
+        }
+        
+        if !self.database.url.starts_with("postgres://") && !self.database.url.starts_with("postgresql://") {
+            errors.push("database.url must be a postgres:// URL".to_string());
+        }
+        if self.database.max_connections == 0 {
+            errors.push("database.max_connections must be at least 1".to_string());
+        }
+        
+        if self.auth.jwt_secret.is_empty() {
+            errors.push("auth.jwt_secret must be set".to_string());
+        } else if production && self.auth.jwt_secret.len() < 32 {
+            errors.push("auth.jwt_secret must be at least 32 characters in production".to_string());
+        }
+        if self.auth.webauthn_rp_id.is_empty() {
+            errors.push("auth.webauthn_rp_id must be set".to_string());
+        }
+        if !is_http_url(&self.auth.webauthn_rp_origin) {
+            errors.push("auth.webauthn_rp_origin must be an http(s) URL".to_string());
+        }
+        for provider in &self.auth.oidc_providers {
+            if provider.name.is_empty() || provider.client_id.is_empty() {
+                errors.push(format!("auth.oidc_providers.{}: name and client_id must be set", provider.name));
+            }
+            if !is_http_url(&provider.issuer_url) {
+                errors.push(format!("auth.oidc_providers.{}: issuer_url must be an http(s) URL", provider.name));
+            }
+        }
+        
+        if self.mail.smtp_host.is_empty() {
+            errors.push("mail.smtp_host must be set".to_string());
+        } else if production && self.mail.smtp_host == "localhost" {
+            errors.push("mail.smtp_host must not be localhost in production".to_string());
+        }
+        if !self.mail.from_email.contains('@') {
+            errors.push("mail.from_email must be an email address".to_string());
+        }
+        
+        if self.storage.max_upload_bytes == 0 {
+            errors.push("storage.max_upload_bytes must be greater than 0".to_string());
+        }
+        for content_type in &self.compression.content_types {
+            if content_type.is_empty() || content_type.contains(char::is_whitespace) {
+                errors.push(format!("compression.content_types: '{}' is not a content type prefix", content_type));
+            }
+        }
+        
+        for (name, quota) in [
+            ("api", self.rate_limit.api),
+            ("microposts", self.rate_limit.microposts),
+            ("relationships", self.rate_limit.relationships),
+        ] {
+            if quota.burst == 0 || quota.period_secs == 0 {
+                errors.push(format!("rate_limit.{}: burst and period_secs must be greater than 0", name));
+            }
+        }
+        
+        for origin in &self.cors.allowed_origins {
+            if origin == "*" {
+                errors.push("cors.allowed_origins must list exact origins, not *".to_string());
+            } else if !is_http_url(origin) || origin.ends_with('/') {
+                errors.push(format!("cors.allowed_origins: '{}' must be a scheme://host[:port] origin", origin));
+            }
+        }
+        for method in &self.cors.allowed_methods {
+            if method.parse::<axum::http::Method>().is_err() {
+                errors.push(format!("cors.allowed_methods: '{}' is not an HTTP method", method));
+            }
+        }
+        for deprecation in &self.api.deprecations {
+            if deprecation.version == ApiVersion::LATEST {
+                errors.push(format!("api.deprecations: v{} is the latest version", deprecation.version.number()));
+            }
+            if deprecation.sunset_at.map_or(false, |sunset| sunset <= deprecation.deprecated_at) {
+                errors.push(format!("api.deprecations: v{} sunset_at must be after deprecated_at", deprecation.version.number()));
+            }
+        }
+        
+        for name in self.cors.allowed_headers.iter().chain(&self.cors.exposed_headers) {
+            if name.parse::<axum::http::HeaderName>().is_err() {
+                errors.push(format!("cors: '{}' is not a valid header name", name));
+            }
+        }
+        if self.security_headers.content_security_policy.is_empty() {
+            errors.push("security_headers.content_security_policy must be set".to_string());
+        }
+        if production && self.security_headers.hsts_max_age_secs == 0 {
+            errors.push("security_headers.hsts_max_age_secs must be set in production".to_string());
+        }
+        
+        if !self.telemetry.otlp_endpoint.is_empty() && !is_http_url(&self.telemetry.otlp_endpoint) {
+            errors.push("telemetry.otlp_endpoint must be an http(s) URL".to_string());
+        }
+        if self.telemetry.service_name.is_empty() {
+            errors.push("telemetry.service_name must be set".to_string());
+        }
+        if production && !self.telemetry.metrics_token.is_empty() && self.telemetry.metrics_token.len() < 32 {
+            errors.push("telemetry.metrics_token must be at least 32 characters in production".to_string());
+        }
+        
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Config(format!("invalid configuration:\n  - {}", errors.join("\n  - "))))
+        }
+    }
+    
+    /// Origins the CORS layer accepts, defaulting to the frontend itself.
+    pub fn cors_origins(&self) -> Vec<String> {
+        if self.cors.allowed_origins.is_empty() {
+            vec![self.server.frontend_url.trim_end_matches('/').to_string()]
+        } else {
+            self.cors.allowed_origins.clone()
+        }
+    }
+    
+    pub fn oidc_provider(&self, name: &str) -> Option<&OidcProviderConfig> {
+        self.auth.oidc_providers.iter().find(|p| p.name == name)
+    }
+}
+
+/// Deserialization stops at the first bad value, so retry each section on its
+/// own to report every one of them at once.
+fn section_errors(source: &config_rs::Config, error: config_rs::ConfigError) -> AppError {
+    fn check<T: DeserializeOwned>(source: &config_rs::Config, key: &str, errors: &mut Vec<String>) {
+        if let Err(e) = source.get::<T>(key) {
+            errors.push(e.to_string());
+        }
+    }
+    
+    let mut errors = Vec::new();
+    check::<ServerConfig>(source, "server", &mut errors);
+    check::<DatabaseConfig>(source, "database", &mut errors);
+    check::<AuthConfig>(source, "auth", &mut errors);
+    check::<MailConfig>(source, "mail", &mut errors);
+    check::<StorageConfig>(source, "storage", &mut errors);
+    check::<RateLimitConfig>(source, "rate_limit", &mut errors);
+    check::<TelemetryConfig>(source, "telemetry", &mut errors);
+    check::<CorsConfig>(source, "cors", &mut errors);
+    check::<SecurityHeadersConfig>(source, "security_headers", &mut errors);
+    check::<ApiConfig>(source, "api", &mut errors);
+    check::<CompressionConfig>(source, "compression", &mut errors);
+    
+    if errors.is_empty() {
+        errors.push(error.to_string());
+    }
+    AppError::Config(format!("invalid configuration:\n  - {}", errors.join("\n  - ")))
+}
+
+fn is_http_url(value: &str) -> bool {
+    value.starts_with("http://") || value.starts_with("https://")
+}
+
+impl fmt::Debug for DatabaseConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The URL usually embeds the password, so only show where it points
+        let location = self.url.rsplit('@').next().unwrap_or(REDACTED);
+        f.debug_struct("DatabaseConfig")
+            .field("url", &format!("postgres://{}@{}", REDACTED, location))
+            .field("max_connections", &self.max_connections)
+            .finish()
+    }
+}
+
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("jwt_secret", &REDACTED)
+            .field("webauthn_rp_id", &self.webauthn_rp_id)
+            .field("webauthn_rp_origin", &self.webauthn_rp_origin)
+            .field("oidc_providers", &self.oidc_providers)
+            .finish()
+    }
+}
+
+impl fmt::Debug for OidcProviderConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OidcProviderConfig")
+            .field("name", &self.name)
+            .field("display_name", &self.display_name)
+            .field("issuer_url", &self.issuer_url)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &REDACTED)
+            .finish()
+    }
+}
+
+impl fmt::Debug for MailConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MailConfig")
+            .field("smtp_host", &self.smtp_host)
+            .field("smtp_port", &self.smtp_port)
+            .field("smtp_username", &self.smtp_username)
+            .field("smtp_password", &REDACTED)
+            .field("from_email", &self.from_email)
+            .finish()
+    }
+}
+
+impl fmt::Debug for TelemetryConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TelemetryConfig")
+            .field("log_format", &self.log_format)
+            .field("otlp_endpoint", &self.otlp_endpoint)
+            .field("service_name", &self.service_name)
+            .field("metrics_token", &REDACTED)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn every_malformed_section_is_reported() {
+        let dir = std::env::temp_dir().join(format!("config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy("config/default.toml", dir.join("default.toml")).unwrap();
+        std::fs::write(dir.join("test.toml"), "[storage]\nmax_upload_bytes = \"big\"\n\n[compression]\nmin_size_bytes = -1\n").unwrap();
+        
+        let result = Config::load(&ConfigOverrides {
+            environment: Some(Environment::Test),
+            config_dir: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+        
+        let Err(AppError::Config(message)) = result else {
+            panic!("expected a configuration error");
+        };
+        assert!(message.contains("key `storage`"), "{}", message);
+        assert!(message.contains("key `compression`"), "{}", message);
     }
 }
@@ -0,0 +1,65 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use chrono::Utc;
+use crate::models::UserBlock;
+use crate::error::AppError;
+use crate::telemetry::QueryTimer;
+
+pub struct BlockRepository;
+
+impl BlockRepository {
+    pub async fn block(pool: &PgPool, blocker_id: Uuid, blocked_id: Uuid) -> Result<UserBlock, AppError> {
+        let block = sqlx::query_as!(
+            UserBlock,
+            r#"
+            INSERT INTO user_blocks (id, blocker_id, blocked_id, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (blocker_id, blocked_id) DO UPDATE SET blocker_id = EXCLUDED.blocker_id
+            RETURNING *
+            "#,
+            Uuid::new_v4(),
+            blocker_id,
+            blocked_id,
+            Utc::now()
+        )
+        .fetch_one(pool)
+        .timed("blocks.block")
+        .await?;
+        
+        Ok(block)
+    }
+    
+    pub async fn unblock(pool: &PgPool, blocker_id: Uuid, blocked_id: Uuid) -> Result<(), AppError> {
+        sqlx::query!(
+            "DELETE FROM user_blocks WHERE blocker_id = $1 AND blocked_id = $2",
+            blocker_id,
+            blocked_id
+        )
+        .execute(pool)
+        .timed("blocks.unblock")
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// True when `user_id` has blocked, or is blocked by, any of `others`.
+    pub async fn is_blocked_with_any(pool: &PgPool, user_id: Uuid, others: &[Uuid]) -> Result<bool, AppError> {
+        let blocked = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM user_blocks
+                WHERE (blocker_id = $1 AND blocked_id = ANY($2))
+                   OR (blocked_id = $1 AND blocker_id = ANY($2))
+            )
+            "#,
+            user_id,
+            others
+        )
+        .fetch_one(pool)
+        .timed("blocks.is_blocked_with_any")
+        .await?
+        .unwrap_or(false);
+        
+        Ok(blocked)
+    }
+}
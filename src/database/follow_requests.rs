@@ -0,0 +1,134 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use chrono::Utc;
+use crate::models::{Relationship, User, RELATIONSHIP_ACCEPTED, RELATIONSHIP_PENDING};
+use crate::error::AppError;
+use crate::telemetry::QueryTimer;
+
+pub struct FollowRequestRepository;
+
+impl FollowRequestRepository {
+    /// Follows `followed`, leaving the relationship pending when the account is private.
+    pub async fn follow(pool: &PgPool, follower_id: Uuid, followed: &User) -> Result<Relationship, AppError> {
+        let (status, accepted_at) = if followed.is_private {
+            (RELATIONSHIP_PENDING, None)
+        } else {
+            (RELATIONSHIP_ACCEPTED, Some(Utc::now()))
+        };
+        
+        let relationship = sqlx::query_as!(
+            Relationship,
+            r#"
+            INSERT INTO relationships (id, follower_id, followed_id, status, accepted_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+            Uuid::new_v4(),
+            follower_id,
+            followed.id,
+            status,
+            accepted_at,
+            Utc::now()
+        )
+        .fetch_one(pool)
+        .timed("follow_requests.follow")
+        .await?;
+        
+        Ok(relationship)
+    }
+    
+    pub async fn list_pending(pool: &PgPool, followed_id: Uuid) -> Result<Vec<Relationship>, AppError> {
+        let requests = sqlx::query_as!(
+            Relationship,
+            "SELECT * FROM relationships WHERE followed_id = $1 AND status = 'pending' ORDER BY created_at DESC",
+            followed_id
+        )
+        .fetch_all(pool)
+        .timed("follow_requests.list_pending")
+        .await?;
+        
+        Ok(requests)
+    }
+    
+    pub async fn approve(pool: &PgPool, id: Uuid, followed_id: Uuid) -> Result<Relationship, AppError> {
+        let relationship = sqlx::query_as!(
+            Relationship,
+            "UPDATE relationships SET status = 'accepted', accepted_at = $1 WHERE id = $2 AND followed_id = $3 AND status = 'pending' RETURNING *",
+            Utc::now(),
+            id,
+            followed_id
+        )
+        .fetch_optional(pool)
+        .timed("follow_requests.approve")
+        .await?;
+        
+        relationship.ok_or_else(|| AppError::NotFound("Follow request not found".to_string()))
+    }
+    
+    /// Approves every pending request, used when an account is switched back to public.
+    pub async fn approve_all(pool: &PgPool, followed_id: Uuid) -> Result<u64, AppError> {
+        let result = sqlx::query!(
+            "UPDATE relationships SET status = 'accepted', accepted_at = $1 WHERE followed_id = $2 AND status = 'pending'",
+            Utc::now(),
+            followed_id
+        )
+        .execute(pool)
+        .timed("follow_requests.approve_all")
+        .await?;
+        
+        Ok(result.rows_affected())
+    }
+    
+    pub async fn deny(pool: &PgPool, id: Uuid, followed_id: Uuid) -> Result<(), AppError> {
+        let result = sqlx::query!(
+            "DELETE FROM relationships WHERE id = $1 AND followed_id = $2 AND status = 'pending'",
+            id,
+            followed_id
+        )
+        .execute(pool)
+        .timed("follow_requests.deny")
+        .await?;
+        
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Follow request not found".to_string()));
+        }
+        
+        Ok(())
+    }
+    
+    pub async fn is_following(pool: &PgPool, follower_id: Uuid, followed_id: Uuid) -> Result<bool, AppError> {
+        let following = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM relationships WHERE follower_id = $1 AND followed_id = $2 AND status = 'accepted')",
+            follower_id,
+            followed_id
+        )
+        .fetch_one(pool)
+        .timed("follow_requests.is_following")
+        .await?
+        .unwrap_or(false);
+        
+        Ok(following)
+    }
+    
+    /// Public accounts are visible to everyone; private ones only to the owner,
+    /// admins and approved followers.
+    pub async fn can_view(pool: &PgPool, viewer: Option<&User>, owner: &User) -> Result<bool, AppError> {
+        if !owner.is_private {
+            return Ok(true);
+        }
+        
+        match viewer {
+            Some(viewer) if viewer.id == owner.id || viewer.admin => Ok(true),
+            Some(viewer) => Self::is_following(pool, viewer.id, owner.id).await,
+            None => Ok(false),
+        }
+    }
+    
+    pub async fn ensure_can_view(pool: &PgPool, viewer: Option<&User>, owner: &User) -> Result<(), AppError> {
+        if Self::can_view(pool, viewer, owner).await? {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden("This account is private".to_string()))
+        }
+    }
+}
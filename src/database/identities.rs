@@ -0,0 +1,123 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use chrono::{Duration, Utc};
+use crate::models::{OidcLoginState, User, UserIdentity};
+use crate::error::AppError;
+use crate::telemetry::QueryTimer;
+
+pub struct IdentityRepository;
+
+impl IdentityRepository {
+    pub async fn find_user(pool: &PgPool, provider: &str, subject: &str) -> Result<Option<User>, AppError> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            SELECT u.* FROM users u
+            JOIN user_identities i ON i.user_id = u.id
+            WHERE i.provider = $1 AND i.subject = $2
+            "#,
+            provider,
+            subject
+        )
+        .fetch_optional(pool)
+        .timed("identities.find_user")
+        .await?;
+        
+        Ok(user)
+    }
+    
+    pub async fn link(pool: &PgPool, user_id: Uuid, provider: &str, subject: &str, email: Option<&str>) -> Result<UserIdentity, AppError> {
+        let identity = sqlx::query_as!(
+            UserIdentity,
+            r#"
+            INSERT INTO user_identities (id, user_id, provider, subject, email, last_login_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            provider,
+            subject,
+            email,
+            Utc::now(),
+            Utc::now()
+        )
+        .fetch_one(pool)
+        .timed("identities.link")
+        .await?;
+        
+        Ok(identity)
+    }
+    
+    pub async fn record_login(pool: &PgPool, provider: &str, subject: &str) -> Result<(), AppError> {
+        sqlx::query!(
+            "UPDATE user_identities SET last_login_at = $1 WHERE provider = $2 AND subject = $3",
+            Utc::now(),
+            provider,
+            subject
+        )
+        .execute(pool)
+        .timed("identities.record_login")
+        .await?;
+        
+        Ok(())
+    }
+    
+    pub async fn list_for_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<UserIdentity>, AppError> {
+        let identities = sqlx::query_as!(
+            UserIdentity,
+            "SELECT * FROM user_identities WHERE user_id = $1 ORDER BY created_at",
+            user_id
+        )
+        .fetch_all(pool)
+        .timed("identities.list_for_user")
+        .await?;
+        
+        Ok(identities)
+    }
+    
+    /// Authorization requests must be completed within ten minutes.
+    pub async fn create_login_state(pool: &PgPool, state: &str, provider: &str, pkce_verifier: &str, nonce: &str) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO oidc_login_states (state, provider, pkce_verifier, nonce, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            state,
+            provider,
+            pkce_verifier,
+            nonce,
+            Utc::now() + Duration::minutes(10),
+            Utc::now()
+        )
+        .execute(pool)
+        .timed("identities.create_login_state")
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Deletes and returns the stored request so a callback can't be replayed.
+    pub async fn take_login_state(pool: &PgPool, state: &str, provider: &str) -> Result<Option<OidcLoginState>, AppError> {
+        let login_state = sqlx::query_as!(
+            OidcLoginState,
+            "DELETE FROM oidc_login_states WHERE state = $1 AND provider = $2 AND expires_at > NOW() RETURNING *",
+            state,
+            provider
+        )
+        .fetch_optional(pool)
+        .timed("identities.take_login_state")
+        .await?;
+        
+        Ok(login_state)
+    }
+    
+    pub async fn delete_expired_login_states(pool: &PgPool) -> Result<u64, AppError> {
+        let result = sqlx::query!("DELETE FROM oidc_login_states WHERE expires_at <= NOW()")
+            .execute(pool)
+            .timed("identities.delete_expired_login_states")
+            .await?;
+        
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use sqlx::PgPool;
+use uuid::Uuid;
+use chrono::Utc;
+use crate::models::Like;
+use crate::error::AppError;
+use crate::telemetry::QueryTimer;
+
+pub struct LikeRepository;
+
+impl LikeRepository {
+    /// Liking twice is a no-op that returns the existing like.
+    pub async fn like(pool: &PgPool, user_id: Uuid, micropost_id: Uuid) -> Result<Like, AppError> {
+        let like = sqlx::query_as!(
+            Like,
+            r#"
+            INSERT INTO likes (id, user_id, micropost_id, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, micropost_id) DO UPDATE SET user_id = EXCLUDED.user_id
+            RETURNING *
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            micropost_id,
+            Utc::now()
+        )
+        .fetch_one(pool)
+        .timed("likes.like")
+        .await?;
+        
+        Ok(like)
+    }
+    
+    pub async fn unlike(pool: &PgPool, user_id: Uuid, micropost_id: Uuid) -> Result<(), AppError> {
+        sqlx::query!(
+            "DELETE FROM likes WHERE user_id = $1 AND micropost_id = $2",
+            user_id,
+            micropost_id
+        )
+        .execute(pool)
+        .timed("likes.unlike")
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Like counts for many microposts in one query; microposts without likes are absent.
+    pub async fn counts(pool: &PgPool, micropost_ids: &[Uuid]) -> Result<HashMap<Uuid, i64>, AppError> {
+        let rows = sqlx::query!(
+            r#"SELECT micropost_id, COUNT(*) AS "count!" FROM likes WHERE micropost_id = ANY($1) GROUP BY micropost_id"#,
+            micropost_ids
+        )
+        .fetch_all(pool)
+        .timed("likes.counts")
+        .await?;
+        
+        Ok(rows.into_iter().map(|row| (row.micropost_id, row.count)).collect())
+    }
+    
+    /// Which of `micropost_ids` the user has liked.
+    pub async fn liked_by(pool: &PgPool, user_id: Uuid, micropost_ids: &[Uuid]) -> Result<Vec<Uuid>, AppError> {
+        let liked = sqlx::query_scalar!(
+            "SELECT micropost_id FROM likes WHERE user_id = $1 AND micropost_id = ANY($2)",
+            user_id,
+            micropost_ids
+        )
+        .fetch_all(pool)
+        .timed("likes.liked_by")
+        .await?;
+        
+        Ok(liked)
+    }
+}
@@ -0,0 +1,76 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use chrono::{DateTime, Duration, Utc};
+use crate::error::AppError;
+use crate::telemetry::QueryTimer;
+
+pub struct LoginAttemptRepository;
+
+impl LoginAttemptRepository {
+    pub async fn record(
+        pool: &PgPool,
+        user_id: Option<Uuid>,
+        email: Option<&str>,
+        ip_address: &str,
+        success: bool,
+        reason: Option<&str>,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO login_attempts (id, user_id, email, ip_address, success, reason, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            email.map(|e| e.to_lowercase()),
+            ip_address,
+            success,
+            reason,
+            Utc::now()
+        )
+        .execute(pool)
+        .timed("login_attempts.record")
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Counts a failure and locks the account once `max_failures` is reached.
+    /// Returns the lock expiry when this failure triggered a lockout.
+    pub async fn register_failure(
+        pool: &PgPool,
+        user_id: Uuid,
+        max_failures: i32,
+        lockout: Duration,
+    ) -> Result<Option<DateTime<Utc>>, AppError> {
+        let locked_until = sqlx::query_scalar!(
+            r#"
+            UPDATE users SET
+                failed_login_attempts = CASE WHEN failed_login_attempts + 1 >= $1 THEN 0 ELSE failed_login_attempts + 1 END,
+                locked_until = CASE WHEN failed_login_attempts + 1 >= $1 THEN $2 ELSE locked_until END
+            WHERE id = $3
+            RETURNING locked_until
+            "#,
+            max_failures,
+            Utc::now() + lockout,
+            user_id
+        )
+        .fetch_one(pool)
+        .timed("login_attempts.register_failure")
+        .await?;
+        
+        Ok(locked_until.filter(|until| *until > Utc::now()))
+    }
+    
+    pub async fn reset_failures(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
+        sqlx::query!(
+            "UPDATE users SET failed_login_attempts = 0, locked_until = NULL WHERE id = $1",
+            user_id
+        )
+        .execute(pool)
+        .timed("login_attempts.reset_failures")
+        .await?;
+        
+        Ok(())
+    }
+}
@@ -0,0 +1,336 @@
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use chrono::Utc;
+use crate::models::{Conversation, ConversationParticipant, Message};
+use crate::error::AppError;
+use crate::utils::pagination::PaginationParams;
+use crate::telemetry::QueryTimer;
+
+pub struct MessageRepository;
+
+impl MessageRepository {
+    /// Creates a conversation between `creator_id` and `participant_ids` with its first message.
+    pub async fn create_conversation(
+        pool: &PgPool,
+        creator_id: Uuid,
+        participant_ids: &[Uuid],
+        content: &str,
+    ) -> Result<(Conversation, Message), AppError> {
+        let mut tx = pool.begin().await?;
+        let created = Self::insert_conversation(&mut tx, creator_id, participant_ids, content).await?;
+        tx.commit().await?;
+        
+        Ok(created)
+    }
+    
+    /// Sends `content` to the one-to-one conversation between `sender_id` and
+    /// `other_id`, starting it if there is none yet; the flag says whether it was
+    /// started. Concurrent first messages wait on an advisory lock keyed by the
+    /// pair, so they can't both start one.
+    pub async fn send_direct(pool: &PgPool, sender_id: Uuid, other_id: Uuid, content: &str) -> Result<(Conversation, bool), AppError> {
+        let mut tx = pool.begin().await?;
+        let (low, high) = if sender_id < other_id { (sender_id, other_id) } else { (other_id, sender_id) };
+        
+        sqlx::query!(
+            "SELECT pg_advisory_xact_lock(hashtextextended($1, 0))",
+            format!("direct_conversation:{}:{}", low, high)
+        )
+        .execute(&mut *tx)
+        .timed("messages.send_direct")
+        .await?;
+        
+        let sent = match Self::find_direct_conversation(&mut tx, sender_id, other_id).await? {
+            Some(conversation) => {
+                Self::insert_message(&mut tx, conversation.id, sender_id, content).await?;
+                (conversation, false)
+            }
+            None => (Self::insert_conversation(&mut tx, sender_id, &[other_id], content).await?.0, true),
+        };
+        
+        tx.commit().await?;
+        
+        Ok(sent)
+    }
+    
+    async fn insert_conversation(
+        conn: &mut PgConnection,
+        creator_id: Uuid,
+        participant_ids: &[Uuid],
+        content: &str,
+    ) -> Result<(Conversation, Message), AppError> {
+        let now = Utc::now();
+        
+        let conversation = sqlx::query_as!(
+            Conversation,
+            "INSERT INTO conversations (id, created_by, created_at, updated_at) VALUES ($1, $2, $3, $4) RETURNING *",
+            Uuid::new_v4(),
+            creator_id,
+            now,
+            now
+        )
+        .fetch_one(&mut *conn)
+        .timed("messages.create_conversation")
+        .await?;
+        
+        sqlx::query!(
+            "INSERT INTO conversation_participants (conversation_id, user_id, last_read_at, joined_at) VALUES ($1, $2, $3, $4)",
+            conversation.id,
+            creator_id,
+            now,
+            now
+        )
+        .execute(&mut *conn)
+        .timed("messages.create_conversation")
+        .await?;
+        
+        for user_id in participant_ids {
+            sqlx::query!(
+                "INSERT INTO conversation_participants (conversation_id, user_id, joined_at) VALUES ($1, $2, $3)",
+                conversation.id,
+                user_id,
+                now
+            )
+            .execute(&mut *conn)
+            .timed("messages.create_conversation")
+            .await?;
+        }
+        
+        let message = sqlx::query_as!(
+            Message,
+            "INSERT INTO messages (id, conversation_id, sender_id, content, created_at) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+            Uuid::new_v4(),
+            conversation.id,
+            creator_id,
+            content,
+            now
+        )
+        .fetch_one(&mut *conn)
+        .timed("messages.create_conversation")
+        .await?;
+        
+        Ok((conversation, message))
+    }
+    
+    /// Finds the existing one-to-one conversation between two users, if any.
+    async fn find_direct_conversation(conn: &mut PgConnection, user_a: Uuid, user_b: Uuid) -> Result<Option<Conversation>, AppError> {
+        let conversation = sqlx::query_as!(
+            Conversation,
+            r#"
+            SELECT c.* FROM conversations c
+            WHERE (SELECT COUNT(*) FROM conversation_participants p WHERE p.conversation_id = c.id) = 2
+              AND EXISTS (SELECT 1 FROM conversation_participants p WHERE p.conversation_id = c.id AND p.user_id = $1)
+              AND EXISTS (SELECT 1 FROM conversation_participants p WHERE p.conversation_id = c.id AND p.user_id = $2)
+            LIMIT 1
+            "#,
+            user_a,
+            user_b
+        )
+        .fetch_optional(conn)
+        .timed("messages.find_direct_conversation")
+        .await?;
+        
+        Ok(conversation)
+    }
+    
+    pub async fn find_conversation(pool: &PgPool, id: Uuid) -> Result<Option<Conversation>, AppError> {
+        let conversation = sqlx::query_as!(
+            Conversation,
+            "SELECT * FROM conversations WHERE id = $1",
+            id
+        )
+        .fetch_optional(pool)
+        .timed("messages.find_conversation")
+        .await?;
+        
+        Ok(conversation)
+    }
+    
+    pub async fn list_conversations(pool: &PgPool, user_id: Uuid, params: &PaginationParams) -> Result<Vec<Conversation>, AppError> {
+        let offset = (params.page - 1) * params.per_page;
+        
+        let conversations = sqlx::query_as!(
+            Conversation,
+            r#"
+            SELECT c.* FROM conversations c
+            JOIN conversation_participants p ON p.conversation_id = c.id
+            WHERE p.user_id = $1
+            ORDER BY c.updated_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            user_id,
+            params.per_page as i64,
+            offset as i64
+        )
+        .fetch_all(pool)
+        .timed("messages.list_conversations")
+        .await?;
+        
+        Ok(conversations)
+    }
+    
+    pub async fn count_conversations(pool: &PgPool, user_id: Uuid) -> Result<usize, AppError> {
+        let total = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM conversation_participants WHERE user_id = $1",
+            user_id
+        )
+        .fetch_one(pool)
+        .timed("messages.count_conversations")
+        .await?
+        .unwrap_or(0) as usize;
+        
+        Ok(total)
+    }
+    
+    pub async fn participants(pool: &PgPool, conversation_id: Uuid) -> Result<Vec<ConversationParticipant>, AppError> {
+        let participants = sqlx::query_as!(
+            ConversationParticipant,
+            "SELECT * FROM conversation_participants WHERE conversation_id = $1 ORDER BY joined_at",
+            conversation_id
+        )
+        .fetch_all(pool)
+        .timed("messages.participants")
+        .await?;
+        
+        Ok(participants)
+    }
+    
+    pub async fn is_participant(pool: &PgPool, conversation_id: Uuid, user_id: Uuid) -> Result<bool, AppError> {
+        let participant = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM conversation_participants WHERE conversation_id = $1 AND user_id = $2)",
+            conversation_id,
+            user_id
+        )
+        .fetch_one(pool)
+        .timed("messages.is_participant")
+        .await?
+        .unwrap_or(false);
+        
+        Ok(participant)
+    }
+    
+    /// Adds a message and marks it read for the sender.
+    pub async fn create_message(pool: &PgPool, conversation_id: Uuid, sender_id: Uuid, content: &str) -> Result<Message, AppError> {
+        let mut tx = pool.begin().await?;
+        let message = Self::insert_message(&mut tx, conversation_id, sender_id, content).await?;
+        tx.commit().await?;
+        
+        Ok(message)
+    }
+    
+    async fn insert_message(conn: &mut PgConnection, conversation_id: Uuid, sender_id: Uuid, content: &str) -> Result<Message, AppError> {
+        let now = Utc::now();
+        
+        let message = sqlx::query_as!(
+            Message,
+            "INSERT INTO messages (id, conversation_id, sender_id, content, created_at) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+            Uuid::new_v4(),
+            conversation_id,
+            sender_id,
+            content,
+            now
+        )
+        .fetch_one(&mut *conn)
+        .timed("messages.create_message")
+        .await?;
+        
+        sqlx::query!(
+            "UPDATE conversations SET updated_at = $1 WHERE id = $2",
+            now,
+            conversation_id
+        )
+        .execute(&mut *conn)
+        .timed("messages.create_message")
+        .await?;
+        
+        sqlx::query!(
+            "UPDATE conversation_participants SET last_read_at = $1 WHERE conversation_id = $2 AND user_id = $3",
+            now,
+            conversation_id,
+            sender_id
+        )
+        .execute(&mut *conn)
+        .timed("messages.create_message")
+        .await?;
+        
+        Ok(message)
+    }
+    
+    /// Message history, newest first.
+    pub async fn list_messages(pool: &PgPool, conversation_id: Uuid, params: &PaginationParams) -> Result<Vec<Message>, AppError> {
+        let offset = (params.page - 1) * params.per_page;
+        
+        let messages = sqlx::query_as!(
+            Message,
+            "SELECT * FROM messages WHERE conversation_id = $1 ORDER BY created_at DESC LIMIT $2 OFFSET $3",
+            conversation_id,
+            params.per_page as i64,
+            offset as i64
+        )
+        .fetch_all(pool)
+        .timed("messages.list_messages")
+        .await?;
+        
+        Ok(messages)
+    }
+    
+    pub async fn count_messages(pool: &PgPool, conversation_id: Uuid) -> Result<usize, AppError> {
+        let total = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM messages WHERE conversation_id = $1",
+            conversation_id
+        )
+        .fetch_one(pool)
+        .timed("messages.count_messages")
+        .await?
+        .unwrap_or(0) as usize;
+        
+        Ok(total)
+    }
+    
+    pub async fn last_message(pool: &PgPool, conversation_id: Uuid) -> Result<Option<Message>, AppError> {
+        let message = sqlx::query_as!(
+            Message,
+            "SELECT * FROM messages WHERE conversation_id = $1 ORDER BY created_at DESC LIMIT 1",
+            conversation_id
+        )
+        .fetch_optional(pool)
+        .timed("messages.last_message")
+        .await?;
+        
+        Ok(message)
+    }
+    
+    pub async fn mark_read(pool: &PgPool, conversation_id: Uuid, user_id: Uuid) -> Result<ConversationParticipant, AppError> {
+        let participant = sqlx::query_as!(
+            ConversationParticipant,
+            "UPDATE conversation_participants SET last_read_at = $1 WHERE conversation_id = $2 AND user_id = $3 RETURNING *",
+            Utc::now(),
+            conversation_id,
+            user_id
+        )
+        .fetch_optional(pool)
+        .timed("messages.mark_read")
+        .await?;
+        
+        participant.ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))
+    }
+    
+    pub async fn unread_count(pool: &PgPool, conversation_id: Uuid, user_id: Uuid) -> Result<i64, AppError> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) FROM messages m
+            JOIN conversation_participants p ON p.conversation_id = m.conversation_id AND p.user_id = $2
+            WHERE m.conversation_id = $1
+              AND m.sender_id != $2
+              AND (p.last_read_at IS NULL OR m.created_at > p.last_read_at)
+            "#,
+            conversation_id,
+            user_id
+        )
+        .fetch_one(pool)
+        .timed("messages.unread_count")
+        .await?
+        .unwrap_or(0);
+        
+        Ok(count)
+    }
+}
@@ -0,0 +1,153 @@
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use chrono::Utc;
+use crate::models::User;
+use crate::error::AppError;
+use crate::telemetry::QueryTimer;
+
+pub struct MfaRepository;
+
+impl MfaRepository {
+    /// Stores a secret awaiting confirmation; MFA stays off until `enable`.
+    pub async fn set_secret(pool: &PgPool, user_id: Uuid, secret: &str) -> Result<User, AppError> {
+        let user = sqlx::query_as!(
+            User,
+            "UPDATE users SET mfa_secret = $1, mfa_enabled = false, mfa_enabled_at = NULL, updated_at = $2 WHERE id = $3 RETURNING *",
+            secret,
+            Utc::now(),
+            user_id
+        )
+        .fetch_one(pool)
+        .timed("mfa.set_secret")
+        .await?;
+        
+        Ok(user)
+    }
+    
+    /// Turns MFA on and replaces any previous recovery codes.
+    pub async fn enable(pool: &PgPool, user_id: Uuid, recovery_code_digests: &[String]) -> Result<User, AppError> {
+        let mut tx = pool.begin().await?;
+        
+        let user = sqlx::query_as!(
+            User,
+            "UPDATE users SET mfa_enabled = true, mfa_enabled_at = $1, updated_at = $2 WHERE id = $3 RETURNING *",
+            Utc::now(),
+            Utc::now(),
+            user_id
+        )
+        .fetch_one(&mut *tx)
+        .timed("mfa.enable")
+        .await?;
+        
+        sqlx::query!("DELETE FROM mfa_recovery_codes WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .timed("mfa.enable")
+            .await?;
+        
+        for digest in recovery_code_digests {
+            sqlx::query!(
+                "INSERT INTO mfa_recovery_codes (id, user_id, code_digest, created_at) VALUES ($1, $2, $3, $4)",
+                Uuid::new_v4(),
+                user_id,
+                digest,
+                Utc::now()
+            )
+            .execute(&mut *tx)
+            .timed("mfa.enable")
+            .await?;
+        }
+        
+        tx.commit().await?;
+        
+        Ok(user)
+    }
+    
+    /// Clears the secret and recovery codes; used when a user turns MFA off.
+    /// Passkeys are managed separately and stay registered.
+    pub async fn reset(pool: &PgPool, user_id: Uuid) -> Result<User, AppError> {
+        let mut tx = pool.begin().await?;
+        let user = Self::clear_totp(&mut tx, user_id).await?;
+        tx.commit().await?;
+        
+        Ok(user)
+    }
+    
+    /// Admin reset for a locked-out user: `reset` plus every registered passkey,
+    /// since a passkey alone is also enforced as a second factor.
+    pub async fn reset_all(pool: &PgPool, user_id: Uuid) -> Result<User, AppError> {
+        let mut tx = pool.begin().await?;
+        let user = Self::clear_totp(&mut tx, user_id).await?;
+        
+        sqlx::query!("DELETE FROM webauthn_credentials WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .timed("mfa.reset_all")
+            .await?;
+        
+        tx.commit().await?;
+        
+        Ok(user)
+    }
+    
+    async fn clear_totp(conn: &mut PgConnection, user_id: Uuid) -> Result<User, AppError> {
+        let user = sqlx::query_as!(
+            User,
+            "UPDATE users SET mfa_enabled = false, mfa_secret = NULL, mfa_enabled_at = NULL, mfa_last_used_step = NULL, updated_at = $1 WHERE id = $2 RETURNING *",
+            Utc::now(),
+            user_id
+        )
+        .fetch_optional(&mut *conn)
+        .timed("mfa.reset")
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+        
+        sqlx::query!("DELETE FROM mfa_recovery_codes WHERE user_id = $1", user_id)
+            .execute(&mut *conn)
+            .timed("mfa.reset")
+            .await?;
+        
+        Ok(user)
+    }
+    
+    /// Records `step` as the last accepted TOTP step. Returns false if the same
+    /// or a later step was already used, e.g. by a concurrent request.
+    pub async fn record_step(pool: &PgPool, user_id: Uuid, step: i64) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            "UPDATE users SET mfa_last_used_step = $1 WHERE id = $2 AND (mfa_last_used_step IS NULL OR mfa_last_used_step < $1)",
+            step,
+            user_id
+        )
+        .execute(pool)
+        .timed("mfa.record_step")
+        .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    /// Marks a matching unused recovery code as spent. Returns false if none matched.
+    pub async fn consume_recovery_code(pool: &PgPool, user_id: Uuid, code_digest: &str) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            "UPDATE mfa_recovery_codes SET used_at = $1 WHERE user_id = $2 AND code_digest = $3 AND used_at IS NULL",
+            Utc::now(),
+            user_id,
+            code_digest
+        )
+        .execute(pool)
+        .timed("mfa.consume_recovery_code")
+        .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    pub async fn remaining_recovery_codes(pool: &PgPool, user_id: Uuid) -> Result<i64, AppError> {
+        let count = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM mfa_recovery_codes WHERE user_id = $1 AND used_at IS NULL",
+            user_id
+        )
+        .fetch_one(pool)
+        .timed("mfa.remaining_recovery_codes")
+        .await?
+        .unwrap_or(0);
+        
+        Ok(count)
+    }
+}
@@ -0,0 +1,318 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use crate::models::{CreateMicropostRequest, Micropost, UpdateMicropostRequest, User};
+use crate::error::AppError;
+use crate::database::partial_update::PartialUpdate;
+use crate::utils::pagination::{Pagination, PaginationParams};
+use crate::telemetry::QueryTimer;
+
+pub struct MicropostRepository;
+
+/// A micropost's own `updated_at` and its author's, which together date a
+/// representation that embeds the author.
+pub struct MicropostRevisions {
+    pub updated_at: DateTime<Utc>,
+    pub author_updated_at: DateTime<Utc>,
+}
+
+/// The post count and latest post and author `updated_at` across a feed,
+/// which adds, edits and deletes all move.
+pub struct FeedFingerprint {
+    pub count: i64,
+    pub latest_post: Option<DateTime<Utc>>,
+    pub latest_author: Option<DateTime<Utc>>,
+}
+
+impl MicropostRepository {
+    #[tracing::instrument(name = "microposts.create", skip_all, fields(user_id = %user_id), err)]
+    pub async fn create(pool: &PgPool, user_id: Uuid, req: &CreateMicropostRequest) -> Result<Micropost, AppError> {
+        let micropost = sqlx::query_as!(
+            Micropost,
+            r#"
+            INSERT INTO microposts (id, content, user_id, picture, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            RETURNING *
+            "#,
+            Uuid::new_v4(),
+            req.content,
+            user_id,
+            req.picture,
+            Utc::now()
+        )
+        .fetch_one(pool)
+        .timed("microposts.create")
+        .await?;
+        
+        Ok(micropost)
+    }
+    
+    #[tracing::instrument(name = "microposts.find_by_id", skip_all, fields(micropost_id = %id), err)]
+    pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Micropost>, AppError> {
+        let micropost = sqlx::query_as!(
+            Micropost,
+            "SELECT * FROM microposts WHERE id = $1",
+            id
+        )
+        .fetch_optional(pool)
+        .timed("microposts.find_by_id")
+        .await?;
+        
+        Ok(micropost)
+    }
+    
+    /// The author of a micropost, for visibility checks that don't need the post itself.
+    #[tracing::instrument(name = "microposts.author_id", skip_all, fields(micropost_id = %id), err)]
+    pub async fn author_id(pool: &PgPool, id: Uuid) -> Result<Option<Uuid>, AppError> {
+        let author_id = sqlx::query_scalar!("SELECT user_id FROM microposts WHERE id = $1", id)
+            .fetch_optional(pool)
+            .timed("microposts.author_id")
+            .await?;
+        
+        Ok(author_id)
+    }
+    
+    /// Every micropost `viewer` may see: public authors, plus private ones that
+    /// are the viewer themselves or that they follow. Admins see everything.
+    #[tracing::instrument(name = "microposts.list_visible", skip_all, fields(page = params.page, per_page = params.per_page), err)]
+    pub async fn list_visible(pool: &PgPool, viewer: Option<&User>, params: &PaginationParams) -> Result<Pagination<Micropost>, AppError> {
+        let offset = (params.page - 1) * params.per_page;
+        let viewer_id = viewer.map(|viewer| viewer.id);
+        let admin = viewer.map_or(false, |viewer| viewer.admin);
+        
+        let microposts = sqlx::query_as!(
+            Micropost,
+            r#"
+            SELECT m.* FROM microposts m
+            JOIN users u ON u.id = m.user_id
+            WHERE NOT u.is_private OR $2 OR u.id = $1 OR EXISTS (
+                SELECT 1 FROM relationships r
+                WHERE r.follower_id = $1 AND r.followed_id = u.id AND r.status = 'accepted'
+            )
+            ORDER BY m.created_at DESC
+            LIMIT $3 OFFSET $4
+            "#,
+            viewer_id,
+            admin,
+            params.per_page as i64,
+            offset as i64
+        )
+        .fetch_all(pool)
+        .timed("microposts.list_visible")
+        .await?;
+        
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) FROM microposts m
+            JOIN users u ON u.id = m.user_id
+            WHERE NOT u.is_private OR $2 OR u.id = $1 OR EXISTS (
+                SELECT 1 FROM relationships r
+                WHERE r.follower_id = $1 AND r.followed_id = u.id AND r.status = 'accepted'
+            )
+            "#,
+            viewer_id,
+            admin
+        )
+        .fetch_one(pool)
+        .timed("microposts.list_visible")
+        .await?
+        .unwrap_or(0) as usize;
+        
+        Ok(Pagination::new(microposts, total, params.page, params.per_page))
+    }
+    
+    /// A user's microposts. Callers check `FollowRequestRepository::can_view` first.
+    #[tracing::instrument(name = "microposts.list_for_user", skip_all, fields(user_id = %user_id), err)]
+    pub async fn list_for_user(pool: &PgPool, user_id: Uuid, params: &PaginationParams) -> Result<Pagination<Micropost>, AppError> {
+        let offset = (params.page - 1) * params.per_page;
+        
+        let microposts = sqlx::query_as!(
+            Micropost,
+            "SELECT * FROM microposts WHERE user_id = $1 ORDER BY created_at DESC LIMIT $2 OFFSET $3",
+            user_id,
+            params.per_page as i64,
+            offset as i64
+        )
+        .fetch_all(pool)
+        .timed("microposts.list_for_user")
+        .await?;
+        
+        let total = sqlx::query_scalar!("SELECT COUNT(*) FROM microposts WHERE user_id = $1", user_id)
+            .fetch_one(pool)
+            .timed("microposts.list_for_user")
+            .await?
+            .unwrap_or(0) as usize;
+        
+        Ok(Pagination::new(microposts, total, params.page, params.per_page))
+    }
+    
+    /// The user's own microposts plus those of accounts whose follow was accepted;
+    /// pending requests to private accounts contribute nothing.
+    #[tracing::instrument(name = "microposts.feed", skip_all, fields(user_id = %user_id), err)]
+    pub async fn feed(pool: &PgPool, user_id: Uuid, params: &PaginationParams) -> Result<Pagination<Micropost>, AppError> {
+        let offset = (params.page - 1) * params.per_page;
+        
+        let microposts = sqlx::query_as!(
+            Micropost,
+            r#"
+            SELECT * FROM microposts
+            WHERE user_id = $1 OR user_id IN (
+                SELECT followed_id FROM relationships WHERE follower_id = $1 AND status = 'accepted'
+            )
+            ORDER BY created_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            user_id,
+            params.per_page as i64,
+            offset as i64
+        )
+        .fetch_all(pool)
+        .timed("microposts.feed")
+        .await?;
+        
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) FROM microposts
+            WHERE user_id = $1 OR user_id IN (
+                SELECT followed_id FROM relationships WHERE follower_id = $1 AND status = 'accepted'
+            )
+            "#,
+            user_id
+        )
+        .fetch_one(pool)
+        .timed("microposts.feed")
+        .await?
+        .unwrap_or(0) as usize;
+        
+        Ok(Pagination::new(microposts, total, params.page, params.per_page))
+    }
+    
+    /// Keyset page for GraphQL connections: up to `limit` of the user's microposts
+    /// older than `after`, a `(created_at, id)` position, newest first.
+    #[tracing::instrument(name = "microposts.list_for_user_after", skip_all, fields(user_id = %user_id), err)]
+    pub async fn list_for_user_after(pool: &PgPool, user_id: Uuid, after: Option<(DateTime<Utc>, Uuid)>, limit: i64) -> Result<Vec<Micropost>, AppError> {
+        let (created_at, id) = after.unzip();
+        let microposts = sqlx::query_as!(
+            Micropost,
+            r#"
+            SELECT * FROM microposts
+            WHERE user_id = $1 AND ($2::timestamptz IS NULL OR (created_at, id) < ($2, $3::uuid))
+            ORDER BY created_at DESC, id DESC
+            LIMIT $4
+            "#,
+            user_id,
+            created_at,
+            id,
+            limit
+        )
+        .fetch_all(pool)
+        .timed("microposts.list_for_user_after")
+        .await?;
+        
+        Ok(microposts)
+    }
+    
+    /// `feed` as a keyset page, for GraphQL connections.
+    #[tracing::instrument(name = "microposts.feed_after", skip_all, fields(user_id = %user_id), err)]
+    pub async fn feed_after(pool: &PgPool, user_id: Uuid, after: Option<(DateTime<Utc>, Uuid)>, limit: i64) -> Result<Vec<Micropost>, AppError> {
+        let (created_at, id) = after.unzip();
+        let microposts = sqlx::query_as!(
+            Micropost,
+            r#"
+            SELECT * FROM microposts
+            WHERE (user_id = $1 OR user_id IN (
+                SELECT followed_id FROM relationships WHERE follower_id = $1 AND status = 'accepted'
+            ))
+            AND ($2::timestamptz IS NULL OR (created_at, id) < ($2, $3::uuid))
+            ORDER BY created_at DESC, id DESC
+            LIMIT $4
+            "#,
+            user_id,
+            created_at,
+            id,
+            limit
+        )
+        .fetch_all(pool)
+        .timed("microposts.feed_after")
+        .await?;
+        
+        Ok(microposts)
+    }
+    
+    /// Only matches the author's own micropost. `expected_updated_at` is the
+    /// revision an `If-Match` header was checked against; the update only
+    /// applies if the row still has it.
+    #[tracing::instrument(name = "microposts.update", skip_all, fields(micropost_id = %id), err)]
+    pub async fn update(
+        pool: &PgPool,
+        id: Uuid,
+        author_id: Uuid,
+        req: &UpdateMicropostRequest,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> Result<Micropost, AppError> {
+        let mut update = PartialUpdate::new("microposts");
+        update.apply(req).where_eq("id", id).where_eq("user_id", author_id);
+        if let Some(expected) = expected_updated_at {
+            update.where_eq("updated_at", expected);
+        }
+        let micropost = update.fetch_optional(pool, "microposts.update").await?;
+        
+        match (micropost, expected_updated_at) {
+            (Some(micropost), _) => Ok(micropost),
+            (None, Some(_)) => Err(AppError::PreconditionFailed("The resource has changed; fetch it again before updating".to_string())),
+            (None, None) => Err(AppError::NotFound("Micropost not found".to_string())),
+        }
+    }
+    
+    #[tracing::instrument(name = "microposts.revisions", skip_all, fields(micropost_id = %id), err)]
+    pub async fn revisions(pool: &PgPool, id: Uuid) -> Result<Option<MicropostRevisions>, AppError> {
+        let revisions = sqlx::query_as!(
+            MicropostRevisions,
+            r#"
+            SELECT m.updated_at AS "updated_at!", u.updated_at AS "author_updated_at!"
+            FROM microposts m JOIN users u ON u.id = m.user_id
+            WHERE m.id = $1
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .timed("microposts.revisions")
+        .await?;
+        
+        Ok(revisions)
+    }
+    
+    /// Fingerprints the whole feed in one aggregate query.
+    #[tracing::instrument(name = "microposts.feed_fingerprint", skip_all, fields(user_id = %user_id), err)]
+    pub async fn feed_fingerprint(pool: &PgPool, user_id: Uuid) -> Result<FeedFingerprint, AppError> {
+        let fingerprint = sqlx::query_as!(
+            FeedFingerprint,
+            r#"
+            SELECT
+                COUNT(*) AS "count!",
+                MAX(m.updated_at) AS latest_post,
+                MAX(u.updated_at) AS latest_author
+            FROM microposts m JOIN users u ON u.id = m.user_id
+            WHERE m.user_id = $1 OR m.user_id IN (
+                SELECT followed_id FROM relationships WHERE follower_id = $1 AND status = 'accepted'
+            )
+            "#,
+            user_id
+        )
+        .fetch_one(pool)
+        .timed("microposts.feed_fingerprint")
+        .await?;
+        
+        Ok(fingerprint)
+    }
+    
+    #[tracing::instrument(name = "microposts.delete", skip_all, fields(micropost_id = %id), err)]
+    pub async fn delete(pool: &PgPool, id: Uuid) -> Result<(), AppError> {
+        sqlx::query!("DELETE FROM microposts WHERE id = $1", id)
+            .execute(pool)
+            .timed("microposts.delete")
+            .await?;
+        
+        Ok(())
+    }
+}
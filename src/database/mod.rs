@@ -1,15 +1,50 @@
-use sqlx::{PgPool, postgres::PgPoolOptions};
-use crate::error::AppError;
+use sqlx::{migrate::Migrator, PgPool, postgres::PgPoolOptions};
+use crate::{config::DatabaseConfig, error::AppError};
 
 pub mod users;
 pub mod microposts;
 pub mod relationships;
+pub mod follow_requests;
+pub mod messages;
+pub mod blocks;
+pub mod mfa;
+pub mod webauthn;
+pub mod identities;
+pub mod login_attempts;
+pub mod rate_limits;
+pub mod likes;
+pub mod seeds;
+pub mod partial_update;
 
-pub async fn init(database_url: &str) -> Result<PgPool, AppError> {
+/// Embedded migrations, shared by `serve` and the `migrate` commands.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Postgres error code for a missing table.
+const UNDEFINED_TABLE: &str = "42P01";
+
+pub async fn init(config: &DatabaseConfig) -> Result<PgPool, AppError> {
     let pool = PgPoolOptions::new()
-        .max_connections(20)
-        .connect(database_url)
+        .max_connections(config.max_connections)
+        .connect(&config.url)
         .await?;
     
     Ok(pool)
 }
+
+/// Number of embedded migrations not yet applied successfully. Read-only, unlike
+/// the migrator: a database that was never migrated has every migration pending.
+pub async fn pending_migrations(pool: &PgPool) -> Result<usize, AppError> {
+    let applied: Vec<i64> = match sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success").fetch_all(pool).await {
+        Ok(applied) => applied,
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some(UNDEFINED_TABLE) => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    
+    let pending = MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .filter(|m| !applied.contains(&m.version))
+        .count();
+    
+    Ok(pending)
+}
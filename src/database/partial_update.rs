@@ -0,0 +1,225 @@
+use chrono::Utc;
+use sqlx::{postgres::PgRow, Encode, FromRow, PgExecutor, Postgres, QueryBuilder, Type};
+use crate::{
+    error::AppError,
+    models::{UpdateMicropostRequest, UpdateUserRequest},
+    telemetry::QueryTimer,
+};
+
+/// `UPDATE <table> SET updated_at = now, <column> = <value>, ... WHERE ... RETURNING *`
+/// covering only the columns a request actually sets, so new optional fields
+/// add one `set` call instead of another set of hand-written queries.
+pub struct PartialUpdate<'args> {
+    builder: QueryBuilder<'args, Postgres>,
+    columns: Vec<&'static str>,
+    filtered: bool,
+}
+
+impl<'args> PartialUpdate<'args> {
+    /// `table` and column names are pushed verbatim and must be literals, never input.
+    pub fn new(table: &'static str) -> Self {
+        let mut builder = QueryBuilder::new(format!("UPDATE {} SET updated_at = ", table));
+        builder.push_bind(Utc::now());
+        
+        Self { builder, columns: Vec::new(), filtered: false }
+    }
+    
+    /// Sets `column` when `value` is present; `None` leaves it untouched.
+    pub fn set<T>(&mut self, column: &'static str, value: Option<T>) -> &mut Self
+    where
+        T: 'args + Encode<'args, Postgres> + Type<Postgres> + Send,
+    {
+        debug_assert!(!self.filtered, "set `{}` after the WHERE clause", column);
+        if let Some(value) = value {
+            self.builder.push(", ").push(column).push(" = ").push_bind(value);
+            self.columns.push(column);
+        }
+        self
+    }
+    
+    pub fn apply(&mut self, changes: &impl Changeset) -> &mut Self {
+        changes.apply(self);
+        self
+    }
+    
+    /// Adds `column = value` to the WHERE clause; call after every `set`.
+    pub fn where_eq<T>(&mut self, column: &'static str, value: T) -> &mut Self
+    where
+        T: 'args + Encode<'args, Postgres> + Type<Postgres> + Send,
+    {
+        self.builder.push(if self.filtered { " AND " } else { " WHERE " });
+        self.builder.push(column).push(" = ").push_bind(value);
+        self.filtered = true;
+        self
+    }
+    
+    /// Columns that will be written, besides `updated_at`.
+    pub fn columns(&self) -> &[&'static str] {
+        &self.columns
+    }
+    
+    /// Runs the update and returns the row, or `None` when the WHERE clause
+    /// matched nothing. Rejects updates that would only touch `updated_at`.
+    pub async fn fetch_optional<'e, T>(&mut self, executor: impl PgExecutor<'e>, name: &'static str) -> Result<Option<T>, AppError>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        if self.columns.is_empty() {
+            return Err(AppError::BadRequest("No fields to update".to_string()));
+        }
+        if !self.filtered {
+            return Err(AppError::Internal("Refusing to run an UPDATE without a WHERE clause".to_string()));
+        }
+        
+        let row = self
+            .builder
+            .push(" RETURNING *")
+            .build_query_as::<T>()
+            .fetch_optional(executor)
+            .timed(name)
+            .await?;
+        
+        Ok(row)
+    }
+}
+
+/// A request whose present fields map onto columns of one table.
+pub trait Changeset {
+    fn apply(&self, update: &mut PartialUpdate<'_>);
+}
+
+/// The password is hashed by the caller and set as `password_digest` separately.
+impl Changeset for UpdateUserRequest {
+    fn apply(&self, update: &mut PartialUpdate<'_>) {
+        update
+            .set("name", self.name.clone())
+            .set("email", self.email.as_deref().map(str::to_lowercase))
+            .set("username", self.username.as_deref().map(blank_to_null))
+            .set("bio", self.bio.as_deref().map(blank_to_null))
+            .set("location", self.location.as_deref().map(blank_to_null))
+            .set("website", self.website.as_deref().map(blank_to_null))
+            .set("banner", self.banner.as_deref().map(blank_to_null));
+    }
+}
+
+/// Optional text columns, the handle included, are cleared by submitting them empty.
+fn blank_to_null(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Scope it with `where_eq("id", ..)` and `where_eq("user_id", author)` so only
+/// the author's own micropost matches.
+impl Changeset for UpdateMicropostRequest {
+    fn apply(&self, update: &mut PartialUpdate<'_>) {
+        update
+            .set("content", self.content.clone())
+            .set("picture", self.picture.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+    use super::*;
+    
+    /// Which request fields are present, the columns written and the SQL.
+    type Case<Fields> = (Fields, &'static [&'static str], &'static str);
+    
+    fn user_request(name: bool, email: bool, password: bool) -> UpdateUserRequest {
+        UpdateUserRequest {
+            name: name.then(|| "New Name".to_string()),
+            email: email.then(|| "New@Example.com".to_string()),
+            password: password.then(|| "secret123".to_string()),
+            password_confirmation: password.then(|| "secret123".to_string()),
+            username: None,
+            bio: None,
+            location: None,
+            website: None,
+            banner: None,
+        }
+    }
+    
+    fn user_update(req: &UpdateUserRequest) -> PartialUpdate<'static> {
+        let mut update = PartialUpdate::new("users");
+        update
+            .apply(req)
+            .set("password_digest", req.password.as_ref().map(|_| "digest".to_string()))
+            .where_eq("id", Uuid::nil());
+        update
+    }
+    
+    #[test]
+    fn user_updates_cover_every_combination() {
+        let cases: [Case<(bool, bool, bool)>; 8] = [
+            ((false, false, false), &[], "UPDATE users SET updated_at = $1 WHERE id = $2"),
+            ((true, false, false), &["name"], "UPDATE users SET updated_at = $1, name = $2 WHERE id = $3"),
+            ((false, true, false), &["email"], "UPDATE users SET updated_at = $1, email = $2 WHERE id = $3"),
+            ((false, false, true), &["password_digest"], "UPDATE users SET updated_at = $1, password_digest = $2 WHERE id = $3"),
+            ((true, true, false), &["name", "email"], "UPDATE users SET updated_at = $1, name = $2, email = $3 WHERE id = $4"),
+            ((true, false, true), &["name", "password_digest"], "UPDATE users SET updated_at = $1, name = $2, password_digest = $3 WHERE id = $4"),
+            ((false, true, true), &["email", "password_digest"], "UPDATE users SET updated_at = $1, email = $2, password_digest = $3 WHERE id = $4"),
+            (
+                (true, true, true),
+                &["name", "email", "password_digest"],
+                "UPDATE users SET updated_at = $1, name = $2, email = $3, password_digest = $4 WHERE id = $5",
+            ),
+        ];
+        
+        for ((name, email, password), columns, sql) in cases {
+            let update = user_update(&user_request(name, email, password));
+            assert_eq!(update.columns(), columns, "name={} email={} password={}", name, email, password);
+            assert_eq!(update.builder.sql(), sql, "name={} email={} password={}", name, email, password);
+        }
+    }
+    
+    #[test]
+    fn blank_profile_fields_are_still_written() {
+        let req = UpdateUserRequest {
+            username: Some("new_handle".to_string()),
+            bio: Some("  ".to_string()),
+            website: Some(String::new()),
+            ..user_request(false, false, false)
+        };
+        let update = user_update(&req);
+        
+        assert_eq!(update.columns(), ["username", "bio", "website"]);
+        assert_eq!(blank_to_null("  "), None);
+        assert_eq!(blank_to_null(" Tokyo "), Some("Tokyo".to_string()));
+    }
+    
+    #[test]
+    fn micropost_updates_cover_every_combination() {
+        let cases: [Case<(bool, bool)>; 4] = [
+            ((false, false), &[], "UPDATE microposts SET updated_at = $1 WHERE id = $2 AND user_id = $3"),
+            ((true, false), &["content"], "UPDATE microposts SET updated_at = $1, content = $2 WHERE id = $3 AND user_id = $4"),
+            ((false, true), &["picture"], "UPDATE microposts SET updated_at = $1, picture = $2 WHERE id = $3 AND user_id = $4"),
+            (
+                (true, true),
+                &["content", "picture"],
+                "UPDATE microposts SET updated_at = $1, content = $2, picture = $3 WHERE id = $4 AND user_id = $5",
+            ),
+        ];
+        
+        for ((content, picture), columns, sql) in cases {
+            let req = UpdateMicropostRequest {
+                content: content.then(|| "Edited".to_string()),
+                picture: picture.then(|| "uploads/picture.png".to_string()),
+            };
+            let mut update = PartialUpdate::new("microposts");
+            update.apply(&req).where_eq("id", Uuid::nil()).where_eq("user_id", Uuid::nil());
+            
+            assert_eq!(update.columns(), columns, "content={} picture={}", content, picture);
+            assert_eq!(update.builder.sql(), sql, "content={} picture={}", content, picture);
+        }
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn unscoped_updates_are_refused(pool: sqlx::PgPool) {
+        let mut update = PartialUpdate::new("microposts");
+        update.set("content", Some("Everywhere".to_string()));
+        
+        let result = update.fetch_optional::<crate::models::Micropost>(&pool, "microposts.update").await;
+        assert!(matches!(result, Err(AppError::Internal(_))));
+    }
+}
@@ -0,0 +1,74 @@
+use sqlx::PgPool;
+use crate::error::AppError;
+use crate::telemetry::QueryTimer;
+
+pub struct RateLimitRepository;
+
+impl RateLimitRepository {
+    /// Refills and takes one token from a shared bucket. Returns whether the
+    /// request is allowed and the tokens left afterwards. The common case is a
+    /// single conditional UPDATE; new buckets and refusals need one more query.
+    /// Elapsed time comes from the database clock, which every instance shares.
+    /// `period_secs` is how long the bucket takes to refill from empty.
+    pub async fn take(pool: &PgPool, key: &str, capacity: f64, refill_per_sec: f64, period_secs: i64) -> Result<(bool, f64), AppError> {
+        // The refill is recomputed from the row the UPDATE locks, so concurrent
+        // takes never spend the same token twice
+        let taken = sqlx::query_scalar!(
+            r#"
+            UPDATE rate_limit_buckets
+            SET tokens = LEAST($2, tokens + EXTRACT(EPOCH FROM (now() - updated_at))::float8 * $3) - 1, updated_at = now(), period_secs = $4
+            WHERE key = $1 AND LEAST($2, tokens + EXTRACT(EPOCH FROM (now() - updated_at))::float8 * $3) >= 1
+            RETURNING tokens
+            "#,
+            key,
+            capacity,
+            refill_per_sec,
+            period_secs
+        )
+        .fetch_optional(pool)
+        .timed("rate_limits.take")
+        .await?;
+        if let Some(tokens) = taken {
+            return Ok((true, tokens));
+        }
+        
+        let created = sqlx::query_scalar!(
+            "INSERT INTO rate_limit_buckets (key, tokens, updated_at, period_secs) VALUES ($1, $2::float8 - 1, now(), $3) ON CONFLICT (key) DO NOTHING RETURNING tokens",
+            key,
+            capacity,
+            period_secs
+        )
+        .fetch_optional(pool)
+        .timed("rate_limits.take")
+        .await?;
+        if let Some(tokens) = created {
+            return Ok((true, tokens));
+        }
+        
+        // Refused: report what's left without touching the bucket
+        let available = sqlx::query_scalar!(
+            r#"SELECT LEAST($2, tokens + EXTRACT(EPOCH FROM (now() - updated_at))::float8 * $3) AS "tokens!" FROM rate_limit_buckets WHERE key = $1"#,
+            key,
+            capacity,
+            refill_per_sec
+        )
+        .fetch_one(pool)
+        .timed("rate_limits.take")
+        .await?;
+        
+        Ok((false, available))
+    }
+    
+    /// Drops buckets idle long enough to have refilled completely, which takes
+    /// one period of their quota.
+    pub async fn prune(pool: &PgPool) -> Result<u64, AppError> {
+        let result = sqlx::query!(
+            "DELETE FROM rate_limit_buckets WHERE updated_at < now() - make_interval(secs => period_secs)"
+        )
+        .execute(pool)
+        .timed("rate_limits.prune")
+        .await?;
+        
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,162 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::models::{Relationship, User};
+use crate::error::AppError;
+use crate::utils::pagination::{Pagination, PaginationParams};
+use crate::telemetry::QueryTimer;
+
+/// Reads and removals of follows. New follows go through
+/// `FollowRequestRepository::follow`, which knows about private accounts.
+pub struct RelationshipRepository;
+
+impl RelationshipRepository {
+    #[tracing::instrument(name = "relationships.find", skip_all, err)]
+    pub async fn find(pool: &PgPool, follower_id: Uuid, followed_id: Uuid) -> Result<Option<Relationship>, AppError> {
+        let relationship = sqlx::query_as!(
+            Relationship,
+            "SELECT * FROM relationships WHERE follower_id = $1 AND followed_id = $2",
+            follower_id,
+            followed_id
+        )
+        .fetch_optional(pool)
+        .timed("relationships.find")
+        .await?;
+        
+        Ok(relationship)
+    }
+    
+    /// Unfollows, or withdraws a pending request. Returns whether anything was removed.
+    #[tracing::instrument(name = "relationships.unfollow", skip_all, err)]
+    pub async fn unfollow(pool: &PgPool, follower_id: Uuid, followed_id: Uuid) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            "DELETE FROM relationships WHERE follower_id = $1 AND followed_id = $2",
+            follower_id,
+            followed_id
+        )
+        .execute(pool)
+        .timed("relationships.unfollow")
+        .await?;
+        
+        Ok(result.rows_affected() > 0)
+    }
+    
+    /// Accepted followers of `user_id`, most recent first.
+    #[tracing::instrument(name = "relationships.followers", skip_all, fields(user_id = %user_id), err)]
+    pub async fn followers(pool: &PgPool, user_id: Uuid, params: &PaginationParams) -> Result<Pagination<User>, AppError> {
+        let offset = (params.page - 1) * params.per_page;
+        
+        let users = sqlx::query_as!(
+            User,
+            r#"
+            SELECT u.* FROM users u
+            JOIN relationships r ON r.follower_id = u.id
+            WHERE r.followed_id = $1 AND r.status = 'accepted'
+            ORDER BY r.created_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            user_id,
+            params.per_page as i64,
+            offset as i64
+        )
+        .fetch_all(pool)
+        .timed("relationships.followers")
+        .await?;
+        
+        let total = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM relationships WHERE followed_id = $1 AND status = 'accepted'",
+            user_id
+        )
+        .fetch_one(pool)
+        .timed("relationships.followers")
+        .await?
+        .unwrap_or(0) as usize;
+        
+        Ok(Pagination::new(users, total, params.page, params.per_page))
+    }
+    
+    /// Accounts `user_id` follows with an accepted request, most recent first.
+    #[tracing::instrument(name = "relationships.following", skip_all, fields(user_id = %user_id), err)]
+    pub async fn following(pool: &PgPool, user_id: Uuid, params: &PaginationParams) -> Result<Pagination<User>, AppError> {
+        let offset = (params.page - 1) * params.per_page;
+        
+        let users = sqlx::query_as!(
+            User,
+            r#"
+            SELECT u.* FROM users u
+            JOIN relationships r ON r.followed_id = u.id
+            WHERE r.follower_id = $1 AND r.status = 'accepted'
+            ORDER BY r.created_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            user_id,
+            params.per_page as i64,
+            offset as i64
+        )
+        .fetch_all(pool)
+        .timed("relationships.following")
+        .await?;
+        
+        let total = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM relationships WHERE follower_id = $1 AND status = 'accepted'",
+            user_id
+        )
+        .fetch_one(pool)
+        .timed("relationships.following")
+        .await?
+        .unwrap_or(0) as usize;
+        
+        Ok(Pagination::new(users, total, params.page, params.per_page))
+    }
+    
+    /// Keyset page of accepted follows of `user_id`, for GraphQL connections:
+    /// up to `limit` rows older than `after`, a `(created_at, id)` position.
+    #[tracing::instrument(name = "relationships.followers_after", skip_all, fields(user_id = %user_id), err)]
+    pub async fn followers_after(pool: &PgPool, user_id: Uuid, after: Option<(DateTime<Utc>, Uuid)>, limit: i64) -> Result<Vec<Relationship>, AppError> {
+        let (created_at, id) = after.unzip();
+        let relationships = sqlx::query_as!(
+            Relationship,
+            r#"
+            SELECT * FROM relationships
+            WHERE followed_id = $1 AND status = 'accepted'
+            AND ($2::timestamptz IS NULL OR (created_at, id) < ($2, $3::uuid))
+            ORDER BY created_at DESC, id DESC
+            LIMIT $4
+            "#,
+            user_id,
+            created_at,
+            id,
+            limit
+        )
+        .fetch_all(pool)
+        .timed("relationships.followers_after")
+        .await?;
+        
+        Ok(relationships)
+    }
+    
+    /// `followers_after` for the accounts `user_id` follows.
+    #[tracing::instrument(name = "relationships.following_after", skip_all, fields(user_id = %user_id), err)]
+    pub async fn following_after(pool: &PgPool, user_id: Uuid, after: Option<(DateTime<Utc>, Uuid)>, limit: i64) -> Result<Vec<Relationship>, AppError> {
+        let (created_at, id) = after.unzip();
+        let relationships = sqlx::query_as!(
+            Relationship,
+            r#"
+            SELECT * FROM relationships
+            WHERE follower_id = $1 AND status = 'accepted'
+            AND ($2::timestamptz IS NULL OR (created_at, id) < ($2, $3::uuid))
+            ORDER BY created_at DESC, id DESC
+            LIMIT $4
+            "#,
+            user_id,
+            created_at,
+            id,
+            limit
+        )
+        .fetch_all(pool)
+        .timed("relationships.following_after")
+        .await?;
+        
+        Ok(relationships)
+    }
+}
@@ -0,0 +1,145 @@
+use fake::{
+    faker::{lorem::en::Sentence, name::en::Name},
+    Fake,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+use chrono::{Duration, Utc};
+use crate::error::AppError;
+
+pub const EXAMPLE_EMAIL: &str = "example@railstutorial.org";
+const EXAMPLE_PASSWORD: &str = "foobar";
+const SAMPLE_PASSWORD: &str = "password";
+
+#[derive(Debug, Clone, Copy)]
+pub struct SeedOptions {
+    pub users: usize,
+    pub microposts_per_user: usize,
+    pub posting_users: usize,
+}
+
+impl Default for SeedOptions {
+    fn default() -> Self {
+        Self {
+            users: 100,
+            microposts_per_user: 50,
+            posting_users: 6,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SeedSummary {
+    pub users: usize,
+    pub microposts: usize,
+    pub relationships: usize,
+}
+
+/// Populates the sample data from the Rails tutorial: an admin example user,
+/// activated sample users, microposts for the first few and a follow graph
+/// around the example user.
+pub async fn run(pool: &PgPool, options: SeedOptions) -> Result<SeedSummary, AppError> {
+    let already_seeded = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM users WHERE email = $1)",
+        EXAMPLE_EMAIL
+    )
+    .fetch_one(pool)
+    .await?
+    .unwrap_or(false);
+    
+    if already_seeded {
+        return Err(AppError::BadRequest(format!("Database already seeded ({} exists)", EXAMPLE_EMAIL)));
+    }
+    
+    // bcrypt is deliberately slow, so every sample user shares one digest
+    let example_digest = hash(EXAMPLE_PASSWORD)?;
+    let sample_digest = hash(SAMPLE_PASSWORD)?;
+    
+    let mut tx = pool.begin().await?;
+    let mut summary = SeedSummary::default();
+    let mut user_ids = Vec::with_capacity(options.users);
+    let now = Utc::now();
+    
+    for n in 0..options.users {
+        let (name, email, digest, admin) = if n == 0 {
+            ("Example User".to_string(), EXAMPLE_EMAIL.to_string(), &example_digest, true)
+        } else {
+            let name: String = Name().fake();
+            (name.chars().take(50).collect(), format!("example-{}@railstutorial.org", n), &sample_digest, false)
+        };
+        
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO users (id, name, email, password_digest, admin, activated, activated_at, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, true, $6, $7, $8)
+            RETURNING id
+            "#,
+            Uuid::new_v4(),
+            name,
+            email,
+            digest.as_str(),
+            admin,
+            now,
+            now,
+            now
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        
+        user_ids.push(id);
+        summary.users += 1;
+    }
+    
+    // Spread posts back in time so feeds have a realistic order
+    for i in 0..options.microposts_per_user {
+        for user_id in user_ids.iter().take(options.posting_users) {
+            let content: String = Sentence(5..6).fake();
+            let created_at = now - Duration::minutes((i * options.posting_users) as i64 + 1);
+            
+            sqlx::query!(
+                "INSERT INTO microposts (id, content, user_id, created_at, updated_at) VALUES ($1, $2, $3, $4, $5)",
+                Uuid::new_v4(),
+                content,
+                user_id,
+                created_at,
+                created_at
+            )
+            .execute(&mut *tx)
+            .await?;
+            
+            summary.microposts += 1;
+        }
+    }
+    
+    // The example user follows users 3..=50 and is followed by users 4..=40
+    if let Some(&example_id) = user_ids.first() {
+        let following = user_ids.iter().skip(2).take(48).map(|&id| (example_id, id));
+        let followers = user_ids.iter().skip(3).take(37).map(|&id| (id, example_id));
+        
+        for (follower_id, followed_id) in following.chain(followers) {
+            sqlx::query!(
+                r#"
+                INSERT INTO relationships (id, follower_id, followed_id, status, accepted_at, created_at)
+                VALUES ($1, $2, $3, 'accepted', $4, $5)
+                "#,
+                Uuid::new_v4(),
+                follower_id,
+                followed_id,
+                now,
+                now
+            )
+            .execute(&mut *tx)
+            .await?;
+            
+            summary.relationships += 1;
+        }
+    }
+    
+    tx.commit().await?;
+    
+    Ok(summary)
+}
+
+fn hash(password: &str) -> Result<String, AppError> {
+    bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|e| AppError::Internal(format!("Failed to hash password: {}", e)))
+}
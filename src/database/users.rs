@@ -1,13 +1,17 @@
+use std::collections::HashMap;
 use sqlx::PgPool;
 use uuid::Uuid;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use crate::models::{User, CreateUserRequest, UpdateUserRequest};
 use crate::error::AppError;
+use crate::database::partial_update::PartialUpdate;
 use crate::utils::pagination::{Pagination, PaginationParams};
+use crate::telemetry::QueryTimer;
 
 pub struct UserRepository;
 
 impl UserRepository {
+    #[tracing::instrument(name = "users.create", skip_all, err)]
     pub async fn create(pool: &PgPool, req: &CreateUserRequest, password_hash: &str, activation_digest: &str) -> Result<User, AppError> {
         let user = sqlx::query_as!(
             User,
@@ -25,11 +29,38 @@ impl UserRepository {
             Utc::now()
         )
         .fetch_one(pool)
+        .timed("users.create")
         .await?;
         
         Ok(user)
     }
     
+    /// Creates an already activated user for an externally verified identity.
+    #[tracing::instrument(name = "users.create_external", skip_all, err)]
+    pub async fn create_external(pool: &PgPool, name: &str, email: &str, password_hash: &str) -> Result<User, AppError> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (id, name, email, password_digest, activated, activated_at, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, true, $5, $6, $7)
+            RETURNING *
+            "#,
+            Uuid::new_v4(),
+            name,
+            email.to_lowercase(),
+            password_hash,
+            Utc::now(),
+            Utc::now(),
+            Utc::now()
+        )
+        .fetch_one(pool)
+        .timed("users.create_external")
+        .await?;
+        
+        Ok(user)
+    }
+    
+    #[tracing::instrument(name = "users.find_by_id", skip_all, fields(user_id = %id), err)]
     pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<User>, AppError> {
         let user = sqlx::query_as!(
             User,
@@ -37,23 +68,103 @@ impl UserRepository {
             id
         )
         .fetch_optional(pool)
+        .timed("users.find_by_id")
         .await?;
         
         Ok(user)
     }
     
+    /// Batch lookup for GraphQL loaders; missing ids are simply absent.
+    #[tracing::instrument(name = "users.find_by_ids", skip_all, fields(count = ids.len()), err)]
+    pub async fn find_by_ids(pool: &PgPool, ids: &[Uuid]) -> Result<Vec<User>, AppError> {
+        let users = sqlx::query_as!(
+            User,
+            "SELECT * FROM users WHERE id = ANY($1)",
+            ids
+        )
+        .fetch_all(pool)
+        .timed("users.find_by_ids")
+        .await?;
+        
+        Ok(users)
+    }
+    
+    #[tracing::instrument(name = "users.find_by_email", skip_all, err)]
     pub async fn find_by_email(pool: &PgPool, email: &str) -> Result<Option<User>, AppError> {
         let user = sqlx::query_as!(
             User,
-            "SELECT * FROM users WHERE email = $1",
-            email.to_lowercase()
+            "SELECT * FROM users WHERE lower(email) = lower($1)",
+            email
+        )
+        .fetch_optional(pool)
+        .timed("users.find_by_email")
+        .await?;
+        
+        Ok(user)
+    }
+    
+    #[tracing::instrument(name = "users.find_by_username", skip_all, err)]
+    pub async fn find_by_username(pool: &PgPool, username: &str) -> Result<Option<User>, AppError> {
+        let user = sqlx::query_as!(
+            User,
+            "SELECT * FROM users WHERE lower(username) = lower($1)",
+            username
+        )
+        .fetch_optional(pool)
+        .timed("users.find_by_username")
+        .await?;
+        
+        Ok(user)
+    }
+    
+    /// The user a retired handle belonged to, for redirecting old profile URLs.
+    #[tracing::instrument(name = "users.find_by_previous_username", skip_all, err)]
+    pub async fn find_by_previous_username(pool: &PgPool, username: &str) -> Result<Option<User>, AppError> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            SELECT u.* FROM users u
+            JOIN username_redirects r ON r.user_id = u.id
+            WHERE lower(r.username) = lower($1)
+            "#,
+            username
         )
         .fetch_optional(pool)
+        .timed("users.find_by_previous_username")
         .await?;
         
         Ok(user)
     }
     
+    /// Groups of accounts whose emails differ only by case, as `(email, users)`
+    /// with the oldest account first. Empty once `users_email_lower_key` exists.
+    #[tracing::instrument(name = "users.duplicate_emails", skip_all, err)]
+    pub async fn duplicate_emails(pool: &PgPool) -> Result<Vec<(String, Vec<User>)>, AppError> {
+        let users = sqlx::query_as!(
+            User,
+            r#"
+            SELECT * FROM users
+            WHERE lower(email) IN (SELECT lower(email) FROM users GROUP BY lower(email) HAVING COUNT(*) > 1)
+            ORDER BY lower(email), created_at
+            "#
+        )
+        .fetch_all(pool)
+        .timed("users.duplicate_emails")
+        .await?;
+        
+        let mut groups: Vec<(String, Vec<User>)> = Vec::new();
+        for user in users {
+            let email = user.email.to_lowercase();
+            match groups.last_mut() {
+                Some((last, members)) if *last == email => members.push(user),
+                _ => groups.push((email, vec![user])),
+            }
+        }
+        
+        Ok(groups)
+    }
+    
+    #[tracing::instrument(name = "users.find_by_activation_token", skip_all, err)]
     pub async fn find_by_activation_token(pool: &PgPool, token: &str) -> Result<Option<User>, AppError> {
         let user = sqlx::query_as!(
             User,
@@ -61,11 +172,13 @@ impl UserRepository {
             token
         )
         .fetch_optional(pool)
+        .timed("users.find_by_activation_token")
         .await?;
         
         Ok(user)
     }
     
+    #[tracing::instrument(name = "users.find_by_reset_token", skip_all, err)]
     pub async fn find_by_reset_token(pool: &PgPool, token: &str) -> Result<Option<User>, AppError> {
         let user = sqlx::query_as!(
             User,
@@ -73,128 +186,75 @@ impl UserRepository {
             token
         )
         .fetch_optional(pool)
+        .timed("users.find_by_reset_token")
         .await?;
         
         Ok(user)
     }
     
-    pub async fn update(pool: &PgPool, id: Uuid, req: &UpdateUserRequest, password_hash: Option<&str>) -> Result<User, AppError> {
-        let mut query = "UPDATE users SET updated_at = $1".to_string();
-        let mut param_count = 1;
-        let mut params: Vec<Box<dyn sqlx::Encode<'_, sqlx::Postgres> + Send + Sync>> = vec![Box::new(Utc::now())];
-        
-        if let Some(name) = &req.name {
-            param_count += 1;
-            query.push_str(&format!(", name = ${}", param_count));
-            params.push(Box::new(name.clone()));
-        }
+    /// Renaming records the previous handle in `username_redirects`, and
+    /// claiming a handle stops it redirecting to its former owner.
+    ///
+    /// `expected_updated_at` is the revision an `If-Match` header was checked
+    /// against; the row is locked and the update refused if it moved since.
+    #[tracing::instrument(name = "users.update", skip_all, fields(user_id = %id), err)]
+    pub async fn update(
+        pool: &PgPool,
+        id: Uuid,
+        req: &UpdateUserRequest,
+        password_hash: Option<&str>,
+        expected_updated_at: Option<DateTime<Utc>>,
+    ) -> Result<User, AppError> {
+        let mut tx = pool.begin().await?;
         
-        if let Some(email) = &req.email {
-            param_count += 1;
-            query.push_str(&format!(", email = ${}", param_count));
-            params.push(Box::new(email.to_lowercase()));
+        let current = sqlx::query!("SELECT username, updated_at FROM users WHERE id = $1 FOR UPDATE", id)
+            .fetch_optional(&mut *tx)
+            .timed("users.update")
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+        if expected_updated_at.map_or(false, |expected| expected != current.updated_at) {
+            return Err(AppError::PreconditionFailed("The resource has changed; fetch it again before updating".to_string()));
         }
+        let previous = current.username;
+        
+        let user: User = PartialUpdate::new("users")
+            .apply(req)
+            .set("password_digest", password_hash.map(str::to_string))
+            .where_eq("id", id)
+            .fetch_optional(&mut *tx, "users.update")
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
         
-        if let Some(hash) = password_hash {
-            param_count += 1;
-            query.push_str(&format!(", password_digest = ${}", param_count));
-            params.push(Box::new(hash.to_string()));
+        if let Some(username) = &user.username {
+            sqlx::query!("DELETE FROM username_redirects WHERE lower(username) = lower($1)", username)
+                .execute(&mut *tx)
+                .timed("users.update")
+                .await?;
         }
         
-        param_count += 1;
-        query.push_str(&format!(" WHERE id = ${} RETURNING *", param_count));
-        params.push(Box::new(id));
-        
-        // For simplicity, we'll use a direct query here
-        // In a real application, you might want to use a query builder
-        let user = if let Some(name) = &req.name {
-            if let Some(email) = &req.email {
-                if let Some(hash) = password_hash {
-                    sqlx::query_as!(
-                        User,
-                        "UPDATE users SET name = $1, email = $2, password_digest = $3, updated_at = $4 WHERE id = $5 RETURNING *",
-                        name,
-                        email.to_lowercase(),
-                        hash,
-                        Utc::now(),
-                        id
-                    )
-                    .fetch_one(pool)
-                    .await?
-                } else {
-                    sqlx::query_as!(
-                        User,
-                        "UPDATE users SET name = $1, email = $2, updated_at = $3 WHERE id = $4 RETURNING *",
-                        name,
-                        email.to_lowercase(),
-                        Utc::now(),
-                        id
-                    )
-                    .fetch_one(pool)
-                    .await?
-                }
-            } else if let Some(hash) = password_hash {
-                sqlx::query_as!(
-                    User,
-                    "UPDATE users SET name = $1, password_digest = $2, updated_at = $3 WHERE id = $4 RETURNING *",
-                    name,
-                    hash,
-                    Utc::now(),
-                    id
-                )
-                .fetch_one(pool)
-                .await?
-            } else {
-                sqlx::query_as!(
-                    User,
-                    "UPDATE users SET name = $1, updated_at = $2 WHERE id = $3 RETURNING *",
-                    name,
-                    Utc::now(),
-                    id
-                )
-                .fetch_one(pool)
-                .await?
-            }
-        } else if let Some(email) = &req.email {
-            if let Some(hash) = password_hash {
-                sqlx::query_as!(
-                    User,
-                    "UPDATE users SET email = $1, password_digest = $2, updated_at = $3 WHERE id = $4 RETURNING *",
-                    email.to_lowercase(),
-                    hash,
-                    Utc::now(),
-                    id
-                )
-                .fetch_one(pool)
-                .await?
-            } else {
-                sqlx::query_as!(
-                    User,
-                    "UPDATE users SET email = $1, updated_at = $2 WHERE id = $3 RETURNING *",
-                    email.to_lowercase(),
-                    Utc::now(),
-                    id
-                )
-                .fetch_one(pool)
-                .await?
-            }
-        } else if let Some(hash) = password_hash {
-            sqlx::query_as!(
-                User,
-                "UPDATE users SET password_digest = $1, updated_at = $2 WHERE id = $3 RETURNING *",
-                hash,
-                Utc::now(),
+        let renamed_from = previous.filter(|old| {
+            user.username.as_deref().map_or(true, |new| !new.eq_ignore_ascii_case(old))
+        });
+        if let Some(old) = renamed_from {
+            sqlx::query!(
+                r#"
+                INSERT INTO username_redirects (username, user_id)
+                VALUES ($1, $2)
+                ON CONFLICT ((lower(username))) DO UPDATE SET user_id = EXCLUDED.user_id, created_at = NOW()
+                "#,
+                old,
                 id
             )
-            .fetch_one(pool)
-            .await?
-        } else {
-            return Err(AppError::BadRequest("No fields to update".to_string()));
-        };
+            .execute(&mut *tx)
+            .timed("users.update")
+            .await?;
+        }
         
+        tx.commit().await?;
         Ok(user)
     }
     
+    #[tracing::instrument(name = "users.activate", skip_all, fields(user_id = %id), err)]
     pub async fn activate(pool: &PgPool, id: Uuid) -> Result<User, AppError> {
         let user = sqlx::query_as!(
             User,
@@ -204,11 +264,30 @@ impl UserRepository {
             id
         )
         .fetch_one(pool)
+        .timed("users.activate")
+        .await?;
+        
+        Ok(user)
+    }
+    
+    /// Replaces the activation digest, invalidating any link sent earlier.
+    #[tracing::instrument(name = "users.set_activation_token", skip_all, fields(user_id = %id), err)]
+    pub async fn set_activation_token(pool: &PgPool, id: Uuid, activation_digest: &str) -> Result<User, AppError> {
+        let user = sqlx::query_as!(
+            User,
+            "UPDATE users SET activation_digest = $1, updated_at = $2 WHERE id = $3 RETURNING *",
+            activation_digest,
+            Utc::now(),
+            id
+        )
+        .fetch_one(pool)
+        .timed("users.set_activation_token")
         .await?;
         
         Ok(user)
     }
     
+    #[tracing::instrument(name = "users.set_reset_token", skip_all, fields(user_id = %id), err)]
     pub async fn set_reset_token(pool: &PgPool, id: Uuid, reset_digest: &str) -> Result<User, AppError> {
         let user = sqlx::query_as!(
             User,
@@ -219,11 +298,13 @@ impl UserRepository {
             id
         )
         .fetch_one(pool)
+        .timed("users.set_reset_token")
         .await?;
         
         Ok(user)
     }
     
+    #[tracing::instrument(name = "users.clear_reset_token", skip_all, fields(user_id = %id), err)]
     pub async fn clear_reset_token(pool: &PgPool, id: Uuid) -> Result<User, AppError> {
         let user = sqlx::query_as!(
             User,
@@ -232,19 +313,87 @@ impl UserRepository {
             id
         )
         .fetch_one(pool)
+        .timed("users.clear_reset_token")
+        .await?;
+        
+        Ok(user)
+    }
+    
+    #[tracing::instrument(name = "users.set_private", skip_all, fields(user_id = %id), err)]
+    pub async fn set_private(pool: &PgPool, id: Uuid, is_private: bool) -> Result<User, AppError> {
+        let user = sqlx::query_as!(
+            User,
+            "UPDATE users SET is_private = $1, updated_at = $2 WHERE id = $3 RETURNING *",
+            is_private,
+            Utc::now(),
+            id
+        )
+        .fetch_one(pool)
+        .timed("users.set_private")
+        .await?;
+        
+        Ok(user)
+    }
+    
+    #[tracing::instrument(name = "users.set_admin", skip_all, fields(user_id = %id), err)]
+    pub async fn set_admin(pool: &PgPool, id: Uuid, admin: bool) -> Result<User, AppError> {
+        let user = sqlx::query_as!(
+            User,
+            "UPDATE users SET admin = $1, updated_at = $2 WHERE id = $3 RETURNING *",
+            admin,
+            Utc::now(),
+            id
+        )
+        .fetch_one(pool)
+        .timed("users.set_admin")
+        .await?;
+        
+        Ok(user)
+    }
+    
+    /// Replaces the password and invalidates any outstanding reset token.
+    #[tracing::instrument(name = "users.set_password", skip_all, fields(user_id = %id), err)]
+    pub async fn set_password(pool: &PgPool, id: Uuid, password_hash: &str) -> Result<User, AppError> {
+        let user = sqlx::query_as!(
+            User,
+            "UPDATE users SET password_digest = $1, reset_digest = NULL, reset_sent_at = NULL, updated_at = $2 WHERE id = $3 RETURNING *",
+            password_hash,
+            Utc::now(),
+            id
+        )
+        .fetch_one(pool)
+        .timed("users.set_password")
         .await?;
         
         Ok(user)
     }
     
+    /// Invalidates every access, refresh and session token issued so far.
+    #[tracing::instrument(name = "users.revoke_tokens", skip_all, fields(user_id = %id), err)]
+    pub async fn revoke_tokens(pool: &PgPool, id: Uuid) -> Result<(), AppError> {
+        sqlx::query!(
+            "UPDATE users SET tokens_valid_after = $1 WHERE id = $2",
+            Utc::now(),
+            id
+        )
+        .execute(pool)
+        .timed("users.revoke_tokens")
+        .await?;
+        
+        Ok(())
+    }
+    
+    #[tracing::instrument(name = "users.delete", skip_all, fields(user_id = %id), err)]
     pub async fn delete(pool: &PgPool, id: Uuid) -> Result<(), AppError> {
         sqlx::query!("DELETE FROM users WHERE id = $1", id)
             .execute(pool)
+            .timed("users.delete")
             .await?;
         
         Ok(())
     }
     
+    #[tracing::instrument(name = "users.list", skip_all, fields(page = params.page, per_page = params.per_page), err)]
     pub async fn list(pool: &PgPool, params: &PaginationParams) -> Result<Pagination<User>, AppError> {
         let offset = (params.page - 1) * params.per_page;
         
@@ -255,43 +404,74 @@ impl UserRepository {
             offset as i64
         )
         .fetch_all(pool)
+        .timed("users.list")
         .await?;
         
         let total = sqlx::query_scalar!(
             "SELECT COUNT(*) FROM users WHERE activated = true"
         )
         .fetch_one(pool)
+        .timed("users.list")
         .await?
         .unwrap_or(0) as usize;
         
         Ok(Pagination::new(users, total, params.page, params.per_page))
     }
     
+    #[tracing::instrument(name = "users.get_stats", skip_all, fields(user_id = %user_id), err)]
     pub async fn get_stats(pool: &PgPool, user_id: Uuid) -> Result<(i64, i64, i64), AppError> {
         let microposts_count = sqlx::query_scalar!(
             "SELECT COUNT(*) FROM microposts WHERE user_id = $1",
             user_id
         )
         .fetch_one(pool)
+        .timed("users.get_stats")
         .await?
         .unwrap_or(0);
         
         let following_count = sqlx::query_scalar!(
-            "SELECT COUNT(*) FROM relationships WHERE follower_id = $1",
+            "SELECT COUNT(*) FROM relationships WHERE follower_id = $1 AND status = 'accepted'",
             user_id
         )
         .fetch_one(pool)
+        .timed("users.get_stats")
         .await?
         .unwrap_or(0);
         
         let followers_count = sqlx::query_scalar!(
-            "SELECT COUNT(*) FROM relationships WHERE followed_id = $1",
+            "SELECT COUNT(*) FROM relationships WHERE followed_id = $1 AND status = 'accepted'",
             user_id
         )
         .fetch_one(pool)
+        .timed("users.get_stats")
         .await?
         .unwrap_or(0);
         
         Ok((microposts_count, following_count, followers_count))
     }
+    
+    /// `get_stats` for many users in one round trip.
+    #[tracing::instrument(name = "users.get_stats_for", skip_all, fields(count = user_ids.len()), err)]
+    pub async fn get_stats_for(pool: &PgPool, user_ids: &[Uuid]) -> Result<HashMap<Uuid, (i64, i64, i64)>, AppError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                u.id,
+                (SELECT COUNT(*) FROM microposts m WHERE m.user_id = u.id) AS "microposts_count!",
+                (SELECT COUNT(*) FROM relationships r WHERE r.follower_id = u.id AND r.status = 'accepted') AS "following_count!",
+                (SELECT COUNT(*) FROM relationships r WHERE r.followed_id = u.id AND r.status = 'accepted') AS "followers_count!"
+            FROM users u
+            WHERE u.id = ANY($1)
+            "#,
+            user_ids
+        )
+        .fetch_all(pool)
+        .timed("users.get_stats_for")
+        .await?;
+        
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.id, (row.microposts_count, row.following_count, row.followers_count)))
+            .collect())
+    }
 }
@@ -0,0 +1,156 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use chrono::{Duration, Utc};
+use crate::models::{WebauthnChallenge, WebauthnCredential};
+use crate::error::AppError;
+use crate::telemetry::QueryTimer;
+
+pub struct WebauthnRepository;
+
+impl WebauthnRepository {
+    pub async fn create_credential(
+        pool: &PgPool,
+        user_id: Uuid,
+        credential_id: &str,
+        name: &str,
+        passkey: serde_json::Value,
+    ) -> Result<WebauthnCredential, AppError> {
+        let credential = sqlx::query_as!(
+            WebauthnCredential,
+            r#"
+            INSERT INTO webauthn_credentials (id, user_id, credential_id, name, passkey, sign_count, created_at)
+            VALUES ($1, $2, $3, $4, $5, 0, $6)
+            RETURNING *
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            credential_id,
+            name,
+            passkey,
+            Utc::now()
+        )
+        .fetch_one(pool)
+        .timed("webauthn.create_credential")
+        .await?;
+        
+        Ok(credential)
+    }
+    
+    pub async fn list_for_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<WebauthnCredential>, AppError> {
+        let credentials = sqlx::query_as!(
+            WebauthnCredential,
+            "SELECT * FROM webauthn_credentials WHERE user_id = $1 ORDER BY created_at",
+            user_id
+        )
+        .fetch_all(pool)
+        .timed("webauthn.list_for_user")
+        .await?;
+        
+        Ok(credentials)
+    }
+    
+    pub async fn has_credentials(pool: &PgPool, user_id: Uuid) -> Result<bool, AppError> {
+        let exists = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM webauthn_credentials WHERE user_id = $1)",
+            user_id
+        )
+        .fetch_one(pool)
+        .timed("webauthn.has_credentials")
+        .await?
+        .unwrap_or(false);
+        
+        Ok(exists)
+    }
+    
+    pub async fn find_by_credential_id(pool: &PgPool, credential_id: &str) -> Result<Option<WebauthnCredential>, AppError> {
+        let credential = sqlx::query_as!(
+            WebauthnCredential,
+            "SELECT * FROM webauthn_credentials WHERE credential_id = $1",
+            credential_id
+        )
+        .fetch_optional(pool)
+        .timed("webauthn.find_by_credential_id")
+        .await?;
+        
+        Ok(credential)
+    }
+    
+    pub async fn record_use(pool: &PgPool, id: Uuid, passkey: serde_json::Value, sign_count: i64) -> Result<(), AppError> {
+        sqlx::query!(
+            "UPDATE webauthn_credentials SET passkey = $1, sign_count = $2, last_used_at = $3 WHERE id = $4",
+            passkey,
+            sign_count,
+            Utc::now(),
+            id
+        )
+        .execute(pool)
+        .timed("webauthn.record_use")
+        .await?;
+        
+        Ok(())
+    }
+    
+    pub async fn delete_credential(pool: &PgPool, id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+        let result = sqlx::query!(
+            "DELETE FROM webauthn_credentials WHERE id = $1 AND user_id = $2",
+            id,
+            user_id
+        )
+        .execute(pool)
+        .timed("webauthn.delete_credential")
+        .await?;
+        
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Passkey not found".to_string()));
+        }
+        
+        Ok(())
+    }
+    
+    /// Ceremony state lives for five minutes, long enough for the browser prompt.
+    pub async fn create_challenge(pool: &PgPool, user_id: Uuid, kind: &str, state: serde_json::Value) -> Result<WebauthnChallenge, AppError> {
+        let challenge = sqlx::query_as!(
+            WebauthnChallenge,
+            r#"
+            INSERT INTO webauthn_challenges (id, user_id, kind, state, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            kind,
+            state,
+            Utc::now() + Duration::minutes(5),
+            Utc::now()
+        )
+        .fetch_one(pool)
+        .timed("webauthn.create_challenge")
+        .await?;
+        
+        Ok(challenge)
+    }
+    
+    /// Deletes and returns an unexpired challenge so each one can only be answered once.
+    pub async fn take_challenge(pool: &PgPool, id: Uuid, kind: &str) -> Result<Option<WebauthnChallenge>, AppError> {
+        let challenge = sqlx::query_as!(
+            WebauthnChallenge,
+            "DELETE FROM webauthn_challenges WHERE id = $1 AND kind = $2 AND expires_at > NOW() RETURNING *",
+            id,
+            kind
+        )
+        .fetch_optional(pool)
+        .timed("webauthn.take_challenge")
+        .await?;
+        
+        Ok(challenge)
+    }
+    
+    pub async fn delete_expired_challenges(pool: &PgPool) -> Result<u64, AppError> {
+        let result = sqlx::query!("DELETE FROM webauthn_challenges WHERE expires_at <= NOW()")
+            .execute(pool)
+            .timed("webauthn.delete_expired_challenges")
+            .await?;
+        
+        Ok(result.rows_affected())
+    }
+}
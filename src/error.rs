@@ -1,10 +1,23 @@
+use std::collections::BTreeMap;
+use askama::Template;
 use axum::{
-    http::StatusCode,
+    body::Body,
+    extract::{rejection::{JsonRejection, PathRejection, QueryRejection}, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use serde_json::{json, Value};
 use thiserror::Error;
+use validator::{ValidationError, ValidationErrors};
+use crate::{
+    app::AppState,
+    auth::is_api_request,
+    templates::{ForbiddenTemplate, NotFoundTemplate, ServerErrorTemplate, UnprocessableTemplate},
+};
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
 
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -14,15 +27,42 @@ pub enum AppError {
     #[error("Validation error: {0}")]
     Validation(String),
     
+    #[error("Validation error: {0}")]
+    ValidationErrors(#[from] ValidationErrors),
+    
     #[error("Authentication error: {0}")]
     Unauthorized(String),
     
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    
     #[error("Not found: {0}")]
     NotFound(String),
     
     #[error("Bad request: {0}")]
     BadRequest(String),
     
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    
+    #[error("Unprocessable entity: {0}")]
+    UnprocessableEntity(String),
+    
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+    
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+    
+    #[error("Not acceptable: {0}")]
+    NotAcceptable(String),
+    
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+    
+    #[error("Too many requests, retry after {0} seconds")]
+    TooManyRequests(u64),
+    
     #[error("Internal server error: {0}")]
     Internal(String),
     
@@ -33,35 +73,397 @@ pub enum AppError {
     Io(#[from] std::io::Error),
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match &self {
-            AppError::Database(e) => {
-                tracing::error!("Database error: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+/// What a client is told about an error. Attached to error responses as an
+/// extension so `render_error_responses` can add the request id and pick the format.
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    pub status: StatusCode,
+    /// Stable, machine-readable identifier such as `not_found` or `validation_failed`.
+    pub code: &'static str,
+    pub message: String,
+    pub errors: BTreeMap<String, Vec<String>>,
+}
+
+impl ErrorReport {
+    fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            errors: BTreeMap::new(),
+        }
+    }
+    
+    fn with_field(mut self, field: &str, message: &str) -> Self {
+        self.errors.entry(field.to_string()).or_default().push(message.to_string());
+        self
+    }
+    
+    /// Sentences suitable for a form's error list, e.g. "Email is not a valid email address".
+    pub fn full_messages(&self) -> Vec<String> {
+        if self.errors.is_empty() {
+            return vec![self.message.clone()];
+        }
+        
+        self.errors
+            .iter()
+            .flat_map(|(field, messages)| {
+                let field = humanize(field);
+                messages.iter().map(move |message| format!("{} {}", field, message))
+            })
+            .collect()
+    }
+    
+    pub fn to_json(&self, request_id: Option<&str>) -> Value {
+        let mut body = json!({
+            "error": self.message,
+            "code": self.code,
+        });
+        if !self.errors.is_empty() {
+            body["errors"] = json!(self.errors);
+        }
+        if let Some(request_id) = request_id {
+            body["request_id"] = json!(request_id);
+        }
+        body
+    }
+    
+    /// RFC 7807 problem details, with our extension members alongside.
+    pub fn to_problem(&self, request_id: Option<&str>, instance: &str) -> Value {
+        let mut body = json!({
+            "type": "about:blank",
+            "title": self.status.canonical_reason().unwrap_or("Error"),
+            "status": self.status.as_u16(),
+            "detail": self.message,
+            "instance": instance,
+            "code": self.code,
+        });
+        if !self.errors.is_empty() {
+            body["errors"] = json!(self.errors);
+        }
+        if let Some(request_id) = request_id {
+            body["request_id"] = json!(request_id);
+        }
+        body
+    }
+}
+
+impl AppError {
+    /// Builds the client-facing report, logging details that must not leak.
+    pub fn report(&self) -> ErrorReport {
+        match self {
+            AppError::Database(e) => match e.as_database_error().filter(|db| db.is_unique_violation()) {
+                Some(db) => unique_violation(db.constraint()),
+                None => {
+                    tracing::error!("Database error: {}", e);
+                    ErrorReport::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", "Internal server error")
+                }
+            },
+            AppError::Validation(msg) => ErrorReport::new(StatusCode::BAD_REQUEST, "validation_failed", msg.as_str()),
+            AppError::ValidationErrors(errors) => {
+                let mut report = ErrorReport::new(StatusCode::UNPROCESSABLE_ENTITY, "validation_failed", "Validation failed");
+                for (field, field_errors) in errors.field_errors() {
+                    for error in field_errors {
+                        report = report.with_field(field, &describe(error));
+                    }
+                }
+                report
             }
-            AppError::Validation(msg) => (StatusCode::BAD_REQUEST, msg.as_str()),
-            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg.as_str()),
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.as_str()),
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.as_str()),
+            AppError::Unauthorized(msg) => ErrorReport::new(StatusCode::UNAUTHORIZED, "unauthorized", msg.as_str()),
+            AppError::Forbidden(msg) => ErrorReport::new(StatusCode::FORBIDDEN, "forbidden", msg.as_str()),
+            AppError::NotFound(msg) => ErrorReport::new(StatusCode::NOT_FOUND, "not_found", msg.as_str()),
+            AppError::BadRequest(msg) => ErrorReport::new(StatusCode::BAD_REQUEST, "bad_request", msg.as_str()),
+            AppError::Conflict(msg) => ErrorReport::new(StatusCode::CONFLICT, "conflict", msg.as_str()),
+            AppError::UnprocessableEntity(msg) => ErrorReport::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_body", msg.as_str()),
+            AppError::PayloadTooLarge(msg) => ErrorReport::new(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", msg.as_str()),
+            AppError::UnsupportedMediaType(msg) => ErrorReport::new(StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type", msg.as_str()),
+            AppError::NotAcceptable(msg) => ErrorReport::new(StatusCode::NOT_ACCEPTABLE, "not_acceptable", msg.as_str()),
+            AppError::PreconditionFailed(msg) => ErrorReport::new(StatusCode::PRECONDITION_FAILED, "precondition_failed", msg.as_str()),
+            AppError::TooManyRequests(_) => ErrorReport::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                "rate_limited",
+                "Too many requests, please try again later",
+            ),
             AppError::Internal(msg) => {
                 tracing::error!("Internal error: {}", msg);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+                ErrorReport::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", "Internal server error")
             }
             AppError::Config(msg) => {
                 tracing::error!("Config error: {}", msg);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Configuration error")
+                ErrorReport::new(StatusCode::INTERNAL_SERVER_ERROR, "configuration_error", "Configuration error")
             }
             AppError::Io(e) => {
                 tracing::error!("IO error: {}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
+                ErrorReport::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", "Internal server error")
             }
-        };
+        }
+    }
+}
+
+/// Keeps the status axum picked for an extractor rejection, with its explanation
+/// as the message.
+fn rejection(status: StatusCode, message: String) -> AppError {
+    match status {
+        StatusCode::UNPROCESSABLE_ENTITY => AppError::UnprocessableEntity(message),
+        StatusCode::PAYLOAD_TOO_LARGE => AppError::PayloadTooLarge(message),
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => AppError::UnsupportedMediaType(message),
+        status if status.is_server_error() => AppError::Internal(message),
+        _ => AppError::BadRequest(message),
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(e: JsonRejection) -> Self {
+        rejection(e.status(), e.body_text())
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(e: PathRejection) -> Self {
+        rejection(e.status(), e.body_text())
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(e: QueryRejection) -> Self {
+        rejection(e.status(), e.body_text())
+    }
+}
+
+/// Maps unique constraints to the conflict clients should see.
+fn unique_violation(constraint: Option<&str>) -> ErrorReport {
+    let conflict = |message: &str| ErrorReport::new(StatusCode::CONFLICT, "conflict", message);
+    
+    match constraint {
+        Some("users_email_lower_key" | "users_email_key") => conflict("Email has already been taken").with_field("email", "has already been taken"),
+        Some("users_username_lower_key") => conflict("Username has already been taken").with_field("username", "has already been taken"),
+        Some("unique_relationships") => conflict("Already following this user"),
+        Some("unique_user_identities") => conflict("This identity is already linked to an account"),
+        Some("webauthn_credentials_credential_id_key") => conflict("This passkey is already registered"),
+        _ => conflict("Resource already exists"),
+    }
+}
+
+fn humanize(field: &str) -> String {
+    let field = field.replace('_', " ");
+    let mut chars = field.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => field,
+    }
+}
+
+/// Human-readable message for one `validator` failure.
+fn describe(error: &ValidationError) -> String {
+    if let Some(message) = &error.message {
+        return message.to_string();
+    }
+    
+    let param = |name: &str| error.params.get(name).map(|value| value.to_string());
+    match (error.code.as_ref(), param("min"), param("max")) {
+        ("length", Some(min), Some(max)) => format!("must be between {} and {} characters", min, max),
+        ("length", Some(min), None) => format!("must be at least {} characters", min),
+        ("length", None, Some(max)) => format!("must be at most {} characters", max),
+        ("range", Some(min), Some(max)) => format!("must be between {} and {}", min, max),
+        ("email", _, _) => "is not a valid email address".to_string(),
+        ("url", _, _) => "is not a valid URL".to_string(),
+        ("required", _, _) => "is required".to_string(),
+        ("must_match", _, _) => "does not match".to_string(),
+        _ => "is invalid".to_string(),
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let report = self.report();
+        let mut response = (report.status, Json(report.to_json(None))).into_response();
+        
+        if let AppError::TooManyRequests(retry_after) = self {
+            response.headers_mut().insert(header::RETRY_AFTER, retry_after.into());
+        }
+        
+        response.extensions_mut().insert(report);
+        response
+    }
+}
 
-        let body = Json(json!({
-            "error": error_message,
-        }));
+/// Re-renders error bodies with the request id. Browser routes get an HTML page,
+/// or are sent to the login form when they need a session; handlers with a form
+/// re-render it themselves. API routes get JSON, as problem details when the
+/// client accepts `application/problem+json` or `server.problem_details` is set.
+pub async fn render_error_responses(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let wants_problem = state.config.server.problem_details
+        || request
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .map_or(false, |accept| accept.contains(PROBLEM_JSON));
+    let wants_html = !is_api_request(&request)
+        && request
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .map_or(false, |accept| accept.contains("text/html"));
+    let instance = request.uri().path().to_string();
+    
+    let response = next.run(request).await;
+    let Some(report) = response.extensions().get::<ErrorReport>().cloned() else {
+        return response;
+    };
+    
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    
+    if wants_html && report.status == StatusCode::UNAUTHORIZED {
+        parts.status = StatusCode::SEE_OTHER;
+        parts.headers.insert(header::LOCATION, HeaderValue::from_static("/login"));
+        return Response::from_parts(parts, Body::empty());
+    }
+    if wants_html {
+        parts.status = report.status;
+        parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/html; charset=utf-8"));
+        return Response::from_parts(parts, Body::from(render_error_page(&report, request_id)));
+    }
+    
+    let (body, content_type) = if wants_problem {
+        (report.to_problem(request_id.as_deref(), &instance), PROBLEM_JSON)
+    } else {
+        (report.to_json(request_id.as_deref()), "application/json")
+    };
+    
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    Response::from_parts(parts, Body::from(body.to_string()))
+}
 
-        (status, body).into_response()
+/// Error pages follow Rails' public/404, 422 and 500 pages, plus 403 for access denied.
+fn render_error_page(report: &ErrorReport, request_id: Option<String>) -> String {
+    let rendered = match report.status {
+        StatusCode::NOT_FOUND => NotFoundTemplate {
+            title: "Page not found".to_string(),
+            request_id,
+        }
+        .render(),
+        StatusCode::FORBIDDEN => ForbiddenTemplate {
+            title: "Access denied".to_string(),
+            message: report.message.clone(),
+            request_id,
+        }
+        .render(),
+        status if status.is_server_error() => ServerErrorTemplate {
+            title: "Something went wrong".to_string(),
+            request_id,
+        }
+        .render(),
+        _ => UnprocessableTemplate {
+            title: "The change you wanted was rejected".to_string(),
+            message: report.message.clone(),
+            messages: if report.errors.is_empty() { Vec::new() } else { report.full_messages() },
+            request_id,
+        }
+        .render(),
+    };
+    
+    rendered.unwrap_or_else(|e| {
+        tracing::error!("Failed to render error page: {}", e);
+        report.message.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use serde_json::json;
+    use sqlx::PgPool;
+    use crate::test_support::*;
+    use super::PROBLEM_JSON;
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn duplicate_emails_are_conflicts(pool: PgPool) {
+        let app = app(pool.clone());
+        create_user(&pool, "Alice", false).await;
+        
+        let (status, body) = post_json(&app, "/api/v1/auth/signup", json!({
+            "name": "Another Alice",
+            "email": "ALICE@example.com",
+            "password": PASSWORD,
+            "password_confirmation": PASSWORD,
+        }))
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "conflict");
+        assert_eq!(body["errors"]["email"], json!(["has already been taken"]));
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn problem_details_are_negotiated(pool: PgPool) {
+        let app = app(pool);
+        let request = |accept: &str| {
+            Request::get("/api/v1/users/00000000-0000-0000-0000-000000000000")
+                .header(header::ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap()
+        };
+        
+        let response = send(&app, request("application/json")).await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        let body = body_json(response).await;
+        assert_eq!(body["code"], "not_found");
+        assert!(body["request_id"].is_string());
+        
+        let response = send(&app, request(PROBLEM_JSON)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+        let body = body_json(response).await;
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["instance"], "/api/v1/users/00000000-0000-0000-0000-000000000000");
+        assert!(body["request_id"].is_string());
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn extractor_rejections_are_app_errors(pool: PgPool) {
+        let app = app(pool);
+        
+        let request = Request::post("/api/v1/auth/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("{not json"))
+            .unwrap();
+        let response = send(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = body_json(response).await;
+        assert_eq!(body["code"], "bad_request");
+        assert!(body["request_id"].is_string());
+        
+        let (status, body) = post_json(&app, "/api/v1/auth/login", json!({ "email": "alice@example.com" })).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "invalid_body");
+        
+        let request = Request::post("/api/v1/auth/login").body(Body::from("{}")).unwrap();
+        let response = send(&app, request).await;
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(body_json(response).await["code"], "unsupported_media_type");
+        
+        let request = Request::get("/api/v1/users/not-a-uuid")
+            .header(header::ACCEPT, PROBLEM_JSON)
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+        let body = body_json(response).await;
+        assert_eq!(body["code"], "bad_request");
+        assert!(body["request_id"].is_string());
     }
 }
@@ -0,0 +1,52 @@
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::Request,
+    http::header::CONTENT_TYPE,
+};
+use crate::error::AppError;
+
+/// The request's `Content-Type`, or an empty string.
+pub fn content_type(request: &Request) -> String {
+    request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Buffers the body up to `limit` (`storage.max_upload_bytes`, the extractors'
+/// limit too) and rebuilds the request around it, so middleware can peek at a
+/// field and still pass the form on.
+pub async fn buffer_body(request: Request, limit: usize) -> Result<(Request, Bytes), AppError> {
+    let (parts, body) = request.into_parts();
+    let bytes = to_bytes(body, limit)
+        .await
+        .map_err(|_| AppError::PayloadTooLarge("Request body too large".to_string()))?;
+    
+    Ok((Request::from_parts(parts, Body::from(bytes.clone())), bytes))
+}
+
+/// Reads a single top-level field from a buffered form body, for middleware that
+/// needs one value (`_method`, `authenticity_token`) without extracting the form.
+pub fn form_field(content_type: &str, body: &[u8], name: &str) -> Option<String> {
+    if content_type.starts_with("application/x-www-form-urlencoded") {
+        form_urlencoded::parse(body)
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    } else if content_type.starts_with("multipart/form-data") {
+        multipart_field(body, name)
+    } else {
+        None
+    }
+}
+
+fn multipart_field(body: &[u8], name: &str) -> Option<String> {
+    let marker = format!("name=\"{}\"", name);
+    let body = String::from_utf8_lossy(body);
+    let start = body.find(&marker)?;
+    let value_start = start + body[start..].find("\r\n\r\n")? + 4;
+    let value_end = value_start + body[value_start..].find("\r\n")?;
+    
+    Some(body[value_start..value_end].to_string())
+}
@@ -0,0 +1,52 @@
+use std::future::Future;
+use async_graphql::{
+    connection::{Connection, Edge, OpaqueCursor},
+    OutputType,
+};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use crate::error::AppError;
+
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/// Keyset position: rows are ordered by `(created_at, id)` descending.
+pub type Cursor = OpaqueCursor<(DateTime<Utc>, Uuid)>;
+
+/// One page of rows plus whether more follow.
+pub struct Page<T> {
+    pub rows: Vec<T>,
+    pub has_next: bool,
+}
+
+impl<T> Page<T> {
+    /// Runs a repository keyset query (the `*_after` methods) for `limit + 1`
+    /// rows past `after`; the extra row only tells whether another page follows.
+    pub async fn fetch<F>(
+        after: Option<Cursor>,
+        limit: usize,
+        query: impl FnOnce(Option<(DateTime<Utc>, Uuid)>, i64) -> F,
+    ) -> Result<Self, AppError>
+    where
+        F: Future<Output = Result<Vec<T>, AppError>>,
+    {
+        let mut rows = query(after.map(|OpaqueCursor(position)| position), limit as i64 + 1).await?;
+        let has_next = rows.len() > limit;
+        rows.truncate(limit);
+        
+        Ok(Self { rows, has_next })
+    }
+    
+    pub fn into_connection<N: OutputType>(self, has_previous: bool, mut edge: impl FnMut(T) -> (Cursor, N)) -> Connection<Cursor, N> {
+        let mut connection = Connection::new(has_previous, self.has_next);
+        connection.edges.extend(self.rows.into_iter().map(|row| {
+            let (cursor, node) = edge(row);
+            Edge::new(cursor, node)
+        }));
+        connection
+    }
+}
+
+pub fn page_size(first: Option<usize>) -> usize {
+    first.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
@@ -0,0 +1,72 @@
+use std::{collections::HashMap, sync::Arc};
+use async_graphql::{dataloader::Loader, SimpleObject};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::{
+    database::{likes::LikeRepository, users::UserRepository},
+    error::AppError,
+    models::User,
+};
+
+pub struct UserLoader(pub PgPool);
+
+impl Loader<Uuid> for UserLoader {
+    type Value = User;
+    type Error = Arc<AppError>;
+    
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, User>, Self::Error> {
+        let users = UserRepository::find_by_ids(&self.0, keys).await?;
+        Ok(users.into_iter().map(|user| (user.id, user)).collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, SimpleObject)]
+pub struct UserStats {
+    pub microposts_count: i64,
+    pub following_count: i64,
+    pub followers_count: i64,
+}
+
+pub struct StatsLoader(pub PgPool);
+
+impl Loader<Uuid> for StatsLoader {
+    type Value = UserStats;
+    type Error = Arc<AppError>;
+    
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, UserStats>, Self::Error> {
+        let stats = UserRepository::get_stats_for(&self.0, keys).await?;
+        Ok(stats
+            .into_iter()
+            .map(|(id, (microposts_count, following_count, followers_count))| {
+                (id, UserStats { microposts_count, following_count, followers_count })
+            })
+            .collect())
+    }
+}
+
+pub struct LikeCountLoader(pub PgPool);
+
+impl Loader<Uuid> for LikeCountLoader {
+    type Value = i64;
+    type Error = Arc<AppError>;
+    
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, i64>, Self::Error> {
+        Ok(LikeRepository::counts(&self.0, keys).await?)
+    }
+}
+
+/// Whether the signed-in viewer liked each micropost; only registered for authenticated requests.
+pub struct LikedLoader {
+    pub db: PgPool,
+    pub viewer_id: Uuid,
+}
+
+impl Loader<Uuid> for LikedLoader {
+    type Value = bool;
+    type Error = Arc<AppError>;
+    
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, bool>, Self::Error> {
+        let liked = LikeRepository::liked_by(&self.db, self.viewer_id, keys).await?;
+        Ok(liked.into_iter().map(|id| (id, true)).collect())
+    }
+}
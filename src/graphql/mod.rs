@@ -0,0 +1,287 @@
+use std::sync::Arc;
+use async_graphql::{
+    connection::{self, Connection, OpaqueCursor},
+    dataloader::DataLoader,
+    Context, EmptySubscription, ErrorExtensions, Object, Schema, Value,
+};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::{extract::State, Extension};
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+use crate::{
+    app::AppState,
+    config::{RateLimitConfig, RateLimitQuota},
+    database::{
+        follow_requests::FollowRequestRepository, likes::LikeRepository, microposts::MicropostRepository,
+        relationships::RelationshipRepository, users::UserRepository,
+    },
+    error::AppError,
+    models::{CreateMicropostRequest, Micropost, Relationship, User},
+    rate_limit::RateLimiter,
+    telemetry,
+};
+
+pub mod connections;
+pub mod loaders;
+pub mod types;
+
+use connections::{page_size, Cursor, Page};
+use loaders::{LikeCountLoader, LikedLoader, StatsLoader, UserLoader};
+use types::{db, viewer};
+
+pub type AppSchema = Schema<Query, Mutation, EmptySubscription>;
+
+const MAX_DEPTH: usize = 10;
+const MAX_COMPLEXITY: usize = 1000;
+
+pub fn build_schema(db: PgPool) -> AppSchema {
+    Schema::build(Query, Mutation, EmptySubscription)
+        .data(db)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// Runs behind `optional_auth_middleware`, so the viewer comes from the same
+/// access-token `Claims` as the REST API. Loaders are per request so their
+/// caches never leak data between viewers.
+pub async fn handler(
+    State(state): State<AppState>,
+    current_user: Option<Extension<User>>,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    let mut request = request
+        .into_inner()
+        .data(DataLoader::new(UserLoader(state.db.clone()), tokio::spawn))
+        .data(DataLoader::new(StatsLoader(state.db.clone()), tokio::spawn))
+        .data(DataLoader::new(LikeCountLoader(state.db.clone()), tokio::spawn))
+        .data(state.rate_limiter.clone())
+        .data(state.config.rate_limit.clone());
+    
+    if let Some(Extension(user)) = current_user {
+        request = request
+            .data(DataLoader::new(LikedLoader { db: state.db.clone(), viewer_id: user.id }, tokio::spawn))
+            .data(user);
+    }
+    
+    state.graphql.execute(request).await.into()
+}
+
+/// Carries the `ErrorReport` code and field errors into GraphQL error extensions.
+pub trait IntoGraphQL<T> {
+    fn into_graphql(self) -> async_graphql::Result<T>;
+}
+
+impl<T> IntoGraphQL<T> for Result<T, AppError> {
+    fn into_graphql(self) -> async_graphql::Result<T> {
+        self.map_err(|e| graphql_error(&e))
+    }
+}
+
+impl<T> IntoGraphQL<T> for Result<T, Arc<AppError>> {
+    fn into_graphql(self) -> async_graphql::Result<T> {
+        self.map_err(|e| graphql_error(&e))
+    }
+}
+
+fn graphql_error(error: &AppError) -> async_graphql::Error {
+    let report = error.report();
+    if report.status.is_server_error() {
+        tracing::error!(error = %error, "GraphQL resolver failed");
+    }
+    
+    let errors = Value::from_json(serde_json::json!(report.errors)).ok();
+    async_graphql::Error::new(report.message).extend_with(|_, extensions| {
+        extensions.set("code", report.code);
+        if let Some(errors) = errors.filter(|_| !report.errors.is_empty()) {
+            extensions.set("errors", errors);
+        }
+    })
+}
+
+fn require_viewer<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a User> {
+    viewer(ctx)
+        .ok_or_else(|| AppError::Unauthorized("Missing authorization header".to_string()))
+        .into_graphql()
+}
+
+/// The REST routes for these mutations sit behind a `RateLimitLayer`, but GraphQL
+/// has one endpoint, so each mutation spends from the same per-user bucket itself.
+async fn throttle(
+    ctx: &Context<'_>,
+    user: &User,
+    scope: &str,
+    quota: fn(&RateLimitConfig) -> RateLimitQuota,
+) -> async_graphql::Result<()> {
+    let quota = quota(ctx.data_unchecked::<RateLimitConfig>());
+    match ctx.data_unchecked::<Arc<RateLimiter>>().take_for_user(scope, user.id, quota).await {
+        Ok(decision) if !decision.allowed => Err(AppError::TooManyRequests(decision.retry_after_secs)).into_graphql(),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            // Fail open, like the layer
+            tracing::error!("Rate limiter unavailable: {}", e);
+            Ok(())
+        }
+    }
+}
+
+/// Fetches a micropost the viewer is allowed to see.
+async fn visible_micropost(ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Micropost> {
+    let micropost = MicropostRepository::find_by_id(db(ctx), id)
+        .await
+        .into_graphql()?
+        .ok_or_else(|| AppError::NotFound("Micropost not found".to_string()))
+        .into_graphql()?;
+    let author = types::load_user(ctx, micropost.user_id).await?;
+    FollowRequestRepository::ensure_can_view(db(ctx), viewer(ctx), &author).await.into_graphql()?;
+    
+    Ok(micropost)
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// The signed-in user, or null for anonymous requests.
+    async fn viewer(&self, ctx: &Context<'_>) -> Option<User> {
+        viewer(ctx).cloned()
+    }
+    
+    async fn user(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Option<User>> {
+        types::loader::<UserLoader>(ctx).load_one(id).await.into_graphql()
+    }
+    
+    /// Looks a user up by handle, following renames like profile URLs do.
+    async fn user_by_username(&self, ctx: &Context<'_>, username: String) -> async_graphql::Result<Option<User>> {
+        if let Some(user) = UserRepository::find_by_username(db(ctx), &username).await.into_graphql()? {
+            return Ok(Some(user));
+        }
+        UserRepository::find_by_previous_username(db(ctx), &username).await.into_graphql()
+    }
+    
+    async fn micropost(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Micropost> {
+        visible_micropost(ctx, id).await
+    }
+    
+    /// Microposts by the viewer and the accounts they follow, newest first.
+    async fn feed(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<Cursor, Micropost>> {
+        let user = require_viewer(ctx)?;
+        
+        connection::query(after, None, first, None, |after: Option<Cursor>, _: Option<Cursor>, first, _| async move {
+            let has_previous = after.is_some();
+            let page = Page::fetch(after, page_size(first), |after, limit| MicropostRepository::feed_after(db(ctx), user.id, after, limit))
+                .await
+                .into_graphql()?;
+            Ok::<_, async_graphql::Error>(page.into_connection(has_previous, |micropost| {
+                (OpaqueCursor((micropost.created_at, micropost.id)), micropost)
+            }))
+        })
+        .await
+    }
+}
+
+pub struct Mutation;
+
+#[Object]
+impl Mutation {
+    async fn create_micropost(
+        &self,
+        ctx: &Context<'_>,
+        content: String,
+        picture: Option<String>,
+    ) -> async_graphql::Result<Micropost> {
+        let user = require_viewer(ctx)?;
+        let req = CreateMicropostRequest { content, picture };
+        req.validate().map_err(AppError::from).into_graphql()?;
+        throttle(ctx, user, "microposts", |limits| limits.microposts).await?;
+        
+        let micropost = MicropostRepository::create(db(ctx), user.id, &req).await.into_graphql()?;
+        telemetry::record_micropost_created();
+        
+        Ok(micropost)
+    }
+    
+    /// Pending until approved when the target account is private.
+    async fn follow(&self, ctx: &Context<'_>, user_id: Uuid) -> async_graphql::Result<Relationship> {
+        let user = require_viewer(ctx)?;
+        if user_id == user.id {
+            return Err(AppError::BadRequest("You cannot follow yourself".to_string())).into_graphql();
+        }
+        throttle(ctx, user, "relationships", |limits| limits.relationships).await?;
+        
+        let followed = UserRepository::find_by_id(db(ctx), user_id)
+            .await
+            .into_graphql()?
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))
+            .into_graphql()?;
+        let relationship = FollowRequestRepository::follow(db(ctx), user.id, &followed).await.into_graphql()?;
+        telemetry::record_follow();
+        
+        Ok(relationship)
+    }
+    
+    /// Also withdraws a pending follow request. Returns whether anything was removed.
+    async fn unfollow(&self, ctx: &Context<'_>, user_id: Uuid) -> async_graphql::Result<bool> {
+        let user = require_viewer(ctx)?;
+        RelationshipRepository::unfollow(db(ctx), user.id, user_id).await.into_graphql()
+    }
+    
+    async fn like_micropost(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Micropost> {
+        let user = require_viewer(ctx)?;
+        let micropost = visible_micropost(ctx, id).await?;
+        LikeRepository::like(db(ctx), user.id, micropost.id).await.into_graphql()?;
+        
+        Ok(micropost)
+    }
+    
+    async fn unlike_micropost(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Micropost> {
+        let user = require_viewer(ctx)?;
+        let micropost = visible_micropost(ctx, id).await?;
+        LikeRepository::unlike(db(ctx), user.id, micropost.id).await.into_graphql()?;
+        
+        Ok(micropost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Method;
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+    use crate::{app::AppState, config::RateLimitQuota, models::User, test_support::*};
+    
+    async fn graphql(app: &axum::Router, user: &User, query: &str) -> Value {
+        let response = send(app, json_request(Method::POST, "/graphql", user, json!({ "query": query }))).await;
+        body_json(response).await
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn mutations_spend_the_rest_rate_limits(pool: PgPool) {
+        let mut config = config();
+        config.rate_limit.microposts = RateLimitQuota { burst: 1, period_secs: 60 };
+        config.rate_limit.relationships = RateLimitQuota { burst: 1, period_secs: 60 };
+        let app = crate::create_app(AppState::new(pool.clone(), config).unwrap());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", false).await;
+        let carol = create_user(&pool, "Carol", false).await;
+        
+        let post = r#"mutation { createMicropost(content: "Hello") { id } }"#;
+        assert!(graphql(&app, &alice, post).await.get("errors").is_none());
+        let refused = graphql(&app, &alice, post).await;
+        assert_eq!(refused["errors"][0]["extensions"]["code"], "rate_limited");
+        
+        let follow = |user: &User| format!(r#"mutation {{ follow(userId: "{}") {{ status }} }}"#, user.id);
+        assert!(graphql(&app, &alice, &follow(&bob)).await.get("errors").is_none());
+        let refused = graphql(&app, &alice, &follow(&carol)).await;
+        assert_eq!(refused["errors"][0]["extensions"]["code"], "rate_limited");
+        
+        // Buckets are per user
+        assert!(graphql(&app, &bob, post).await.get("errors").is_none());
+    }
+}
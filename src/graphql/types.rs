@@ -0,0 +1,238 @@
+use async_graphql::{
+    connection::{self, Connection, OpaqueCursor},
+    dataloader::DataLoader,
+    Context, Object, Result,
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::{
+    database::{follow_requests::FollowRequestRepository, microposts::MicropostRepository, relationships::RelationshipRepository},
+    error::AppError,
+    models::{Micropost, Relationship, User},
+};
+use super::{
+    loaders::{LikeCountLoader, LikedLoader, StatsLoader, UserLoader, UserStats},
+    connections::{page_size, Cursor, Page},
+    IntoGraphQL,
+};
+
+pub fn db<'a>(ctx: &Context<'a>) -> &'a PgPool {
+    ctx.data_unchecked::<PgPool>()
+}
+
+pub fn viewer<'a>(ctx: &Context<'a>) -> Option<&'a User> {
+    ctx.data_opt::<User>()
+}
+
+pub fn loader<'a, T: Send + Sync + 'static>(ctx: &Context<'a>) -> &'a DataLoader<T> {
+    ctx.data_unchecked::<DataLoader<T>>()
+}
+
+pub async fn load_user(ctx: &Context<'_>, id: Uuid) -> Result<User> {
+    loader::<UserLoader>(ctx)
+        .load_one(id)
+        .await
+        .into_graphql()?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))
+        .into_graphql()
+}
+
+/// Relationship rows whose user has since been deleted are dropped from the page.
+async fn relationship_users(
+    ctx: &Context<'_>,
+    mut page: Page<Relationship>,
+    has_previous: bool,
+    user_id: fn(&Relationship) -> Uuid,
+) -> Result<Connection<Cursor, User>> {
+    let mut users = loader::<UserLoader>(ctx)
+        .load_many(page.rows.iter().map(user_id))
+        .await
+        .into_graphql()?;
+    page.rows.retain(|relationship| users.contains_key(&user_id(relationship)));
+    
+    Ok(page.into_connection(has_previous, |relationship| {
+        let user = users.remove(&user_id(&relationship)).expect("retained above");
+        (OpaqueCursor((relationship.created_at, relationship.id)), user)
+    }))
+}
+
+#[Object]
+impl User {
+    async fn id(&self) -> Uuid {
+        self.id
+    }
+    
+    async fn name(&self) -> &str {
+        &self.name
+    }
+    
+    /// Only visible to the account owner and admins.
+    async fn email(&self, ctx: &Context<'_>) -> Option<&str> {
+        viewer(ctx)
+            .filter(|viewer| viewer.id == self.id || viewer.admin)
+            .map(|_| self.email.as_str())
+    }
+    
+    async fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+    
+    async fn bio(&self) -> Option<&str> {
+        self.bio.as_deref()
+    }
+    
+    async fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+    
+    async fn website(&self) -> Option<&str> {
+        self.website.as_deref()
+    }
+    
+    async fn banner(&self) -> Option<&str> {
+        self.banner.as_deref()
+    }
+    
+    #[graphql(name = "gravatarUrl")]
+    async fn resolve_gravatar_url(&self, #[graphql(default = 80)] size: u32) -> String {
+        self.gravatar_url(size)
+    }
+    
+    async fn admin(&self) -> bool {
+        self.admin
+    }
+    
+    async fn is_private(&self) -> bool {
+        self.is_private
+    }
+    
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+    
+    async fn stats(&self, ctx: &Context<'_>) -> Result<UserStats> {
+        let stats = loader::<StatsLoader>(ctx).load_one(self.id).await.into_graphql()?;
+        Ok(stats.unwrap_or_default())
+    }
+    
+    async fn microposts(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<Cursor, Micropost>> {
+        FollowRequestRepository::ensure_can_view(db(ctx), viewer(ctx), self).await.into_graphql()?;
+        
+        connection::query(after, None, first, None, |after: Option<Cursor>, _: Option<Cursor>, first, _| async move {
+            let has_previous = after.is_some();
+            let page = Page::fetch(after, page_size(first), |after, limit| MicropostRepository::list_for_user_after(db(ctx), self.id, after, limit))
+                .await
+                .into_graphql()?;
+            Ok::<_, async_graphql::Error>(page.into_connection(has_previous, |micropost| {
+                (OpaqueCursor((micropost.created_at, micropost.id)), micropost)
+            }))
+        })
+        .await
+    }
+    
+    async fn followers(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<Cursor, User>> {
+        FollowRequestRepository::ensure_can_view(db(ctx), viewer(ctx), self).await.into_graphql()?;
+        
+        connection::query(after, None, first, None, |after: Option<Cursor>, _: Option<Cursor>, first, _| async move {
+            let has_previous = after.is_some();
+            let page = Page::fetch(after, page_size(first), |after, limit| RelationshipRepository::followers_after(db(ctx), self.id, after, limit))
+                .await
+                .into_graphql()?;
+            relationship_users(ctx, page, has_previous, |relationship| relationship.follower_id).await
+        })
+        .await
+    }
+    
+    async fn following(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<Cursor, User>> {
+        FollowRequestRepository::ensure_can_view(db(ctx), viewer(ctx), self).await.into_graphql()?;
+        
+        connection::query(after, None, first, None, |after: Option<Cursor>, _: Option<Cursor>, first, _| async move {
+            let has_previous = after.is_some();
+            let page = Page::fetch(after, page_size(first), |after, limit| RelationshipRepository::following_after(db(ctx), self.id, after, limit))
+                .await
+                .into_graphql()?;
+            relationship_users(ctx, page, has_previous, |relationship| relationship.followed_id).await
+        })
+        .await
+    }
+}
+
+#[Object]
+impl Micropost {
+    async fn id(&self) -> Uuid {
+        self.id
+    }
+    
+    async fn content(&self) -> &str {
+        &self.content
+    }
+    
+    async fn picture(&self) -> Option<&str> {
+        self.picture.as_deref()
+    }
+    
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+    
+    async fn author(&self, ctx: &Context<'_>) -> Result<User> {
+        load_user(ctx, self.user_id).await
+    }
+    
+    async fn likes_count(&self, ctx: &Context<'_>) -> Result<i64> {
+        let count = loader::<LikeCountLoader>(ctx).load_one(self.id).await.into_graphql()?;
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// Always false for anonymous requests.
+    async fn viewer_has_liked(&self, ctx: &Context<'_>) -> Result<bool> {
+        match ctx.data_opt::<DataLoader<LikedLoader>>() {
+            Some(liked) => Ok(liked.load_one(self.id).await.into_graphql()?.unwrap_or(false)),
+            None => Ok(false),
+        }
+    }
+}
+
+#[Object]
+impl Relationship {
+    async fn id(&self) -> Uuid {
+        self.id
+    }
+    
+    /// `pending` until a private account approves the request, then `accepted`.
+    async fn status(&self) -> &str {
+        &self.status
+    }
+    
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+    
+    async fn accepted_at(&self) -> Option<DateTime<Utc>> {
+        self.accepted_at
+    }
+    
+    async fn follower(&self, ctx: &Context<'_>) -> Result<User> {
+        load_user(ctx, self.follower_id).await
+    }
+    
+    async fn followed(&self, ctx: &Context<'_>) -> Result<User> {
+        load_user(ctx, self.followed_id).await
+    }
+}
@@ -0,0 +1,260 @@
+use axum::{extract::State, http::StatusCode};
+use validator::Validate;
+use crate::{
+    app::AppState,
+    auth::{generate_token, hash_password, token_digest, verify_password, JwtService},
+    database::users::UserRepository,
+    error::AppError,
+    extract::{Json, Path},
+    handlers::api::{mfa::{challenge, issue_auth_response}, users::{register, user_response}},
+    mailer,
+    models::{
+        AuthResponse, CreateUserRequest, EmailRequest, LoginRequest, LoginResponse, RefreshTokenRequest,
+        ResetPasswordRequest, User, VersionedUserResponse,
+    },
+    versioning::ApiVersion,
+};
+
+/// Checks an email/password pair. Shared with the HTML login form.
+pub async fn authenticate_password(state: &AppState, email: &str, password: &str) -> Result<User, AppError> {
+    let user = UserRepository::find_by_email(&state.db, email.trim())
+        .await?
+        .filter(|user| verify_password(password, &user.password_digest))
+        .ok_or_else(|| AppError::Unauthorized("Invalid email/password combination".to_string()))?;
+    
+    if !user.activated {
+        return Err(AppError::Unauthorized("Account not activated. Check your email for the activation link".to_string()));
+    }
+    
+    Ok(user)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Signed in, or an MFA challenge", body = LoginResponse),
+        (status = 401, description = "Invalid email or password"),
+        (status = 429, description = "Too many attempts"),
+    )
+)]
+pub async fn login(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    Json(req): Json<LoginRequest>,
+) -> Result<Json<LoginResponse>, AppError> {
+    let user = authenticate_password(&state, &req.email, &req.password).await?;
+    if let Some(challenge) = challenge(&state, &user).await? {
+        return Ok(Json(LoginResponse::MfaRequired(challenge)));
+    }
+    
+    Ok(Json(LoginResponse::Authenticated(issue_auth_response(&state, version, &user).await?)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/signup",
+    tag = "auth",
+    request_body = CreateUserRequest,
+    responses(
+        (status = 201, description = "Account created; activation email sent", body = UserResponse),
+        (status = 409, description = "Email already taken"),
+        (status = 422, description = "Validation failed"),
+    )
+)]
+pub async fn signup(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    Json(req): Json<CreateUserRequest>,
+) -> Result<(StatusCode, Json<VersionedUserResponse>), AppError> {
+    let user = register(&state, &req).await?;
+    
+    // Whoever just registered is the owner
+    Ok((StatusCode::CREATED, Json(user_response(&state, &user).await?.for_version(version, Some(&user)))))
+}
+
+/// Resolves the user behind a refresh token that hasn't been revoked.
+async fn refresh_token_user(state: &AppState, refresh_token: &str) -> Result<User, AppError> {
+    let claims = JwtService::new(&state.config.auth.jwt_secret).verify_token(refresh_token)?;
+    if claims.token_type != "refresh" {
+        return Err(AppError::Unauthorized("Invalid token type".to_string()));
+    }
+    
+    let user_id = claims.sub.parse().map_err(|_| AppError::Unauthorized("Invalid user ID".to_string()))?;
+    let user = UserRepository::find_by_id(&state.db, user_id)
+        .await?
+        .filter(|user| user.activated)
+        .ok_or_else(|| AppError::Unauthorized("Invalid token".to_string()))?;
+    
+    if user.tokens_revoked(claims.iat) {
+        return Err(AppError::Unauthorized("Token has been revoked".to_string()));
+    }
+    
+    Ok(user)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/refresh",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Fresh token pair", body = AuthResponse),
+        (status = 401, description = "Refresh token invalid or revoked"),
+    )
+)]
+pub async fn refresh(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    Json(req): Json<RefreshTokenRequest>,
+) -> Result<Json<AuthResponse>, AppError> {
+    let user = refresh_token_user(&state, &req.refresh_token).await?;
+    
+    Ok(Json(issue_auth_response(&state, version, &user).await?))
+}
+
+/// Tokens are stateless, so revoking one signs the user out everywhere:
+/// every access, refresh and session token issued so far stops working.
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/revoke",
+    tag = "auth",
+    responses((status = 204, description = "Refresh token revoked"))
+)]
+pub async fn revoke(
+    State(state): State<AppState>,
+    Json(req): Json<RefreshTokenRequest>,
+) -> Result<StatusCode, AppError> {
+    let user = refresh_token_user(&state, &req.refresh_token).await?;
+    UserRepository::revoke_tokens(&state.db, user.id).await?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Emails a fresh activation link to an unactivated account. Shared with the
+/// HTML form; unknown or already-activated addresses are silently ignored.
+pub async fn send_activation(state: &AppState, email: &str) -> Result<(), AppError> {
+    let Some(user) = UserRepository::find_by_email(&state.db, email).await?.filter(|user| !user.activated) else {
+        return Ok(());
+    };
+    
+    let token = generate_token();
+    let user = UserRepository::set_activation_token(&state.db, user.id, &token_digest(&token)).await?;
+    mailer::send_account_activation(&state.config, &user, &token).await
+}
+
+/// Emails a password reset link, valid for two hours. Shared with the HTML
+/// form; unknown or unactivated addresses are silently ignored.
+pub async fn send_reset(state: &AppState, email: &str) -> Result<(), AppError> {
+    let Some(user) = UserRepository::find_by_email(&state.db, email).await?.filter(|user| user.activated) else {
+        return Ok(());
+    };
+    
+    let token = generate_token();
+    let user = UserRepository::set_reset_token(&state.db, user.id, &token_digest(&token)).await?;
+    mailer::send_password_reset(&state.config, &user, &token).await
+}
+
+/// Activates the account behind an emailed token. Shared with the HTML link.
+pub async fn activate(state: &AppState, token: &str) -> Result<User, AppError> {
+    let user = UserRepository::find_by_activation_token(&state.db, &token_digest(token))
+        .await?
+        .filter(|user| !user.activated)
+        .ok_or_else(|| AppError::BadRequest("Invalid activation link".to_string()))?;
+    
+    UserRepository::activate(&state.db, user.id).await
+}
+
+/// Sets a new password through an emailed token and signs out every existing
+/// session. Shared with the HTML form.
+pub async fn change_forgotten_password(state: &AppState, token: &str, req: &ResetPasswordRequest) -> Result<User, AppError> {
+    let user = UserRepository::find_by_reset_token(&state.db, &token_digest(token))
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Password reset link is invalid or has expired".to_string()))?;
+    req.validate()?;
+    
+    UserRepository::revoke_tokens(&state.db, user.id).await?;
+    UserRepository::set_password(&state.db, user.id, &hash_password(&req.password)?).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/account_activations",
+    tag = "auth",
+    responses((status = 202, description = "Activation email sent if the account exists"))
+)]
+pub async fn resend_activation(
+    State(state): State<AppState>,
+    Json(req): Json<EmailRequest>,
+) -> Result<StatusCode, AppError> {
+    req.validate()?;
+    send_activation(&state, &req.email).await?;
+    
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/account_activations/{token}",
+    tag = "auth",
+    params(("token" = String, Path, description = "Token from the activation email")),
+    responses(
+        (status = 200, description = "Account activated", body = AuthResponse),
+        (status = 400, description = "Invalid or expired token"),
+    )
+)]
+pub async fn activate_account(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    Path(token): Path<String>,
+) -> Result<Json<AuthResponse>, AppError> {
+    let user = activate(&state, &token).await?;
+    
+    Ok(Json(issue_auth_response(&state, version, &user).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/password_resets",
+    tag = "auth",
+    responses((status = 202, description = "Reset email sent if the account exists"))
+)]
+pub async fn send_password_reset(
+    State(state): State<AppState>,
+    Json(req): Json<EmailRequest>,
+) -> Result<StatusCode, AppError> {
+    req.validate()?;
+    send_reset(&state, &req.email).await?;
+    
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Sets a new password through an emailed token.
+///
+/// The link proves access to the inbox, not the second factor, so MFA accounts
+/// get a challenge rather than tokens.
+#[utoipa::path(
+    post,
+    path = "/api/v1/password_resets/{token}",
+    tag = "auth",
+    params(("token" = String, Path, description = "Token from the reset email")),
+    responses(
+        (status = 200, description = "Password changed; signed in, or an MFA challenge", body = LoginResponse),
+        (status = 400, description = "Invalid or expired token"),
+        (status = 422, description = "Validation failed"),
+    )
+)]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    Path(token): Path<String>,
+    Json(req): Json<ResetPasswordRequest>,
+) -> Result<Json<LoginResponse>, AppError> {
+    let user = change_forgotten_password(&state, &token, &req).await?;
+    if let Some(challenge) = challenge(&state, &user).await? {
+        return Ok(Json(LoginResponse::MfaRequired(challenge)));
+    }
+    
+    Ok(Json(LoginResponse::Authenticated(issue_auth_response(&state, version, &user).await?)))
+}
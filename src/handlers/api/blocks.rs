@@ -0,0 +1,61 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Extension,
+};
+use uuid::Uuid;
+use crate::{
+    app::AppState,
+    database::{blocks::BlockRepository, users::UserRepository},
+    error::AppError,
+    extract::{Json, Path},
+    models::{CreateBlockRequest, User},
+};
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/blocks",
+    tag = "messages",
+    request_body = CreateBlockRequest,
+    responses(
+        (status = 201, description = "User blocked"),
+        (status = 400, description = "Cannot block yourself"),
+        (status = 404, description = "User not found"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Json(req): Json<CreateBlockRequest>,
+) -> Result<StatusCode, AppError> {
+    if req.blocked_id == current_user.id {
+        return Err(AppError::BadRequest("You cannot block yourself".to_string()));
+    }
+    
+    UserRepository::find_by_id(&state.db, req.blocked_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    
+    BlockRepository::block(&state.db, current_user.id, req.blocked_id).await?;
+    
+    Ok(StatusCode::CREATED)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/blocks/{id}",
+    tag = "messages",
+    params(("id" = Uuid, Path, description = "Blocked user id")),
+    responses((status = 204, description = "User unblocked")),
+    security(("bearer" = []))
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(blocked_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    BlockRepository::unblock(&state.db, current_user.id, blocked_id).await?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
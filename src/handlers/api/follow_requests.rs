@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Extension,
+};
+use uuid::Uuid;
+use crate::{
+    app::AppState,
+    database::{follow_requests::FollowRequestRepository, users::UserRepository},
+    error::AppError,
+    extract::{Json, Path},
+    models::{FollowRequestResponse, RelationshipResponse, UpdatePrivacyRequest, User, VersionedUserResponse},
+    versioning::ApiVersion,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/follow_requests",
+    tag = "relationships",
+    responses((status = 200, description = "Pending follow requests", body = [FollowRequestResponse])),
+    security(("bearer" = []))
+)]
+pub async fn index(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+) -> Result<Json<Vec<FollowRequestResponse>>, AppError> {
+    let requests = FollowRequestRepository::list_pending(&state.db, current_user.id).await?;
+    
+    // Requesters are loaded in one query; a request only exists while its follower does
+    let follower_ids: Vec<Uuid> = requests.iter().map(|request| request.follower_id).collect();
+    let followers: HashMap<Uuid, User> = UserRepository::find_by_ids(&state.db, &follower_ids)
+        .await?
+        .into_iter()
+        .map(|user| (user.id, user))
+        .collect();
+    
+    let responses = requests
+        .iter()
+        .filter_map(|request| Some(request.to_follow_request_response(followers.get(&request.follower_id)?)))
+        .collect();
+    
+    Ok(Json(responses))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/follow_requests/{id}/approve",
+    tag = "relationships",
+    params(("id" = Uuid, Path, description = "Follow request id")),
+    responses(
+        (status = 200, description = "Request approved", body = RelationshipResponse),
+        (status = 404, description = "Follow request not found"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn approve(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<RelationshipResponse>, AppError> {
+    let relationship = FollowRequestRepository::approve(&state.db, id, current_user.id).await?;
+    Ok(Json(relationship.to_response()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/follow_requests/{id}",
+    tag = "relationships",
+    params(("id" = Uuid, Path, description = "Follow request id")),
+    responses((status = 204, description = "Request denied")),
+    security(("bearer" = []))
+)]
+pub async fn deny(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    FollowRequestRepository::deny(&state.db, id, current_user.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/account/privacy",
+    tag = "users",
+    request_body = UpdatePrivacyRequest,
+    responses((status = 200, description = "Updated account", body = UserResponse)),
+    security(("bearer" = []))
+)]
+pub async fn update_privacy(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    Extension(current_user): Extension<User>,
+    Json(req): Json<UpdatePrivacyRequest>,
+) -> Result<Json<VersionedUserResponse>, AppError> {
+    let user = UserRepository::set_private(&state.db, current_user.id, req.is_private).await?;
+    
+    // Going public implicitly accepts everyone who was waiting
+    if !user.is_private {
+        FollowRequestRepository::approve_all(&state.db, user.id).await?;
+    }
+    
+    Ok(Json(user.to_response().for_version(version, Some(&current_user))))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use crate::{database::follow_requests::FollowRequestRepository, test_support::*};
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn lists_pending_requests_with_their_followers(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", true).await;
+        let bob = create_user(&pool, "Bob", false).await;
+        let carol = create_user(&pool, "Carol", false).await;
+        FollowRequestRepository::follow(&pool, bob.id, &alice).await.unwrap();
+        FollowRequestRepository::follow(&pool, carol.id, &alice).await.unwrap();
+        
+        let response = send(&app, json_request(Method::GET, "/api/v1/follow_requests", &alice, json!(null))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        
+        let body = body_json(response).await;
+        let mut names: Vec<&str> = body.as_array().unwrap().iter().map(|r| r["follower"]["name"].as_str().unwrap()).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["Bob", "Carol"]);
+    }
+}
@@ -0,0 +1,337 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Extension,
+};
+use uuid::Uuid;
+use validator::Validate;
+use crate::{
+    app::AppState,
+    database::{blocks::BlockRepository, messages::MessageRepository, users::UserRepository},
+    error::AppError,
+    extract::{Json, Path, Query},
+    models::{
+        Conversation, ConversationResponse, CreateConversationRequest, CreateMessageRequest,
+        MessageResponse, ParticipantResponse, User,
+    },
+    utils::pagination::{Pagination, PaginationParams},
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/conversations",
+    tag = "messages",
+    params(
+        ("page" = Option<i64>, Query, description = "Page number, starting at 1"),
+        ("per_page" = Option<i64>, Query, description = "Items per page, at most 100"),
+    ),
+    responses((status = 200, description = "Page of conversations, most recently active first")),
+    security(("bearer" = []))
+)]
+pub async fn index(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Query(params): Query<PaginationParams>,
+) -> Result<Json<Pagination<ConversationResponse>>, AppError> {
+    let conversations = MessageRepository::list_conversations(&state.db, current_user.id, &params).await?;
+    let total = MessageRepository::count_conversations(&state.db, current_user.id).await?;
+    
+    let mut responses = Vec::with_capacity(conversations.len());
+    for conversation in &conversations {
+        responses.push(build_conversation_response(&state, conversation, current_user.id).await?);
+    }
+    
+    Ok(Json(Pagination::new(responses, total, params.page, params.per_page)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/conversations",
+    tag = "messages",
+    request_body = CreateConversationRequest,
+    responses(
+        (status = 201, description = "Conversation started", body = ConversationResponse),
+        (status = 422, description = "Validation failed"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Json(req): Json<CreateConversationRequest>,
+) -> Result<(StatusCode, Json<ConversationResponse>), AppError> {
+    req.validate()?;
+    
+    let mut participant_ids: Vec<Uuid> = req
+        .participant_ids
+        .iter()
+        .copied()
+        .filter(|id| *id != current_user.id)
+        .collect();
+    participant_ids.sort();
+    participant_ids.dedup();
+    
+    if participant_ids.is_empty() {
+        return Err(AppError::BadRequest("A conversation needs at least one other participant".to_string()));
+    }
+    
+    for id in &participant_ids {
+        match UserRepository::find_by_id(&state.db, *id).await? {
+            Some(user) if user.activated => {}
+            _ => return Err(AppError::NotFound("User not found".to_string())),
+        }
+    }
+    
+    if BlockRepository::is_blocked_with_any(&state.db, current_user.id, &participant_ids).await? {
+        return Err(AppError::Forbidden("You cannot message this user".to_string()));
+    }
+    
+    // One-to-one conversations are reused rather than duplicated
+    if let [other_id] = participant_ids[..] {
+        let (conversation, started) = MessageRepository::send_direct(&state.db, current_user.id, other_id, &req.content).await?;
+        let status = if started { StatusCode::CREATED } else { StatusCode::OK };
+        let response = build_conversation_response(&state, &conversation, current_user.id).await?;
+        return Ok((status, Json(response)));
+    }
+    
+    let (conversation, _) = MessageRepository::create_conversation(&state.db, current_user.id, &participant_ids, &req.content).await?;
+    let response = build_conversation_response(&state, &conversation, current_user.id).await?;
+    
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/conversations/{id}/messages",
+    tag = "messages",
+    params(
+        ("id" = Uuid, Path, description = "Conversation id"),
+        ("page" = Option<i64>, Query, description = "Page number, starting at 1"),
+        ("per_page" = Option<i64>, Query, description = "Items per page, at most 100"),
+    ),
+    responses(
+        (status = 200, description = "Page of messages, newest first"),
+        (status = 404, description = "Conversation not found"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn messages(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<PaginationParams>,
+) -> Result<Json<Pagination<MessageResponse>>, AppError> {
+    let conversation = find_conversation_for(&state, id, current_user.id).await?;
+    
+    let messages = MessageRepository::list_messages(&state.db, conversation.id, &params).await?;
+    let total = MessageRepository::count_messages(&state.db, conversation.id).await?;
+    let participants = MessageRepository::participants(&state.db, conversation.id).await?;
+    
+    let responses = messages.iter().map(|m| m.to_response(&participants)).collect();
+    
+    Ok(Json(Pagination::new(responses, total, params.page, params.per_page)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/conversations/{id}/messages",
+    tag = "messages",
+    params(("id" = Uuid, Path, description = "Conversation id")),
+    request_body = CreateMessageRequest,
+    responses(
+        (status = 201, description = "Message sent", body = MessageResponse),
+        (status = 404, description = "Conversation not found"),
+        (status = 422, description = "Validation failed"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create_message(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<CreateMessageRequest>,
+) -> Result<(StatusCode, Json<MessageResponse>), AppError> {
+    req.validate()?;
+    
+    let conversation = find_conversation_for(&state, id, current_user.id).await?;
+    let participants = MessageRepository::participants(&state.db, conversation.id).await?;
+    
+    let others: Vec<Uuid> = participants
+        .iter()
+        .map(|p| p.user_id)
+        .filter(|user_id| *user_id != current_user.id)
+        .collect();
+    
+    if BlockRepository::is_blocked_with_any(&state.db, current_user.id, &others).await? {
+        return Err(AppError::Forbidden("You cannot message this conversation".to_string()));
+    }
+    
+    let message = MessageRepository::create_message(&state.db, conversation.id, current_user.id, &req.content).await?;
+    
+    Ok((StatusCode::CREATED, Json(message.to_response(&participants))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/conversations/{id}/read",
+    tag = "messages",
+    params(("id" = Uuid, Path, description = "Conversation id")),
+    responses((status = 204, description = "Read marker moved to now")),
+    security(("bearer" = []))
+)]
+pub async fn mark_read(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let conversation = find_conversation_for(&state, id, current_user.id).await?;
+    MessageRepository::mark_read(&state.db, conversation.id, current_user.id).await?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Non-participants get a 404 so conversation ids can't be probed.
+async fn find_conversation_for(state: &AppState, id: Uuid, user_id: Uuid) -> Result<Conversation, AppError> {
+    let conversation = MessageRepository::find_conversation(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
+    
+    if !MessageRepository::is_participant(&state.db, conversation.id, user_id).await? {
+        return Err(AppError::NotFound("Conversation not found".to_string()));
+    }
+    
+    Ok(conversation)
+}
+
+async fn build_conversation_response(state: &AppState, conversation: &Conversation, user_id: Uuid) -> Result<ConversationResponse, AppError> {
+    let participants = MessageRepository::participants(&state.db, conversation.id).await?;
+    let last_message = MessageRepository::last_message(&state.db, conversation.id).await?;
+    let unread_count = MessageRepository::unread_count(&state.db, conversation.id, user_id).await?;
+    
+    let mut participant_responses = Vec::with_capacity(participants.len());
+    for participant in &participants {
+        if let Some(user) = UserRepository::find_by_id(&state.db, participant.user_id).await? {
+            participant_responses.push(ParticipantResponse {
+                id: user.id,
+                name: user.name.clone(),
+                gravatar_url: user.gravatar_url(50),
+                last_read_at: participant.last_read_at,
+            });
+        }
+    }
+    
+    Ok(ConversationResponse {
+        id: conversation.id,
+        participants: participant_responses,
+        last_message: last_message.map(|m| m.to_response(&participants)),
+        unread_count,
+        updated_at: conversation.updated_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+    use crate::{
+        database::{blocks::BlockRepository, users::UserRepository},
+        models::{User, MAX_CONVERSATION_PARTICIPANTS},
+        test_support::*,
+    };
+    
+    fn start(creator: &User, participant_ids: &[Uuid]) -> axum::http::Request<axum::body::Body> {
+        json_request(Method::POST, "/api/v1/conversations", creator, json!({ "participant_ids": participant_ids, "content": "hi" }))
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn create_caps_participants_including_the_creator(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let mut others = Vec::new();
+        for i in 0..MAX_CONVERSATION_PARTICIPANTS {
+            others.push(create_user(&pool, &format!("User{}", i), false).await.id);
+        }
+        
+        let response = send(&app, start(&alice, &others)).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        
+        let response = send(&app, start(&alice, &others[1..])).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(body_json(response).await["participants"].as_array().unwrap().len(), MAX_CONVERSATION_PARTICIPANTS);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn blocks_stop_new_conversations_in_either_direction(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", false).await;
+        BlockRepository::block(&pool, bob.id, alice.id).await.unwrap();
+        
+        assert_eq!(send(&app, start(&alice, &[bob.id])).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(send(&app, start(&bob, &[alice.id])).await.status(), StatusCode::FORBIDDEN);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn blocking_mid_conversation_stops_replies(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", false).await;
+        let response = send(&app, start(&alice, &[bob.id])).await;
+        let id = body_json(response).await["id"].as_str().unwrap().to_string();
+        BlockRepository::block(&pool, bob.id, alice.id).await.unwrap();
+        
+        let uri = format!("/api/v1/conversations/{}/messages", id);
+        let response = send(&app, json_request(Method::POST, &uri, &alice, json!({ "content": "still there?" }))).await;
+        
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn conversations_are_hidden_from_non_participants(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", false).await;
+        let carol = create_user(&pool, "Carol", false).await;
+        let response = send(&app, start(&alice, &[bob.id])).await;
+        let id = body_json(response).await["id"].as_str().unwrap().to_string();
+        
+        let uri = format!("/api/v1/conversations/{}/messages", id);
+        let response = send(&app, json_request(Method::GET, &uri, &carol, json!(null))).await;
+        
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn concurrent_first_messages_share_one_direct_conversation(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", false).await;
+        
+        let (first, second) = tokio::join!(send(&app, start(&alice, &[bob.id])), send(&app, start(&bob, &[alice.id])));
+        let mut statuses = [first.status(), second.status()];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::CREATED]);
+        
+        let first = body_json(first).await;
+        let second = body_json(second).await;
+        assert_eq!(first["id"], second["id"]);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn conversations_outlive_their_creator(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", false).await;
+        let carol = create_user(&pool, "Carol", false).await;
+        let response = send(&app, start(&alice, &[bob.id, carol.id])).await;
+        let id = body_json(response).await["id"].as_str().unwrap().to_string();
+        
+        UserRepository::delete(&pool, alice.id).await.unwrap();
+        
+        let uri = format!("/api/v1/conversations/{}/messages", id);
+        let response = send(&app, json_request(Method::GET, &uri, &bob, json!(null))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
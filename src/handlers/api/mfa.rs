@@ -0,0 +1,430 @@
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension,
+};
+use uuid::Uuid;
+use validator::Validate;
+use crate::{
+    app::AppState,
+    auth::{JwtService, MfaService},
+    database::{mfa::MfaRepository, users::UserRepository, webauthn::WebauthnRepository},
+    error::AppError,
+    extract::{Json, Path},
+    handlers::api::users::user_response,
+    models::{
+        AuthResponse, MfaChallengeResponse, MfaCodeRequest, MfaEnrollmentResponse, MfaLoginRequest,
+        MfaMethodsResponse, MfaRecoveryCodesResponse, User, MFA_METHOD_PASSKEY, MFA_METHOD_TOTP,
+    },
+    versioning::ApiVersion,
+};
+
+/// Called by the password login handlers once the password has been verified.
+/// Returns a challenge when the user must still present a second factor: a
+/// TOTP code if MFA is enabled, or any registered passkey.
+pub async fn challenge(state: &AppState, user: &User) -> Result<Option<MfaChallengeResponse>, AppError> {
+    let methods = second_factors(state, user).await?;
+    if methods.is_empty() {
+        return Ok(None);
+    }
+    
+    let jwt_service = JwtService::new(&state.config.auth.jwt_secret);
+    Ok(Some(MfaChallengeResponse {
+        mfa_required: true,
+        mfa_token: jwt_service.generate_mfa_pending_token(user.id)?,
+        methods,
+    }))
+}
+
+/// The second factors `user` can sign in with.
+pub async fn second_factors(state: &AppState, user: &User) -> Result<Vec<String>, AppError> {
+    let mut methods = Vec::new();
+    if user.mfa_enabled {
+        methods.push(MFA_METHOD_TOTP.to_string());
+    }
+    if WebauthnRepository::has_credentials(&state.db, user.id).await? {
+        methods.push(MFA_METHOD_PASSKEY.to_string());
+    }
+    
+    Ok(methods)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/mfa/enrollment",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "Pending TOTP secret", body = MfaEnrollmentResponse),
+        (status = 400, description = "MFA is already enabled"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn enroll(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+) -> Result<Json<MfaEnrollmentResponse>, AppError> {
+    if current_user.mfa_enabled {
+        return Err(AppError::BadRequest("MFA is already enabled".to_string()));
+    }
+    
+    let secret = MfaService::generate_secret();
+    MfaRepository::set_secret(&state.db, current_user.id, &secret).await?;
+    
+    Ok(Json(MfaEnrollmentResponse {
+        otpauth_uri: MfaService::otpauth_uri(&secret, &current_user.email)?,
+        secret,
+        qr_code_url: "/api/v1/mfa/qr_code".to_string(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/mfa/qr_code",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "QR code for the pending secret", content_type = "image/png"),
+        (status = 404, description = "No pending MFA enrollment"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn qr_code(
+    Extension(current_user): Extension<User>,
+) -> Result<impl IntoResponse, AppError> {
+    let secret = current_user
+        .mfa_secret
+        .as_deref()
+        .filter(|_| !current_user.mfa_enabled)
+        .ok_or_else(|| AppError::NotFound("No pending MFA enrollment".to_string()))?;
+    
+    let uri = MfaService::otpauth_uri(secret, &current_user.email)?;
+    let png = MfaService::qr_code_png(&uri)?;
+    
+    Ok((
+        [(header::CONTENT_TYPE, "image/png"), (header::CACHE_CONTROL, "no-store")],
+        png,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/mfa/confirm",
+    tag = "mfa",
+    request_body = MfaCodeRequest,
+    responses(
+        (status = 200, description = "MFA enabled", body = MfaRecoveryCodesResponse),
+        (status = 401, description = "Invalid code"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn confirm(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Json(req): Json<MfaCodeRequest>,
+) -> Result<Json<MfaRecoveryCodesResponse>, AppError> {
+    req.validate()?;
+    
+    let secret = current_user
+        .mfa_secret
+        .as_deref()
+        .filter(|_| !current_user.mfa_enabled)
+        .ok_or_else(|| AppError::BadRequest("No pending MFA enrollment".to_string()))?;
+    
+    if !accept_code(&state, &current_user, secret, &req.code).await? {
+        return Err(AppError::Unauthorized("Invalid authentication code".to_string()));
+    }
+    
+    // Plaintext codes are shown exactly once; only digests are stored
+    let recovery_codes = MfaService::generate_recovery_codes();
+    let digests: Vec<String> = recovery_codes.iter().map(|c| MfaService::hash_recovery_code(c)).collect();
+    MfaRepository::enable(&state.db, current_user.id, &digests).await?;
+    
+    Ok(Json(MfaRecoveryCodesResponse { recovery_codes }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/mfa",
+    tag = "mfa",
+    request_body = MfaCodeRequest,
+    responses(
+        (status = 204, description = "MFA disabled"),
+        (status = 401, description = "Invalid code"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn disable(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Json(req): Json<MfaCodeRequest>,
+) -> Result<StatusCode, AppError> {
+    req.validate()?;
+    
+    let secret = current_user
+        .mfa_secret
+        .as_deref()
+        .filter(|_| current_user.mfa_enabled)
+        .ok_or_else(|| AppError::BadRequest("MFA is not enabled".to_string()))?;
+    
+    if !accept_code(&state, &current_user, secret, &req.code).await? {
+        return Err(AppError::Unauthorized("Invalid authentication code".to_string()));
+    }
+    
+    MfaRepository::reset(&state.db, current_user.id).await?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Second login step: exchanges an `mfa_pending` token plus a TOTP or recovery code
+/// for the usual access and refresh tokens.
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/mfa",
+    tag = "auth",
+    request_body = MfaLoginRequest,
+    responses(
+        (status = 200, description = "Second factor accepted", body = AuthResponse),
+        (status = 401, description = "Invalid code or token"),
+        (status = 429, description = "Too many attempts"),
+    )
+)]
+pub async fn verify(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    Json(req): Json<MfaLoginRequest>,
+) -> Result<Json<AuthResponse>, AppError> {
+    let user = verify_second_factor(&state, &req).await?;
+    
+    Ok(Json(issue_auth_response(&state, version, &user).await?))
+}
+
+/// Checks the TOTP or recovery code for a pending MFA login and returns the
+/// user it signs in. Shared with the HTML form, which sends blank fields.
+pub async fn verify_second_factor(state: &AppState, req: &MfaLoginRequest) -> Result<User, AppError> {
+    let user = pending_user(state, &req.mfa_token).await?;
+    if !user.mfa_enabled {
+        return Err(AppError::Unauthorized("Invalid token".to_string()));
+    }
+    
+    let code = req.code.as_deref().filter(|code| !code.trim().is_empty());
+    let recovery_code = req.recovery_code.as_deref().filter(|code| !code.trim().is_empty());
+    let verified = match (code, recovery_code, &user.mfa_secret) {
+        (Some(code), _, Some(secret)) => accept_code(state, &user, secret, code).await?,
+        (None, Some(recovery_code), _) => {
+            let digest = MfaService::hash_recovery_code(recovery_code);
+            MfaRepository::consume_recovery_code(&state.db, user.id, &digest).await?
+        }
+        _ => return Err(AppError::BadRequest("An authentication code or recovery code is required".to_string())),
+    };
+    
+    if !verified {
+        return Err(AppError::Unauthorized("Invalid authentication code".to_string()));
+    }
+    
+    Ok(user)
+}
+
+/// Checks a TOTP code and records its time step, so each code works once.
+async fn accept_code(state: &AppState, user: &User, secret: &str, code: &str) -> Result<bool, AppError> {
+    match MfaService::verify_code(secret, &user.email, code, user.mfa_last_used_step)? {
+        Some(step) => MfaRepository::record_step(&state.db, user.id, step).await,
+        None => Ok(false),
+    }
+}
+
+/// Issues access and refresh tokens once every required factor has been checked.
+/// The caller is now signed in as `user`, so the profile is shown as its owner sees it.
+pub async fn issue_auth_response(state: &AppState, version: ApiVersion, user: &User) -> Result<AuthResponse, AppError> {
+    let jwt_service = JwtService::new(&state.config.auth.jwt_secret);
+    
+    Ok(AuthResponse {
+        user: user_response(state, user).await?.for_version(version, Some(user)),
+        token: jwt_service.generate_access_token(user.id)?,
+        refresh_token: jwt_service.generate_refresh_token(user.id)?,
+    })
+}
+
+/// Resolves the user behind an `mfa_pending` token that hasn't been revoked.
+pub async fn pending_user(state: &AppState, mfa_token: &str) -> Result<User, AppError> {
+    let jwt_service = JwtService::new(&state.config.auth.jwt_secret);
+    let claims = jwt_service.verify_token(mfa_token)?;
+    
+    if claims.token_type != "mfa_pending" {
+        return Err(AppError::Unauthorized("Invalid token type".to_string()));
+    }
+    
+    let user_id: Uuid = claims.sub.parse().map_err(|_| AppError::Unauthorized("Invalid user ID".to_string()))?;
+    let user = UserRepository::find_by_id(&state.db, user_id)
+        .await?
+        .filter(|user| user.activated)
+        .ok_or_else(|| AppError::Unauthorized("Invalid token".to_string()))?;
+    
+    if user.tokens_revoked(claims.iat) {
+        return Err(AppError::Unauthorized("Token has been revoked".to_string()));
+    }
+    
+    Ok(user)
+}
+
+/// Removes the TOTP authenticator and every passkey, so a locked-out user can
+/// sign in with their password alone.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/users/{id}/mfa",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "User id")),
+    responses(
+        (status = 200, description = "MFA reset; the second factors still enforced", body = MfaMethodsResponse),
+        (status = 403, description = "Admins only"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_reset(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<MfaMethodsResponse>, AppError> {
+    let user = MfaRepository::reset_all(&state.db, id).await?;
+    tracing::info!("MFA reset by admin for user {}", user.id);
+    
+    Ok(Json(MfaMethodsResponse {
+        methods: second_factors(&state, &user).await?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Method, Request, StatusCode},
+    };
+    use serde_json::json;
+    use sqlx::PgPool;
+    use crate::{
+        auth::token_digest,
+        database::{users::UserRepository, webauthn::WebauthnRepository},
+        models::User,
+        test_support::*,
+    };
+    
+    async fn mfa_token(app: &axum::Router, user: &User) -> String {
+        let (status, body) = post_json(app, "/api/v1/auth/login", json!({ "email": user.email, "password": PASSWORD })).await;
+        assert_eq!(status, StatusCode::OK);
+        
+        body["mfa_token"].as_str().unwrap().to_string()
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn password_alone_does_not_sign_in_mfa_users(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        enable_mfa(&pool, &alice).await;
+        
+        let (status, body) = post_json(&app, "/api/v1/auth/login", json!({ "email": alice.email, "password": PASSWORD })).await;
+        
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["mfa_required"], true);
+        assert!(body.get("token").is_none());
+        
+        // The pending token is not an access token
+        let pending = body["mfa_token"].as_str().unwrap();
+        let request = Request::get("/api/v1/feed")
+            .header(header::AUTHORIZATION, format!("Bearer {}", pending))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(send(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn password_resets_do_not_sign_in_mfa_users(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        enable_mfa(&pool, &alice).await;
+        UserRepository::set_reset_token(&pool, alice.id, &token_digest("reset-token")).await.unwrap();
+        
+        let reset = json!({ "password": "new-password", "password_confirmation": "new-password" });
+        let (status, body) = post_json(&app, "/api/v1/password_resets/reset-token", reset).await;
+        
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["mfa_required"], true);
+        assert!(body.get("token").is_none());
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn a_valid_code_completes_the_login_once(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let secret = enable_mfa(&pool, &alice).await;
+        let code = totp_code(&secret, &alice);
+        
+        let token = mfa_token(&app, &alice).await;
+        let (status, body) = post_json(&app, "/api/v1/auth/mfa", json!({ "mfa_token": token, "code": code })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["token"].is_string());
+        
+        let token = mfa_token(&app, &alice).await;
+        let (status, _) = post_json(&app, "/api/v1/auth/mfa", json!({ "mfa_token": token, "code": code })).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn recovery_codes_work_once(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        enable_mfa(&pool, &alice).await;
+        
+        let token = mfa_token(&app, &alice).await;
+        let (status, _) = post_json(&app, "/api/v1/auth/mfa", json!({ "mfa_token": token, "recovery_code": "ABCDE-12345" })).await;
+        assert_eq!(status, StatusCode::OK);
+        
+        let token = mfa_token(&app, &alice).await;
+        let (status, _) = post_json(&app, "/api/v1/auth/mfa", json!({ "mfa_token": token, "recovery_code": "abcde-12345" })).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn disable_validates_the_code(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let secret = enable_mfa(&pool, &alice).await;
+        
+        let response = send(&app, json_request(Method::DELETE, "/api/v1/mfa", &alice, json!({ "code": "12" }))).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        
+        let code = totp_code(&secret, &alice);
+        let response = send(&app, json_request(Method::DELETE, "/api/v1/mfa", &alice, json!({ "code": code }))).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn pending_tokens_stop_working_for_deactivated_accounts(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let secret = enable_mfa(&pool, &alice).await;
+        let token = mfa_token(&app, &alice).await;
+        
+        sqlx::query("UPDATE users SET activated = false WHERE id = $1").bind(alice.id).execute(&pool).await.unwrap();
+        
+        let code = totp_code(&secret, &alice);
+        let (status, body) = post_json(&app, "/api/v1/auth/mfa", json!({ "mfa_token": token, "code": code })).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.get("token").is_none());
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn admin_reset_removes_totp_and_passkeys(pool: PgPool) {
+        let app = app(pool.clone());
+        let admin = UserRepository::set_admin(&pool, create_user(&pool, "Admin", false).await.id, true).await.unwrap();
+        let alice = create_user(&pool, "Alice", false).await;
+        enable_mfa(&pool, &alice).await;
+        WebauthnRepository::create_credential(&pool, alice.id, "credential", "Laptop", json!({})).await.unwrap();
+        
+        let uri = format!("/api/v1/users/{}/mfa", alice.id);
+        let response = send(&app, json_request(Method::DELETE, &uri, &admin, json!({}))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["methods"], json!([]));
+        
+        let (status, body) = post_json(&app, "/api/v1/auth/login", json!({ "email": alice.email, "password": PASSWORD })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["token"].is_string());
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Extension,
+};
+use uuid::Uuid;
+use validator::Validate;
+use crate::{
+    app::AppState,
+    database::{microposts::MicropostRepository, users::UserRepository},
+    error::AppError,
+    http_cache::IfMatched,
+    extract::{Json, Path, Query},
+    models::{CreateMicropostRequest, Micropost, MicropostResponse, UpdateMicropostRequest, User},
+    utils::pagination::{Pagination, PaginationParams},
+};
+
+/// Responses for a page of microposts, with their authors loaded in one query.
+pub async fn micropost_responses(state: &AppState, microposts: Pagination<Micropost>) -> Result<Pagination<MicropostResponse>, AppError> {
+    let mut author_ids: Vec<Uuid> = microposts.items.iter().map(|m| m.user_id).collect();
+    author_ids.sort_unstable();
+    author_ids.dedup();
+    
+    let authors: HashMap<Uuid, User> = UserRepository::find_by_ids(&state.db, &author_ids)
+        .await?
+        .into_iter()
+        .map(|user| (user.id, user))
+        .collect();
+    
+    Ok(microposts.map(|micropost| {
+        let (name, email) = authors
+            .get(&micropost.user_id)
+            .map(|author| (author.name.clone(), author.email.clone()))
+            .unwrap_or_default();
+        micropost.to_response(name, email)
+    }))
+}
+
+async fn micropost_response(state: &AppState, micropost: &Micropost) -> Result<MicropostResponse, AppError> {
+    let author = UserRepository::find_by_id(&state.db, micropost.user_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    
+    Ok(micropost.to_response(author.name, author.email))
+}
+
+async fn find_micropost(state: &AppState, id: Uuid) -> Result<Micropost, AppError> {
+    MicropostRepository::find_by_id(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Micropost not found".to_string()))
+}
+
+/// Skips private accounts the caller doesn't follow.
+#[utoipa::path(
+    get,
+    path = "/api/v1/microposts",
+    tag = "microposts",
+    params(
+        ("page" = Option<i64>, Query, description = "Page number, starting at 1"),
+        ("per_page" = Option<i64>, Query, description = "Items per page, at most 100"),
+    ),
+    responses((status = 200, description = "Page of microposts, newest first"))
+)]
+pub async fn index(
+    State(state): State<AppState>,
+    current_user: Option<Extension<User>>,
+    Query(params): Query<PaginationParams>,
+) -> Result<Json<Pagination<MicropostResponse>>, AppError> {
+    let viewer = current_user.as_ref().map(|Extension(user)| user);
+    let microposts = MicropostRepository::list_visible(&state.db, viewer, &params).await?;
+    
+    Ok(Json(micropost_responses(&state, microposts).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/microposts",
+    tag = "microposts",
+    request_body = CreateMicropostRequest,
+    responses(
+        (status = 201, description = "Micropost created", body = MicropostResponse),
+        (status = 422, description = "Validation failed"),
+        (status = 429, description = "Posting too fast"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Json(req): Json<CreateMicropostRequest>,
+) -> Result<(StatusCode, Json<MicropostResponse>), AppError> {
+    req.validate()?;
+    
+    let micropost = MicropostRepository::create(&state.db, current_user.id, &req).await?;
+    
+    Ok((StatusCode::CREATED, Json(micropost.to_response(current_user.name, current_user.email))))
+}
+
+/// Behind `private_micropost_middleware`, which has already checked the viewer.
+#[utoipa::path(
+    get,
+    path = "/api/v1/microposts/{id}",
+    tag = "microposts",
+    params(("id" = Uuid, Path, description = "Micropost id")),
+    responses(
+        (status = 200, description = "Micropost", body = MicropostResponse),
+        (status = 404, description = "Micropost not found"),
+    ),
+    security((), ("bearer" = []))
+)]
+pub async fn show(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<MicropostResponse>, AppError> {
+    let micropost = find_micropost(&state, id).await?;
+    
+    Ok(Json(micropost_response(&state, &micropost).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/microposts/{id}",
+    tag = "microposts",
+    params(("id" = Uuid, Path, description = "Micropost id")),
+    request_body = UpdateMicropostRequest,
+    responses(
+        (status = 200, description = "Updated micropost", body = MicropostResponse),
+        (status = 403, description = "Not the author"),
+        (status = 412, description = "If-Match names an older revision"),
+        (status = 422, description = "Validation failed"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    if_matched: Option<Extension<IfMatched>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateMicropostRequest>,
+) -> Result<Json<MicropostResponse>, AppError> {
+    req.validate()?;
+    
+    let micropost = find_micropost(&state, id).await?;
+    if micropost.user_id != current_user.id {
+        return Err(AppError::Forbidden("You can only edit your own microposts".to_string()));
+    }
+    
+    let micropost = MicropostRepository::update(&state.db, id, current_user.id, &req, if_matched.map(|Extension(IfMatched(at))| at)).await?;
+    
+    Ok(Json(micropost.to_response(current_user.name, current_user.email)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/microposts/{id}",
+    tag = "microposts",
+    params(("id" = Uuid, Path, description = "Micropost id")),
+    responses(
+        (status = 204, description = "Micropost deleted"),
+        (status = 403, description = "Not the author"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let micropost = find_micropost(&state, id).await?;
+    if micropost.user_id != current_user.id && !current_user.admin {
+        return Err(AppError::Forbidden("You can only delete your own microposts".to_string()));
+    }
+    
+    MicropostRepository::delete(&state.db, id).await?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Behind `optional_auth_middleware` so `feed_conditional` can see the viewer;
+/// anonymous callers are rejected here.
+#[utoipa::path(
+    get,
+    path = "/api/v1/feed",
+    tag = "microposts",
+    params(
+        ("page" = Option<i64>, Query, description = "Page number, starting at 1"),
+        ("per_page" = Option<i64>, Query, description = "Items per page, at most 100"),
+    ),
+    responses((status = 200, description = "Page of the caller's and followed users' microposts")),
+    security(("bearer" = []))
+)]
+pub async fn feed(
+    State(state): State<AppState>,
+    current_user: Option<Extension<User>>,
+    Query(params): Query<PaginationParams>,
+) -> Result<Json<Pagination<MicropostResponse>>, AppError> {
+    let Some(Extension(current_user)) = current_user else {
+        return Err(AppError::Unauthorized("Missing authorization header".to_string()));
+    };
+    
+    let microposts = MicropostRepository::feed(&state.db, current_user.id, &params).await?;
+    
+    Ok(Json(micropost_responses(&state, microposts).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use sqlx::PgPool;
+    use crate::{
+        database::{follow_requests::FollowRequestRepository, microposts::MicropostRepository},
+        models::{CreateMicropostRequest, User},
+        test_support::*,
+    };
+    
+    async fn post(pool: &PgPool, author: &User, content: &str) {
+        let req = CreateMicropostRequest { content: content.to_string(), picture: None };
+        MicropostRepository::create(pool, author.id, &req).await.unwrap();
+    }
+    
+    async fn contents(app: &axum::Router, uri: &str, viewer: Option<&User>) -> Vec<String> {
+        let request = Request::get(uri);
+        let request = match viewer {
+            Some(viewer) => request.header(header::AUTHORIZATION, bearer(viewer)),
+            None => request,
+        };
+        let response = send(app, request.body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        
+        body_json(response).await["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["content"].as_str().unwrap().to_string())
+            .collect()
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn index_skips_private_accounts_the_caller_does_not_follow(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", true).await;
+        post(&pool, &alice, "public post").await;
+        post(&pool, &bob, "private post").await;
+        
+        assert_eq!(contents(&app, "/api/v1/microposts", None).await, ["public post"]);
+        assert_eq!(contents(&app, "/api/v1/microposts", Some(&alice)).await, ["public post"]);
+        
+        let request = FollowRequestRepository::follow(&pool, alice.id, &bob).await.unwrap();
+        FollowRequestRepository::approve(&pool, request.id, bob.id).await.unwrap();
+        
+        let mut visible = contents(&app, "/api/v1/microposts", Some(&alice)).await;
+        visible.sort();
+        assert_eq!(visible, ["private post", "public post"]);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn feed_leaves_out_pending_follows(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", true).await;
+        post(&pool, &bob, "private post").await;
+        let request = FollowRequestRepository::follow(&pool, alice.id, &bob).await.unwrap();
+        
+        assert!(contents(&app, "/api/v1/feed", Some(&alice)).await.is_empty());
+        
+        FollowRequestRepository::approve(&pool, request.id, bob.id).await.unwrap();
+        assert_eq!(contents(&app, "/api/v1/feed", Some(&alice)).await, ["private post"]);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn show_is_forbidden_for_private_authors(pool: PgPool) {
+        let app = app(pool.clone());
+        let bob = create_user(&pool, "Bob", true).await;
+        let req = CreateMicropostRequest { content: "private post".to_string(), picture: None };
+        let micropost = MicropostRepository::create(&pool, bob.id, &req).await.unwrap();
+        
+        let response = send(&app, Request::get(format!("/api/v1/microposts/{}", micropost.id)).body(Body::empty()).unwrap()).await;
+        
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
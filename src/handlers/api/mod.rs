@@ -2,3 +2,9 @@ pub mod auth;
 pub mod users;
 pub mod microposts;
 pub mod relationships;
+pub mod follow_requests;
+pub mod messages;
+pub mod blocks;
+pub mod mfa;
+pub mod webauthn;
+pub mod oidc;
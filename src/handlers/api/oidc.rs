@@ -0,0 +1,283 @@
+use axum::{
+    extract::State,
+    response::Redirect,
+    Json,
+};
+use rand::{distributions::Alphanumeric, Rng};
+use crate::{
+    app::AppState,
+    auth::{OidcIdentity, OidcService},
+    database::{identities::IdentityRepository, users::UserRepository},
+    error::AppError,
+    extract::{Path, Query},
+    handlers::api::mfa::{challenge, issue_auth_response},
+    models::{LoginResponse, OidcCallbackParams, OidcProviderResponse, User},
+    telemetry,
+    versioning::ApiVersion,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/oidc",
+    tag = "auth",
+    responses((status = 200, description = "Configured identity providers", body = [OidcProviderResponse]))
+)]
+pub async fn providers(State(state): State<AppState>) -> Json<Vec<OidcProviderResponse>> {
+    Json(
+        state
+            .config
+            .auth
+            .oidc_providers
+            .iter()
+            .map(|p| OidcProviderResponse {
+                name: p.name.clone(),
+                display_name: p.display_name.clone(),
+                authorize_url: format!("/api/v1/auth/oidc/{}", p.name),
+            })
+            .collect(),
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/oidc/{provider}",
+    tag = "auth",
+    params(("provider" = String, Path, description = "Provider name")),
+    responses(
+        (status = 303, description = "Redirect to the provider"),
+        (status = 404, description = "Unknown provider"),
+    )
+)]
+pub async fn authorize(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+) -> Result<Redirect, AppError> {
+    let provider_config = state
+        .config
+        .oidc_provider(&provider)
+        .ok_or_else(|| AppError::NotFound("Unknown identity provider".to_string()))?;
+    
+    let oidc = OidcService::discover(&state.config, provider_config, &state.oidc_metadata).await?;
+    let authorization = oidc.authorize();
+    
+    IdentityRepository::create_login_state(
+        &state.db,
+        &authorization.state,
+        &provider_config.name,
+        &authorization.pkce_verifier,
+        &authorization.nonce,
+    )
+    .await?;
+    
+    Ok(Redirect::to(&authorization.url))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/oidc/{provider}/callback",
+    tag = "auth",
+    params(
+        ("provider" = String, Path, description = "Provider name"),
+        ("code" = Option<String>, Query, description = "Authorization code"),
+        ("state" = String, Query, description = "CSRF state from the authorization request"),
+        ("error" = Option<String>, Query, description = "Set when the user cancelled or the provider refused"),
+    ),
+    responses(
+        (status = 200, description = "Signed in, or an MFA challenge", body = LoginResponse),
+        (status = 401, description = "Provider rejected the login"),
+    )
+)]
+pub async fn callback(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    Path(provider): Path<String>,
+    Query(params): Query<OidcCallbackParams>,
+) -> Result<Json<LoginResponse>, AppError> {
+    let provider_config = state
+        .config
+        .oidc_provider(&provider)
+        .ok_or_else(|| AppError::NotFound("Unknown identity provider".to_string()))?;
+    
+    if let Some(error) = params.error {
+        return Err(AppError::Unauthorized(format!("Sign in was not completed: {}", error)));
+    }
+    let code = params
+        .code
+        .ok_or_else(|| AppError::BadRequest("Missing authorization code".to_string()))?;
+    
+    let login_state = IdentityRepository::take_login_state(&state.db, &params.state, &provider_config.name)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Sign in request expired".to_string()))?;
+    
+    let oidc = OidcService::discover(&state.config, provider_config, &state.oidc_metadata).await?;
+    let user = match oidc.exchange(&code, &login_state.pkce_verifier, &login_state.nonce).await {
+        Ok(identity) => resolve_user(&state, &provider_config.name, &identity).await,
+        Err(e) => Err(e),
+    };
+    telemetry::record_login("oidc", user.is_ok());
+    let user = user?;
+    
+    if let Some(mfa_challenge) = challenge(&state, &user).await? {
+        return Ok(Json(LoginResponse::MfaRequired(mfa_challenge)));
+    }
+    
+    Ok(Json(LoginResponse::Authenticated(issue_auth_response(&state, version, &user).await?)))
+}
+
+/// Finds the user for an external identity, linking to an existing account only
+/// when the provider has verified the email address and the account is
+/// activated. An unactivated account may have been registered by someone else
+/// with this address, so activating it here would hand them the login.
+async fn resolve_user(state: &AppState, provider: &str, identity: &OidcIdentity) -> Result<User, AppError> {
+    if let Some(user) = IdentityRepository::find_user(&state.db, provider, &identity.subject).await? {
+        IdentityRepository::record_login(&state.db, provider, &identity.subject).await?;
+        return Ok(user);
+    }
+    
+    let email = identity
+        .email
+        .as_deref()
+        .filter(|_| identity.email_verified)
+        .ok_or_else(|| AppError::Unauthorized("A verified email address is required".to_string()))?;
+    
+    let user = match UserRepository::find_by_email(&state.db, email).await? {
+        Some(user) if !user.activated => {
+            return Err(AppError::Unauthorized(
+                "An account with this email address is awaiting activation. Activate it before signing in with this provider".to_string(),
+            ));
+        }
+        Some(user) => user,
+        None => {
+            let name = identity
+                .name
+                .clone()
+                .unwrap_or_else(|| email.split('@').next().unwrap_or(email).to_string());
+            let name: String = name.chars().take(50).collect();
+            
+            // External users never log in with a password; store an unguessable one
+            let password: String = rand::thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect();
+            let password_hash = bcrypt::hash(password, bcrypt::DEFAULT_COST)
+                .map_err(|e| AppError::Internal(format!("Failed to hash password: {}", e)))?;
+            
+            let user = UserRepository::create_external(&state.db, &name, email, &password_hash).await?;
+            telemetry::record_signup("oidc");
+            user
+        }
+    };
+    
+    IdentityRepository::link(&state.db, user.id, provider, &identity.subject, Some(email)).await?;
+    
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, Response, StatusCode},
+        Router,
+    };
+    use sqlx::PgPool;
+    use tokio::task::JoinHandle;
+    use crate::{
+        app::AppState,
+        config::OidcProviderConfig,
+        database::users::UserRepository,
+        mock_oidc_provider,
+        models::CreateUserRequest,
+        test_support::*,
+    };
+    
+    struct Provider {
+        routes: Router,
+        server: JoinHandle<()>,
+    }
+    
+    /// Serves the mock provider on a free port and returns an app that trusts it.
+    async fn app_with_provider(pool: &PgPool) -> (Router, Provider) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let routes = mock_oidc_provider::router(issuer.clone());
+        let served = routes.clone();
+        let server = tokio::spawn(async move { axum::serve(listener, served).await.unwrap() });
+        
+        let mut config = config();
+        config.auth.oidc_providers = vec![OidcProviderConfig {
+            name: "mock".to_string(),
+            display_name: "Mock".to_string(),
+            issuer_url: issuer,
+            client_id: "sample-app".to_string(),
+            client_secret: String::new(),
+        }];
+        
+        (crate::create_app(AppState::new(pool.clone(), config).unwrap()), Provider { routes, server })
+    }
+    
+    /// Runs the whole redirect dance, with the provider approving `email` at once.
+    async fn sign_in(app: &Router, provider: &Provider, email: &str) -> Response<Body> {
+        let response = send(app, Request::get("/api/v1/auth/oidc/mock").body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let location = response.headers()[header::LOCATION].to_str().unwrap();
+        
+        let authorize = format!("/authorize?{}&login_hint={}", location.split_once('?').unwrap().1, email);
+        let response = send(&provider.routes, Request::get(authorize).body(Body::empty()).unwrap()).await;
+        let callback = response.headers()[header::LOCATION].to_str().unwrap();
+        let callback = callback.strip_prefix(config().server.frontend_url.as_str()).unwrap();
+        
+        send(app, Request::get(callback).body(Body::empty()).unwrap()).await
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn new_identities_get_an_activated_account(pool: PgPool) {
+        let (app, provider) = app_with_provider(&pool).await;
+        
+        let response = sign_in(&app, &provider, "new.user@example.com").await;
+        
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(body_json(response).await["token"].is_string());
+        let user = UserRepository::find_by_email(&pool, "new.user@example.com").await.unwrap().unwrap();
+        assert!(user.activated);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn verified_emails_link_to_activated_accounts(pool: PgPool) {
+        let (app, provider) = app_with_provider(&pool).await;
+        let alice = create_user(&pool, "Alice", false).await;
+        
+        let response = sign_in(&app, &provider, &alice.email).await;
+        
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["user"]["id"], alice.id.to_string());
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn unactivated_accounts_are_not_linked(pool: PgPool) {
+        let (app, provider) = app_with_provider(&pool).await;
+        let req = CreateUserRequest {
+            name: "Squatter".to_string(),
+            email: "victim@example.com".to_string(),
+            password: PASSWORD.to_string(),
+            password_confirmation: PASSWORD.to_string(),
+        };
+        let squatter = UserRepository::create(&pool, &req, &bcrypt::hash(PASSWORD, 4).unwrap(), "unused").await.unwrap();
+        
+        let response = sign_in(&app, &provider, "victim@example.com").await;
+        
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let squatter = UserRepository::find_by_id(&pool, squatter.id).await.unwrap().unwrap();
+        assert!(!squatter.activated);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn provider_metadata_is_cached(pool: PgPool) {
+        let (app, provider) = app_with_provider(&pool).await;
+        let response = send(&app, Request::get("/api/v1/auth/oidc/mock").body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        
+        provider.server.abort();
+        let _ = provider.server.await;
+        
+        let response = send(&app, Request::get("/api/v1/auth/oidc/mock").body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    }
+}
@@ -0,0 +1,137 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Extension,
+};
+use uuid::Uuid;
+use crate::{
+    app::AppState,
+    database::{follow_requests::FollowRequestRepository, relationships::RelationshipRepository, users::UserRepository},
+    error::AppError,
+    extract::{Json, Path},
+    models::{CreateRelationshipRequest, RelationshipResponse, User},
+};
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/relationships",
+    tag = "relationships",
+    request_body = CreateRelationshipRequest,
+    responses(
+        (status = 201, description = "Following, or pending approval for private accounts", body = RelationshipResponse),
+        (status = 429, description = "Following too fast"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Json(req): Json<CreateRelationshipRequest>,
+) -> Result<(StatusCode, Json<RelationshipResponse>), AppError> {
+    if req.followed_id == current_user.id {
+        return Err(AppError::BadRequest("You cannot follow yourself".to_string()));
+    }
+    
+    let followed = UserRepository::find_by_id(&state.db, req.followed_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    
+    // Private accounts get a pending request instead of a follow
+    let relationship = FollowRequestRepository::follow(&state.db, current_user.id, &followed).await?;
+    
+    Ok((StatusCode::CREATED, Json(relationship.to_response())))
+}
+
+/// `id` is the followed user's id. Also withdraws a pending follow request.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/relationships/{id}",
+    tag = "relationships",
+    params(("id" = Uuid, Path, description = "Followed user id")),
+    responses((status = 204, description = "Unfollowed")),
+    security(("bearer" = []))
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    if !RelationshipRepository::unfollow(&state.db, current_user.id, id).await? {
+        return Err(AppError::NotFound("Relationship not found".to_string()));
+    }
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use serde_json::json;
+    use sqlx::PgPool;
+    use crate::{database::relationships::RelationshipRepository, test_support::*};
+    
+    fn follow_request(follower: &crate::models::User, followed_id: uuid::Uuid) -> Request<Body> {
+        Request::post("/api/v1/relationships")
+            .header(header::AUTHORIZATION, bearer(follower))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({ "followed_id": followed_id }).to_string()))
+            .unwrap()
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn following_a_private_account_leaves_a_pending_request(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", true).await;
+        
+        let response = send(&app, follow_request(&alice, bob.id)).await;
+        
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(body_json(response).await["status"], "pending");
+        let relationship = RelationshipRepository::find(&pool, alice.id, bob.id).await.unwrap().unwrap();
+        assert_eq!(relationship.status, "pending");
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn following_a_public_account_is_accepted_at_once(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", false).await;
+        
+        let response = send(&app, follow_request(&alice, bob.id)).await;
+        
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(body_json(response).await["status"], "accepted");
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn following_yourself_is_rejected(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        
+        let response = send(&app, follow_request(&alice, alice.id)).await;
+        
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn unfollowing_takes_the_followed_users_id(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", false).await;
+        send(&app, follow_request(&alice, bob.id)).await;
+        
+        let unfollow = || {
+            Request::delete(format!("/api/v1/relationships/{}", bob.id))
+                .header(header::AUTHORIZATION, bearer(&alice))
+                .body(Body::empty())
+                .unwrap()
+        };
+        
+        assert_eq!(send(&app, unfollow()).await.status(), StatusCode::NO_CONTENT);
+        assert_eq!(send(&app, unfollow()).await.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,251 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Extension,
+};
+use uuid::Uuid;
+use validator::Validate;
+use crate::{
+    app::AppState,
+    auth::{generate_token, hash_password, token_digest},
+    database::{relationships::RelationshipRepository, users::UserRepository},
+    error::AppError,
+    http_cache::IfMatched,
+    extract::{Json, Path, Query},
+    mailer,
+    models::{CreateUserRequest, UpdateUserRequest, User, UserResponse, VersionedUserResponse},
+    utils::pagination::{Pagination, PaginationParams},
+    versioning::ApiVersion,
+};
+
+/// Shared by signup and `POST /users`: creates an unactivated account and
+/// emails its activation link.
+pub async fn register(state: &AppState, req: &CreateUserRequest) -> Result<User, AppError> {
+    req.validate()?;
+    
+    let token = generate_token();
+    let user = UserRepository::create(&state.db, req, &hash_password(&req.password)?, &token_digest(&token)).await?;
+    
+    // The account exists either way; the link can be sent again from /account_activations/new
+    if let Err(e) = mailer::send_account_activation(&state.config, &user, &token).await {
+        tracing::error!("Failed to send activation email to user {}: {}", user.id, e);
+    }
+    
+    Ok(user)
+}
+
+/// `to_response` with the micropost and follow counts filled in.
+pub async fn user_response(state: &AppState, user: &User) -> Result<UserResponse, AppError> {
+    let (microposts_count, following_count, followers_count) = UserRepository::get_stats(&state.db, user.id).await?;
+    
+    Ok(UserResponse {
+        microposts_count,
+        following_count,
+        followers_count,
+        ..user.to_response()
+    })
+}
+
+/// `user_response` for a page of users, with the counts loaded in one query.
+pub async fn user_responses(state: &AppState, users: Pagination<User>) -> Result<Pagination<UserResponse>, AppError> {
+    let ids: Vec<Uuid> = users.items.iter().map(|user| user.id).collect();
+    let stats = UserRepository::get_stats_for(&state.db, &ids).await?;
+    
+    Ok(users.map(|user| {
+        let (microposts_count, following_count, followers_count) = stats.get(&user.id).copied().unwrap_or_default();
+        UserResponse {
+            microposts_count,
+            following_count,
+            followers_count,
+            ..user.to_response()
+        }
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users",
+    tag = "users",
+    params(
+        ("page" = Option<i64>, Query, description = "Page number, starting at 1"),
+        ("per_page" = Option<i64>, Query, description = "Items per page, at most 100"),
+    ),
+    responses((status = 200, description = "Page of activated users"))
+)]
+pub async fn index(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    current_user: Option<Extension<User>>,
+    Query(params): Query<PaginationParams>,
+) -> Result<Json<Pagination<VersionedUserResponse>>, AppError> {
+    let users = UserRepository::list(&state.db, &params).await?;
+    let viewer = current_user.map(|Extension(user)| user);
+    
+    Ok(Json(user_responses(&state, users).await?.map(|user| user.for_version(version, viewer.as_ref()))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/users",
+    tag = "users",
+    request_body = CreateUserRequest,
+    responses(
+        (status = 201, description = "Account created", body = UserResponse),
+        (status = 409, description = "Email already taken"),
+        (status = 422, description = "Validation failed"),
+    )
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    Json(req): Json<CreateUserRequest>,
+) -> Result<(StatusCode, Json<VersionedUserResponse>), AppError> {
+    let user = register(&state, &req).await?;
+    
+    Ok((StatusCode::CREATED, Json(user_response(&state, &user).await?.for_version(version, Some(&user)))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}",
+    tag = "users",
+    params(("id" = String, Path, description = "User id or username")),
+    responses(
+        (status = 200, description = "User profile", body = UserResponse),
+        (status = 302, description = "Retired username; redirects to the current profile URL"),
+        (status = 404, description = "User not found"),
+    )
+)]
+pub async fn show(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    current_user: Option<Extension<User>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<VersionedUserResponse>, AppError> {
+    let user = UserRepository::find_by_id(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    let viewer = current_user.map(|Extension(user)| user);
+    
+    Ok(Json(user_response(&state, &user).await?.for_version(version, viewer.as_ref())))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/users/{id}",
+    tag = "users",
+    params(("id" = String, Path, description = "User id or username")),
+    request_body = UpdateUserRequest,
+    responses(
+        (status = 200, description = "Updated user", body = UserResponse),
+        (status = 403, description = "Not the owner"),
+        (status = 409, description = "Email or username already taken"),
+        (status = 412, description = "If-Match names an older revision"),
+        (status = 422, description = "Validation failed"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    Extension(current_user): Extension<User>,
+    if_matched: Option<Extension<IfMatched>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateUserRequest>,
+) -> Result<Json<VersionedUserResponse>, AppError> {
+    if current_user.id != id && !current_user.admin {
+        return Err(AppError::Forbidden("You can only edit your own profile".to_string()));
+    }
+    req.validate()?;
+    if req.password.is_some() && req.password != req.password_confirmation {
+        return Err(AppError::Validation("Password confirmation does not match".to_string()));
+    }
+    
+    let password_hash = req.password.as_deref().map(hash_password).transpose()?;
+    let user = UserRepository::update(&state.db, id, &req, password_hash.as_deref(), if_matched.map(|Extension(IfMatched(at))| at)).await?;
+    
+    Ok(Json(user_response(&state, &user).await?.for_version(version, Some(&current_user))))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/users/{id}",
+    tag = "users",
+    params(("id" = String, Path, description = "User id or username")),
+    responses(
+        (status = 204, description = "User deleted"),
+        (status = 403, description = "Admins only"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    if !current_user.admin {
+        return Err(AppError::Forbidden("Only admins can delete users".to_string()));
+    }
+    
+    UserRepository::delete(&state.db, id).await?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Behind `private_profile_middleware`, which has already checked the viewer.
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}/following",
+    tag = "relationships",
+    params(
+        ("id" = String, Path, description = "User id or username"),
+        ("page" = Option<i64>, Query, description = "Page number, starting at 1"),
+        ("per_page" = Option<i64>, Query, description = "Items per page, at most 100"),
+    ),
+    responses(
+        (status = 200, description = "Page of followed users"),
+        (status = 403, description = "Private account"),
+    ),
+    security((), ("bearer" = []))
+)]
+pub async fn following(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    current_user: Option<Extension<User>>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<PaginationParams>,
+) -> Result<Json<Pagination<VersionedUserResponse>>, AppError> {
+    let users = RelationshipRepository::following(&state.db, id, &params).await?;
+    let viewer = current_user.map(|Extension(user)| user);
+    
+    Ok(Json(user_responses(&state, users).await?.map(|user| user.for_version(version, viewer.as_ref()))))
+}
+
+/// Behind `private_profile_middleware`, which has already checked the viewer.
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}/followers",
+    tag = "relationships",
+    params(
+        ("id" = String, Path, description = "User id or username"),
+        ("page" = Option<i64>, Query, description = "Page number, starting at 1"),
+        ("per_page" = Option<i64>, Query, description = "Items per page, at most 100"),
+    ),
+    responses(
+        (status = 200, description = "Page of followers"),
+        (status = 403, description = "Private account"),
+    ),
+    security((), ("bearer" = []))
+)]
+pub async fn followers(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    current_user: Option<Extension<User>>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<PaginationParams>,
+) -> Result<Json<Pagination<VersionedUserResponse>>, AppError> {
+    let users = RelationshipRepository::followers(&state.db, id, &params).await?;
+    let viewer = current_user.map(|Extension(user)| user);
+    
+    Ok(Json(user_responses(&state, users).await?.map(|user| user.for_version(version, viewer.as_ref()))))
+}
@@ -0,0 +1,373 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Extension,
+};
+use uuid::Uuid;
+use validator::Validate;
+use webauthn_rs::prelude::{Passkey, PasskeyAuthentication, PasskeyRegistration, PublicKeyCredential};
+use crate::{
+    app::AppState,
+    auth::WebauthnService,
+    database::{users::UserRepository, webauthn::WebauthnRepository},
+    error::AppError,
+    extract::{Json, Path},
+    handlers::api::mfa::{issue_auth_response, pending_user},
+    models::{
+        AuthResponse, FinishPasskeyLoginRequest, FinishPasskeyRegistrationRequest, PasskeyLoginChallengeResponse,
+        PasskeyRegistrationChallengeResponse, StartPasskeyLoginRequest, StartPasskeyMfaRequest, User,
+        WebauthnCredential, WebauthnCredentialResponse, CHALLENGE_AUTHENTICATION, CHALLENGE_REGISTRATION,
+    },
+    telemetry,
+    versioning::ApiVersion,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/webauthn/credentials",
+    tag = "mfa",
+    responses((status = 200, description = "Registered passkeys", body = [WebauthnCredentialResponse])),
+    security(("bearer" = []))
+)]
+pub async fn index(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+) -> Result<Json<Vec<WebauthnCredentialResponse>>, AppError> {
+    let credentials = WebauthnRepository::list_for_user(&state.db, current_user.id).await?;
+    Ok(Json(credentials.iter().map(|c| c.to_response()).collect()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/webauthn/credentials/{id}",
+    tag = "mfa",
+    params(("id" = Uuid, Path, description = "Credential id")),
+    responses(
+        (status = 204, description = "Passkey removed"),
+        (status = 404, description = "Credential not found"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    WebauthnRepository::delete_credential(&state.db, id, current_user.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/webauthn/register/start",
+    tag = "mfa",
+    responses((status = 200, description = "Registration challenge", body = PasskeyRegistrationChallengeResponse)),
+    security(("bearer" = []))
+)]
+pub async fn start_registration(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+) -> Result<Json<PasskeyRegistrationChallengeResponse>, AppError> {
+    let webauthn = WebauthnService::new(&state.config)?;
+    let existing = load_passkeys(&WebauthnRepository::list_for_user(&state.db, current_user.id).await?)?;
+    
+    let (options, registration) = webauthn.start_registration(&current_user, &existing)?;
+    let challenge = WebauthnRepository::create_challenge(
+        &state.db,
+        current_user.id,
+        CHALLENGE_REGISTRATION,
+        to_json(&registration)?,
+    )
+    .await?;
+    
+    Ok(Json(PasskeyRegistrationChallengeResponse {
+        challenge_id: challenge.id,
+        options,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/webauthn/register/finish",
+    tag = "mfa",
+    request_body = FinishPasskeyRegistrationRequest,
+    responses(
+        (status = 201, description = "Passkey registered", body = WebauthnCredentialResponse),
+        (status = 400, description = "Challenge expired or attestation rejected"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn finish_registration(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Json(req): Json<FinishPasskeyRegistrationRequest>,
+) -> Result<(StatusCode, Json<WebauthnCredentialResponse>), AppError> {
+    req.validate()?;
+    
+    let challenge = WebauthnRepository::take_challenge(&state.db, req.challenge_id, CHALLENGE_REGISTRATION)
+        .await?
+        .filter(|c| c.user_id == current_user.id)
+        .ok_or_else(|| AppError::BadRequest("Registration challenge expired".to_string()))?;
+    let registration: PasskeyRegistration = from_json(challenge.state)?;
+    
+    let webauthn = WebauthnService::new(&state.config)?;
+    let passkey = webauthn.finish_registration(&req.credential, &registration)?;
+    
+    let credential = WebauthnRepository::create_credential(
+        &state.db,
+        current_user.id,
+        &WebauthnService::credential_key(passkey.cred_id())?,
+        &req.name,
+        to_json(&passkey)?,
+    )
+    .await?;
+    
+    Ok((StatusCode::CREATED, Json(credential.to_response())))
+}
+
+/// Passwordless login: the passkey replaces both the password and any second factor.
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/passkey/start",
+    tag = "auth",
+    request_body = StartPasskeyLoginRequest,
+    responses((status = 200, description = "Authentication challenge", body = PasskeyLoginChallengeResponse))
+)]
+pub async fn start_login(
+    State(state): State<AppState>,
+    Json(req): Json<StartPasskeyLoginRequest>,
+) -> Result<Json<PasskeyLoginChallengeResponse>, AppError> {
+    let user = UserRepository::find_by_email(&state.db, &req.email)
+        .await?
+        .filter(|u| u.activated)
+        .ok_or_else(|| AppError::Unauthorized("Passkey authentication failed".to_string()))?;
+    
+    Ok(Json(start_authentication(&state, &user).await?))
+}
+
+/// Passkey as a second factor after a successful password step.
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/mfa/passkey",
+    tag = "auth",
+    request_body = StartPasskeyMfaRequest,
+    responses(
+        (status = 200, description = "Authentication challenge", body = PasskeyLoginChallengeResponse),
+        (status = 401, description = "Invalid MFA token"),
+    )
+)]
+pub async fn start_mfa(
+    State(state): State<AppState>,
+    Json(req): Json<StartPasskeyMfaRequest>,
+) -> Result<Json<PasskeyLoginChallengeResponse>, AppError> {
+    let user = pending_user(&state, &req.mfa_token).await?;
+    Ok(Json(start_authentication(&state, &user).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/passkey/finish",
+    tag = "auth",
+    request_body = FinishPasskeyLoginRequest,
+    responses(
+        (status = 200, description = "Signed in", body = AuthResponse),
+        (status = 401, description = "Assertion rejected"),
+    )
+)]
+pub async fn finish_login(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    Json(req): Json<FinishPasskeyLoginRequest>,
+) -> Result<Json<AuthResponse>, AppError> {
+    let user = finish_passkey_login(&state, &req).await?;
+    
+    Ok(Json(issue_auth_response(&state, version, &user).await?))
+}
+
+/// Checks the assertion for a login challenge and returns the user it signs
+/// in. Shared with the HTML second-factor form.
+pub async fn finish_passkey_login(state: &AppState, req: &FinishPasskeyLoginRequest) -> Result<User, AppError> {
+    let challenge = WebauthnRepository::take_challenge(&state.db, req.challenge_id, CHALLENGE_AUTHENTICATION)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Passkey authentication failed".to_string()))?;
+    let authentication: PasskeyAuthentication = from_json(challenge.state)?;
+    
+    let user = verify_assertion(state, challenge.user_id, &req.credential, &authentication).await;
+    telemetry::record_login("passkey", user.is_ok());
+    
+    user
+}
+
+async fn start_authentication(state: &AppState, user: &User) -> Result<PasskeyLoginChallengeResponse, AppError> {
+    let passkeys = load_passkeys(&WebauthnRepository::list_for_user(&state.db, user.id).await?)?;
+    if passkeys.is_empty() {
+        return Err(AppError::Unauthorized("Passkey authentication failed".to_string()));
+    }
+    
+    let webauthn = WebauthnService::new(&state.config)?;
+    let (options, authentication) = webauthn.start_authentication(&passkeys)?;
+    let challenge = WebauthnRepository::create_challenge(
+        &state.db,
+        user.id,
+        CHALLENGE_AUTHENTICATION,
+        to_json(&authentication)?,
+    )
+    .await?;
+    
+    Ok(PasskeyLoginChallengeResponse {
+        challenge_id: challenge.id,
+        options,
+    })
+}
+
+async fn verify_assertion(
+    state: &AppState,
+    user_id: Uuid,
+    credential: &PublicKeyCredential,
+    authentication: &PasskeyAuthentication,
+) -> Result<User, AppError> {
+    let webauthn = WebauthnService::new(&state.config)?;
+    let result = webauthn.finish_authentication(credential, authentication)?;
+    
+    let stored = WebauthnRepository::find_by_credential_id(&state.db, &WebauthnService::credential_key(result.cred_id())?)
+        .await?
+        .filter(|c| c.user_id == user_id)
+        .ok_or_else(|| AppError::Unauthorized("Passkey authentication failed".to_string()))?;
+    
+    WebauthnService::check_sign_count(stored.sign_count, &result)?;
+    
+    // A synced passkey's 0 must not lower the counter other authenticators are checked against
+    let sign_count = stored.sign_count.max(i64::from(result.counter()));
+    let mut passkey: Passkey = from_json(stored.passkey)?;
+    passkey.update_credential(&result);
+    WebauthnRepository::record_use(&state.db, stored.id, to_json(&passkey)?, sign_count).await?;
+    
+    UserRepository::find_by_id(&state.db, user_id)
+        .await?
+        .filter(|u| u.activated)
+        .ok_or_else(|| AppError::Unauthorized("Passkey authentication failed".to_string()))
+}
+
+fn load_passkeys(credentials: &[WebauthnCredential]) -> Result<Vec<Passkey>, AppError> {
+    credentials.iter().map(|c| from_json(c.passkey.clone())).collect()
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<serde_json::Value, AppError> {
+    serde_json::to_value(value).map_err(|e| AppError::Internal(format!("Failed to serialize WebAuthn state: {}", e)))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(value: serde_json::Value) -> Result<T, AppError> {
+    serde_json::from_value(value).map_err(|e| AppError::Internal(format!("Failed to deserialize WebAuthn state: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+    use webauthn_authenticator_rs::{softpasskey::SoftPasskey, WebauthnAuthenticator};
+    use webauthn_rs::prelude::{CreationChallengeResponse, RequestChallengeResponse, Url};
+    use crate::{models::User, test_support::*};
+    
+    type Authenticator = WebauthnAuthenticator<SoftPasskey>;
+    
+    fn origin() -> Url {
+        Url::parse(&config().auth.webauthn_rp_origin).unwrap()
+    }
+    
+    async fn register(app: &axum::Router, user: &User, authenticator: &mut Authenticator) -> Value {
+        let response = send(app, json_request(Method::POST, "/api/v1/webauthn/register/start", user, json!(null))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let challenge = body_json(response).await;
+        
+        let options: CreationChallengeResponse = serde_json::from_value(challenge["options"].clone()).unwrap();
+        let credential = authenticator.do_registration(origin(), options).unwrap();
+        
+        let body = json!({ "challenge_id": challenge["challenge_id"], "name": "Laptop", "credential": credential });
+        let response = send(app, json_request(Method::POST, "/api/v1/webauthn/register/finish", user, body)).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        
+        body_json(response).await
+    }
+    
+    /// Answers a login challenge and posts the assertion to `/auth/passkey/finish`.
+    async fn authenticate(app: &axum::Router, challenge: Value, authenticator: &mut Authenticator) -> (StatusCode, Value) {
+        let options: RequestChallengeResponse = serde_json::from_value(challenge["options"].clone()).unwrap();
+        let credential = authenticator.do_authentication(origin(), options).unwrap();
+        
+        let body = json!({ "challenge_id": challenge["challenge_id"], "credential": credential });
+        post_json(app, "/api/v1/auth/passkey/finish", body).await
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn registers_a_passkey(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+        
+        let credential = register(&app, &alice, &mut authenticator).await;
+        assert_eq!(credential["name"], "Laptop");
+        
+        let response = send(&app, json_request(Method::GET, "/api/v1/webauthn/credentials", &alice, json!(null))).await;
+        assert_eq!(body_json(response).await.as_array().unwrap().len(), 1);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn signs_in_without_a_password(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+        register(&app, &alice, &mut authenticator).await;
+        
+        let (status, challenge) = post_json(&app, "/api/v1/auth/passkey/start", json!({ "email": alice.email })).await;
+        assert_eq!(status, StatusCode::OK);
+        
+        let (status, body) = authenticate(&app, challenge, &mut authenticator).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["user"]["id"], alice.id.to_string());
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn passkeys_are_a_second_factor_without_totp(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+        register(&app, &alice, &mut authenticator).await;
+        
+        let (status, login) = post_json(&app, "/api/v1/auth/login", json!({ "email": alice.email, "password": PASSWORD })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(login["mfa_required"], true);
+        assert_eq!(login["methods"], json!(["passkey"]));
+        
+        let (status, challenge) = post_json(&app, "/api/v1/auth/mfa/passkey", json!({ "mfa_token": login["mfa_token"] })).await;
+        assert_eq!(status, StatusCode::OK);
+        
+        let (status, body) = authenticate(&app, challenge, &mut authenticator).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["token"].is_string());
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn rejects_a_sign_counter_that_went_backwards(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+        register(&app, &alice, &mut authenticator).await;
+        
+        let (_, challenge) = post_json(&app, "/api/v1/auth/passkey/start", json!({ "email": alice.email })).await;
+        let (status, _) = authenticate(&app, challenge, &mut authenticator).await;
+        assert_eq!(status, StatusCode::OK);
+        
+        // As if a clone of the credential had already signed further ahead
+        sqlx::query("UPDATE webauthn_credentials SET sign_count = sign_count + 100 WHERE user_id = $1")
+            .bind(alice.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        
+        let (_, challenge) = post_json(&app, "/api/v1/auth/passkey/start", json!({ "email": alice.email })).await;
+        let (status, body) = authenticate(&app, challenge, &mut authenticator).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.get("token").is_none());
+    }
+}
@@ -0,0 +1,389 @@
+use axum::{
+    extract::State,
+    http::{header::SET_COOKIE, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+use validator::Validate;
+use webauthn_rs::prelude::PublicKeyCredential;
+use crate::{
+    app::AppState,
+    auth::{end_session, start_session, token_digest},
+    database::users::UserRepository,
+    error::AppError,
+    extract::{Json, Path, Query},
+    handlers::api::{
+        auth::{activate, authenticate_password, change_forgotten_password, send_activation, send_reset},
+        mfa::{challenge, pending_user, second_factors, verify_second_factor},
+        users::register,
+        webauthn::finish_passkey_login,
+    },
+    models::{
+        CreateUserRequest, EmailRequest, FinishPasskeyLoginRequest, LoginRequest, MfaLoginRequest,
+        ResetPasswordRequest, User, MFA_METHOD_PASSKEY, MFA_METHOD_TOTP,
+    },
+    templates::{
+        ActivationFormTemplate, LoginTemplate, MfaTemplate, PasswordResetFormTemplate, ResetPasswordTemplate,
+        SignupTemplate,
+    },
+};
+
+/// `?sent=1` after a form that emails a link, in place of a flash message.
+#[derive(Debug, Deserialize)]
+pub struct SentParams {
+    pub sent: Option<String>,
+}
+
+/// Signs `user` in and redirects to their profile.
+fn signed_in_redirect(state: &AppState, user: &User, remember: bool) -> Result<Response, AppError> {
+    let cookie = start_session(&state.config, user.id, remember)?;
+    
+    Ok(([(SET_COOKIE, cookie)], Redirect::to(&user.profile_path())).into_response())
+}
+
+fn signup_page(name: String, email: String, errors: Vec<String>) -> SignupTemplate {
+    SignupTemplate {
+        title: "Sign up".to_string(),
+        name,
+        email,
+        errors,
+    }
+}
+
+pub async fn signup_form() -> SignupTemplate {
+    signup_page(String::new(), String::new(), Vec::new())
+}
+
+/// Invalid or taken details get the form back, filled in, with their errors.
+pub async fn signup(
+    State(state): State<AppState>,
+    Form(req): Form<CreateUserRequest>,
+) -> Result<Response, AppError> {
+    match register(&state, &req).await {
+        Ok(_) => Ok(Redirect::to("/account_activations/new?sent=1").into_response()),
+        Err(e) if e.report().status.is_client_error() => {
+            let form = signup_page(req.name, req.email, e.report().full_messages());
+            Ok((StatusCode::UNPROCESSABLE_ENTITY, form).into_response())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn login_page(email: String, errors: Vec<String>) -> LoginTemplate {
+    LoginTemplate {
+        title: "Log in".to_string(),
+        email,
+        errors,
+    }
+}
+
+pub async fn login_form() -> LoginTemplate {
+    login_page(String::new(), Vec::new())
+}
+
+/// Accounts with a second factor get the second-factor form instead of a session.
+/// Wrong credentials get the form back with a 401, which the login throttle
+/// counts as a failure.
+pub async fn login(
+    State(state): State<AppState>,
+    Form(req): Form<LoginRequest>,
+) -> Result<Response, AppError> {
+    let user = match authenticate_password(&state, &req.email, &req.password).await {
+        Ok(user) => user,
+        Err(AppError::Unauthorized(message)) => {
+            return Ok((StatusCode::UNAUTHORIZED, login_page(req.email, vec![message])).into_response());
+        }
+        Err(e) => return Err(e),
+    };
+    let remember_me = req.remember_me.unwrap_or(false);
+    
+    match challenge(&state, &user).await? {
+        Some(challenge) => Ok(mfa_form(challenge.mfa_token, &challenge.methods, remember_me, Vec::new()).into_response()),
+        None => signed_in_redirect(&state, &user, remember_me),
+    }
+}
+
+/// The second login step. `remember_me` rides along from the password form.
+#[derive(Debug, Deserialize)]
+pub struct MfaForm {
+    pub mfa_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+    pub remember_me: Option<bool>,
+}
+
+fn mfa_form(mfa_token: String, methods: &[String], remember_me: bool, errors: Vec<String>) -> MfaTemplate {
+    MfaTemplate {
+        title: "Two-factor authentication".to_string(),
+        mfa_token,
+        remember_me,
+        totp: methods.iter().any(|m| m == MFA_METHOD_TOTP),
+        passkey: methods.iter().any(|m| m == MFA_METHOD_PASSKEY),
+        errors,
+    }
+}
+
+/// A wrong code gets the form back with the same pending token; anything
+/// else, such as an expired token, is an error page.
+pub async fn login_mfa(
+    State(state): State<AppState>,
+    Form(form): Form<MfaForm>,
+) -> Result<Response, AppError> {
+    let remember_me = form.remember_me.unwrap_or(false);
+    let req = MfaLoginRequest {
+        mfa_token: form.mfa_token,
+        code: form.code,
+        recovery_code: form.recovery_code,
+    };
+    
+    match verify_second_factor(&state, &req).await {
+        Ok(user) => signed_in_redirect(&state, &user, remember_me),
+        Err(AppError::Unauthorized(message)) if message == "Invalid authentication code" => {
+            let methods = second_factors(&state, &pending_user(&state, &req.mfa_token).await?).await?;
+            let form = mfa_form(req.mfa_token, &methods, remember_me, vec![message]);
+            
+            Ok((StatusCode::UNAUTHORIZED, form).into_response())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// The passkey half of the second login step, posted by `passkey.js`.
+#[derive(Debug, Deserialize)]
+pub struct PasskeyForm {
+    pub challenge_id: Uuid,
+    pub credential: PublicKeyCredential,
+    #[serde(default)]
+    pub remember_me: bool,
+}
+
+pub async fn login_passkey(
+    State(state): State<AppState>,
+    Json(form): Json<PasskeyForm>,
+) -> Result<Response, AppError> {
+    let req = FinishPasskeyLoginRequest {
+        challenge_id: form.challenge_id,
+        credential: form.credential,
+    };
+    let user = finish_passkey_login(&state, &req).await?;
+    
+    signed_in_redirect(&state, &user, form.remember_me)
+}
+
+pub async fn logout(State(state): State<AppState>) -> impl IntoResponse {
+    ([(SET_COOKIE, end_session(&state.config))], Redirect::to("/"))
+}
+
+pub async fn activate_account(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Response, AppError> {
+    let user = activate(&state, &token).await?;
+    
+    signed_in_redirect(&state, &user, false)
+}
+
+pub async fn activation_form(Query(params): Query<SentParams>) -> ActivationFormTemplate {
+    ActivationFormTemplate {
+        title: "Resend activation email".to_string(),
+        notice: params
+            .sent
+            .map(|_| "If that account is awaiting activation, a new link is on its way. Check your email.".to_string()),
+    }
+}
+
+pub async fn resend_activation(
+    State(state): State<AppState>,
+    Form(req): Form<EmailRequest>,
+) -> Result<Redirect, AppError> {
+    req.validate()?;
+    send_activation(&state, &req.email).await?;
+    
+    Ok(Redirect::to("/account_activations/new?sent=1"))
+}
+
+pub async fn password_reset_form(Query(params): Query<SentParams>) -> PasswordResetFormTemplate {
+    PasswordResetFormTemplate {
+        title: "Forgot password".to_string(),
+        notice: params
+            .sent
+            .map(|_| "Email sent with password reset instructions, if that account exists.".to_string()),
+    }
+}
+
+pub async fn send_password_reset(
+    State(state): State<AppState>,
+    Form(req): Form<EmailRequest>,
+) -> Result<Redirect, AppError> {
+    req.validate()?;
+    send_reset(&state, &req.email).await?;
+    
+    Ok(Redirect::to("/password_resets/new?sent=1"))
+}
+
+pub async fn reset_password_form(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<ResetPasswordTemplate, AppError> {
+    UserRepository::find_by_reset_token(&state.db, &token_digest(&token))
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Password reset link is invalid or has expired".to_string()))?;
+    
+    Ok(ResetPasswordTemplate {
+        title: "Reset password".to_string(),
+        token,
+        errors: Vec::new(),
+    })
+}
+
+/// Invalid passwords get the form back; a bad or expired link is an error page.
+/// Accounts with a second factor continue to the second-factor form, as after
+/// a password login.
+pub async fn reset_password(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Form(req): Form<ResetPasswordRequest>,
+) -> Result<Response, AppError> {
+    match change_forgotten_password(&state, &token, &req).await {
+        Ok(user) => match challenge(&state, &user).await? {
+            Some(challenge) => Ok(mfa_form(challenge.mfa_token, &challenge.methods, false, Vec::new()).into_response()),
+            None => signed_in_redirect(&state, &user, false),
+        },
+        Err(e @ AppError::ValidationErrors(_)) => Ok((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ResetPasswordTemplate {
+                title: "Reset password".to_string(),
+                token,
+                errors: e.report().full_messages(),
+            },
+        )
+            .into_response()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        response::Response,
+    };
+    use sqlx::PgPool;
+    use crate::{
+        auth::{token_digest, SESSION_COOKIE},
+        database::users::UserRepository,
+        test_support::*,
+    };
+    
+    fn starts_session(response: &Response<Body>) -> bool {
+        response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .any(|cookie| cookie.to_str().unwrap().starts_with(&format!("{}=", SESSION_COOKIE)))
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn mfa_users_need_a_code_after_their_password(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let secret = enable_mfa(&pool, &alice).await;
+        
+        let form = format!("email={}&password={}", alice.email, PASSWORD);
+        let response = send(&app, form_post(&app, "/login", "").await.body(Body::from(form)).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!starts_session(&response));
+        
+        let html = body_text(response).await;
+        let mfa_token = html
+            .split("name=\"mfa_token\" value=\"")
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap()
+            .to_string();
+        
+        let form = format!("mfa_token={}&code=000000&recovery_code=", mfa_token);
+        let response = send(&app, form_post(&app, "/login/mfa", "").await.body(Body::from(form)).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(!starts_session(&response));
+        
+        let form = format!("mfa_token={}&code={}&recovery_code=", mfa_token, totp_code(&secret, &alice));
+        let response = send(&app, form_post(&app, "/login/mfa", "").await.body(Body::from(form)).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], format!("/users/{}", alice.id));
+        assert!(starts_session(&response));
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn password_resets_still_ask_mfa_users_for_a_code(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        enable_mfa(&pool, &alice).await;
+        UserRepository::set_reset_token(&pool, alice.id, &token_digest("reset-token")).await.unwrap();
+        
+        let form = "password=new-password&password_confirmation=new-password";
+        let request = form_post(&app, "/password_resets/reset-token", "").await.body(Body::from(form)).unwrap();
+        let response = send(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!starts_session(&response));
+        assert!(body_text(response).await.contains("name=\"mfa_token\""));
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn rejected_signups_get_the_filled_in_form_back(pool: PgPool) {
+        let app = app(pool.clone());
+        create_user(&pool, "Alice", false).await;
+        
+        let form = "name=Second+Alice&email=alice%40example.com&password=password&password_confirmation=password";
+        let response = send(&app, form_post(&app, "/signup", "").await.body(Body::from(form)).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let html = body_text(response).await;
+        assert!(html.contains("Email has already been taken"));
+        assert!(html.contains(r#"value="Second Alice""#));
+        assert!(html.contains(r#"value="alice@example.com""#));
+        
+        let form = "name=Bob&email=bob%40example.com&password=pw&password_confirmation=pw";
+        let response = send(&app, form_post(&app, "/signup", "").await.body(Body::from(form)).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let html = body_text(response).await;
+        assert!(html.contains("Password must be at least 6 characters"));
+        assert!(html.contains(r#"value="Bob""#));
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn failed_logins_get_the_form_back_and_count_toward_the_lockout(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        
+        for _ in 0..5 {
+            let form = format!("email={}&password=wrong", alice.email);
+            let response = send(&app, form_post(&app, "/login", "").await.body(Body::from(form)).unwrap()).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            let html = body_text(response).await;
+            assert!(html.contains("Invalid email/password combination"));
+            assert!(html.contains(&format!(r#"value="{}""#, alice.email)));
+        }
+        
+        let form = format!("email={}&password={}", alice.email, PASSWORD);
+        let response = send(&app, form_post(&app, "/login", "").await.body(Body::from(form)).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(!starts_session(&response));
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn pages_needing_a_session_redirect_to_login(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        
+        let request = Request::get(format!("/users/{}/edit", alice.id))
+            .header(header::ACCEPT, "text/html")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&app, request).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], "/login");
+    }
+}
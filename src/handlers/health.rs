@@ -0,0 +1,104 @@
+use std::{sync::atomic::Ordering, time::Duration};
+use axum::{extract::State, http::StatusCode, Json};
+use serde_json::{json, Value};
+use crate::{app::AppState, database};
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Liveness: the process is up and serving requests.
+pub async fn healthz() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+/// Readiness: the database is reachable, every migration has been applied and
+/// the server is not shutting down. Migrations only move forward while the
+/// server runs, so once they're all applied readiness stops asking.
+pub async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    let database = match tokio::time::timeout(CHECK_TIMEOUT, sqlx::query("SELECT 1").execute(&state.db)).await {
+        Ok(Ok(_)) => "ok".to_string(),
+        Ok(Err(e)) => {
+            tracing::warn!("Readiness database check failed: {}", e);
+            "unavailable".to_string()
+        }
+        Err(_) => "timeout".to_string(),
+    };
+    
+    let migrations = if database != "ok" {
+        "unknown".to_string()
+    } else if state.migrations_applied.load(Ordering::Relaxed) {
+        "ok".to_string()
+    } else {
+        match tokio::time::timeout(CHECK_TIMEOUT, database::pending_migrations(&state.db)).await {
+            Ok(Ok(0)) => {
+                state.migrations_applied.store(true, Ordering::Relaxed);
+                "ok".to_string()
+            }
+            Ok(Ok(pending)) => format!("{} pending", pending),
+            Ok(Err(e)) => {
+                tracing::warn!("Readiness migration check failed: {}", e);
+                "unknown".to_string()
+            }
+            Err(_) => "timeout".to_string(),
+        }
+    };
+    
+    let shutting_down = state.shutdown.is_shutting_down();
+    let ready = database == "ok" && migrations == "ok" && !shutting_down;
+    
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (
+        status,
+        Json(json!({
+            "status": if ready { "ok" } else { "unavailable" },
+            "checks": {
+                "database": database,
+                "migrations": migrations,
+                "shutting_down": shutting_down,
+            },
+        })),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::{Request, StatusCode}};
+    use sqlx::PgPool;
+    use crate::{database::MIGRATOR, test_support::*};
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn readiness_fails_once_draining_starts(pool: PgPool) {
+        let state = state(pool);
+        let app = crate::create_app(state.clone());
+        let readyz = || Request::get("/readyz").body(Body::empty()).unwrap();
+        
+        let response = send(&app, readyz()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["checks"]["migrations"], "ok");
+        
+        state.shutdown.start_draining();
+        let response = send(&app, readyz()).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body_json(response).await["checks"]["shutting_down"], true);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn readiness_fails_while_a_migration_is_pending(pool: PgPool) {
+        let app = crate::create_app(state(pool.clone()));
+        let readyz = || Request::get("/readyz").body(Body::empty()).unwrap();
+        let versions: Vec<i64> = MIGRATOR
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration())
+            .map(|m| m.version)
+            .collect();
+        MIGRATOR.undo(&pool, versions[versions.len() - 2]).await.unwrap();
+        
+        let response = send(&app, readyz()).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body_json(response).await["checks"]["migrations"], "1 pending");
+        
+        MIGRATOR.run(&pool).await.unwrap();
+        let response = send(&app, readyz()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["checks"]["migrations"], "ok");
+    }
+}
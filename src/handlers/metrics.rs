@@ -0,0 +1,70 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::IntoResponse,
+};
+use crate::{app::AppState, auth::constant_time_eq, error::AppError, telemetry};
+
+/// Prometheus scrape endpoint in the text exposition format. Scrapers
+/// authenticate with `telemetry.metrics_token`; without one it doesn't exist.
+pub async fn render(State(state): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, AppError> {
+    let token = &state.config.telemetry.metrics_token;
+    if token.is_empty() {
+        return Err(AppError::NotFound("Not found".to_string()));
+    }
+    
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "));
+    if !presented.map_or(false, |presented| constant_time_eq(presented.as_bytes(), token.as_bytes())) {
+        return Err(AppError::Unauthorized("A valid metrics token is required".to_string()));
+    }
+    
+    telemetry::record_pool_metrics(&state.db);
+    
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use sqlx::PgPool;
+    use crate::{app::AppState, test_support::*};
+    
+    fn scrape(token: Option<&str>) -> Request<Body> {
+        let mut request = Request::get("/metrics");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        request.body(Body::empty()).unwrap()
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn metrics_are_off_without_a_token(pool: PgPool) {
+        let app = app(pool);
+        
+        assert_eq!(send(&app, scrape(None)).await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(send(&app, scrape(Some(""))).await.status(), StatusCode::NOT_FOUND);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn metrics_require_the_configured_token(pool: PgPool) {
+        let mut config = config();
+        config.telemetry.metrics_token = "scrape-secret".to_string();
+        let app = crate::create_app(AppState::new(pool, config).unwrap());
+        
+        assert_eq!(send(&app, scrape(None)).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&app, scrape(Some("wrong"))).await.status(), StatusCode::UNAUTHORIZED);
+        
+        let response = send(&app, scrape(Some("scrape-secret"))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(body_text(response).await.contains("db_pool_connections"));
+    }
+}
@@ -0,0 +1,50 @@
+use axum::{
+    extract::State,
+    response::Redirect,
+    Extension, Form,
+};
+use uuid::Uuid;
+use validator::Validate;
+use crate::{
+    app::AppState,
+    database::{microposts::MicropostRepository, users::UserRepository},
+    error::AppError,
+    extract::Path,
+    models::{CreateMicropostRequest, User},
+};
+
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Form(req): Form<CreateMicropostRequest>,
+) -> Result<Redirect, AppError> {
+    req.validate()?;
+    
+    MicropostRepository::create(&state.db, current_user.id, &req).await?;
+    
+    Ok(Redirect::to(&current_user.profile_path()))
+}
+
+pub async fn delete(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, AppError> {
+    let micropost = MicropostRepository::find_by_id(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Micropost not found".to_string()))?;
+    if micropost.user_id != current_user.id && !current_user.admin {
+        return Err(AppError::Forbidden("You can only delete your own microposts".to_string()));
+    }
+    
+    MicropostRepository::delete(&state.db, id).await?;
+    
+    // Admins deleting someone else's micropost land back on the author's profile
+    let author = if micropost.user_id == current_user.id {
+        Some(current_user)
+    } else {
+        UserRepository::find_by_id(&state.db, micropost.user_id).await?
+    };
+    
+    Ok(Redirect::to(&author.map_or_else(|| "/users".to_string(), |author| author.profile_path())))
+}
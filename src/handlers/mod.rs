@@ -1,4 +1,6 @@
 pub mod static_pages;
+pub mod health;
+pub mod metrics;
 pub mod auth;
 pub mod users;
 pub mod microposts;
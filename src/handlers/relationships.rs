@@ -0,0 +1,96 @@
+use axum::{
+    extract::State,
+    response::Redirect,
+    Extension, Form,
+};
+use uuid::Uuid;
+use crate::{
+    app::AppState,
+    database::{follow_requests::FollowRequestRepository, relationships::RelationshipRepository, users::UserRepository},
+    error::AppError,
+    extract::Path,
+    models::{CreateRelationshipRequest, User},
+};
+
+async fn find_user(state: &AppState, id: Uuid) -> Result<User, AppError> {
+    UserRepository::find_by_id(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))
+}
+
+/// Private accounts get a pending follow request, exactly as through the API.
+pub async fn create(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Form(req): Form<CreateRelationshipRequest>,
+) -> Result<Redirect, AppError> {
+    if req.followed_id == current_user.id {
+        return Err(AppError::BadRequest("You cannot follow yourself".to_string()));
+    }
+    
+    let followed = find_user(&state, req.followed_id).await?;
+    FollowRequestRepository::follow(&state.db, current_user.id, &followed).await?;
+    
+    Ok(Redirect::to(&followed.profile_path()))
+}
+
+/// `id` is the followed user's id, as in the API. Also withdraws a pending request.
+pub async fn delete(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, AppError> {
+    let followed = find_user(&state, id).await?;
+    if !RelationshipRepository::unfollow(&state.db, current_user.id, id).await? {
+        return Err(AppError::NotFound("Relationship not found".to_string()));
+    }
+    
+    Ok(Redirect::to(&followed.profile_path()))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, StatusCode},
+    };
+    use sqlx::PgPool;
+    use crate::{
+        database::{relationships::RelationshipRepository, users::UserRepository},
+        models::UpdateUserRequest,
+        test_support::*,
+    };
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn following_a_private_account_from_the_profile_page_leaves_a_pending_request(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", true).await;
+        let handle: UpdateUserRequest = serde_json::from_value(serde_json::json!({ "username": "bob" })).unwrap();
+        UserRepository::update(&pool, bob.id, &handle, None, None).await.unwrap();
+        
+        let request = form_post(&app, "/relationships", &session_cookie(alice.id))
+            .await
+            .body(Body::from(format!("followed_id={}", bob.id)))
+            .unwrap();
+        let response = send(&app, request).await;
+        
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], "/users/bob");
+        let relationship = RelationshipRepository::find(&pool, alice.id, bob.id).await.unwrap().unwrap();
+        assert_eq!(relationship.status, "pending");
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn following_requires_a_session(pool: PgPool) {
+        let app = app(pool.clone());
+        let bob = create_user(&pool, "Bob", false).await;
+        
+        let request = form_post(&app, "/relationships", "")
+            .await
+            .body(Body::from(format!("followed_id={}", bob.id)))
+            .unwrap();
+        
+        assert_eq!(send(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+    }
+}
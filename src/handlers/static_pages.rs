@@ -1,5 +1,6 @@
+use askama::Template;
 use axum::{extract::State, response::Html};
-use crate::{app::AppState, templates::StaticPageTemplate};
+use crate::{app::AppState, error::AppError, templates::StaticPageTemplate};
 
 pub async fn home(State(_state): State<AppState>) -> Html<String> {
     let template = StaticPageTemplate {
@@ -36,3 +37,8 @@ pub async fn contact(State(_state): State<AppState>) -> Html<String> {
     };
     Html(template.render().unwrap())
 }
+
+/// Fallback for unmatched routes, rendered as an HTML page or JSON by content negotiation.
+pub async fn not_found() -> AppError {
+    AppError::NotFound("Page not found".to_string())
+}
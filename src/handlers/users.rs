@@ -0,0 +1,283 @@
+use axum::{
+    extract::State,
+    response::Redirect,
+    Extension, Form,
+};
+use uuid::Uuid;
+use validator::Validate;
+use crate::{
+    app::AppState,
+    auth::hash_password,
+    database::{
+        follow_requests::FollowRequestRepository, microposts::MicropostRepository,
+        relationships::RelationshipRepository, users::UserRepository,
+    },
+    error::AppError,
+    extract::{Path, Query},
+    models::{UpdateUserRequest, User},
+    templates::{FollowTemplate, UserEditTemplate, UserShowTemplate, UsersIndexTemplate},
+    utils::pagination::PaginationParams,
+};
+
+async fn find_user(state: &AppState, id: Uuid) -> Result<User, AppError> {
+    UserRepository::find_by_id(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))
+}
+
+fn ensure_can_edit(current_user: &User, id: Uuid) -> Result<(), AppError> {
+    if current_user.id == id || current_user.admin {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden("You can only edit your own profile".to_string()))
+    }
+}
+
+pub async fn index(
+    State(state): State<AppState>,
+    Query(params): Query<PaginationParams>,
+) -> Result<UsersIndexTemplate, AppError> {
+    let users = UserRepository::list(&state.db, &params).await?;
+    
+    Ok(UsersIndexTemplate {
+        title: "All users".to_string(),
+        users,
+    })
+}
+
+/// Private accounts show their counts to everyone but their microposts only to
+/// the owner, admins and approved followers.
+pub async fn show(
+    State(state): State<AppState>,
+    current_user: Option<Extension<User>>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<PaginationParams>,
+) -> Result<UserShowTemplate, AppError> {
+    let viewer = current_user.map(|Extension(user)| user);
+    let user = find_user(&state, id).await?;
+    let (_, following_count, followers_count) = UserRepository::get_stats(&state.db, id).await?;
+    
+    let microposts = if FollowRequestRepository::can_view(&state.db, viewer.as_ref(), &user).await? {
+        Some(MicropostRepository::list_for_user(&state.db, id, &params).await?)
+    } else {
+        None
+    };
+    
+    let follow_status = match &viewer {
+        Some(viewer) if viewer.id != id => RelationshipRepository::find(&state.db, viewer.id, id)
+            .await?
+            .map(|relationship| relationship.status),
+        _ => None,
+    };
+    
+    Ok(UserShowTemplate {
+        title: user.name.clone(),
+        microposts,
+        following_count,
+        followers_count,
+        signed_in: viewer.is_some(),
+        is_current_user: viewer.as_ref().map_or(false, |viewer| viewer.id == id),
+        follow_status,
+        user,
+    })
+}
+
+pub async fn edit_form(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<Uuid>,
+) -> Result<UserEditTemplate, AppError> {
+    ensure_can_edit(&current_user, id)?;
+    
+    Ok(UserEditTemplate {
+        title: "Edit user".to_string(),
+        user: find_user(&state, id).await?,
+    })
+}
+
+/// The edit form always posts both password fields; left blank they keep the
+/// current password.
+pub async fn update(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<User>,
+    Path(id): Path<Uuid>,
+    Form(mut req): Form<UpdateUserRequest>,
+) -> Result<Redirect, AppError> {
+    ensure_can_edit(&current_user, id)?;
+    
+    if req.password.as_deref().map_or(false, str::is_empty) {
+        req.password = None;
+        req.password_confirmation = None;
+    }
+    req.validate()?;
+    if req.password.is_some() && req.password != req.password_confirmation {
+        return Err(AppError::Validation("Password confirmation does not match".to_string()));
+    }
+    
+    let password_hash = req.password.as_deref().map(hash_password).transpose()?;
+    let user = UserRepository::update(&state.db, id, &req, password_hash.as_deref(), None).await?;
+    
+    Ok(Redirect::to(&user.profile_path()))
+}
+
+/// Behind `private_profile_middleware`, which has already checked the viewer.
+pub async fn following(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<PaginationParams>,
+) -> Result<FollowTemplate, AppError> {
+    let user = find_user(&state, id).await?;
+    let users = RelationshipRepository::following(&state.db, id, &params).await?;
+    
+    Ok(FollowTemplate {
+        title: "Following".to_string(),
+        user,
+        users,
+    })
+}
+
+/// Behind `private_profile_middleware`, which has already checked the viewer.
+pub async fn followers(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<PaginationParams>,
+) -> Result<FollowTemplate, AppError> {
+    let user = find_user(&state, id).await?;
+    let users = RelationshipRepository::followers(&state.db, id, &params).await?;
+    
+    Ok(FollowTemplate {
+        title: "Followers".to_string(),
+        user,
+        users,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use sqlx::PgPool;
+    use crate::{
+        database::{follow_requests::FollowRequestRepository, microposts::MicropostRepository, users::UserRepository},
+        models::{CreateMicropostRequest, UpdateUserRequest, User},
+        test_support::*,
+    };
+    
+    fn get(uri: &str, viewer: Option<&User>) -> Request<Body> {
+        let request = Request::get(uri).header(header::ACCEPT, "text/html");
+        let request = match viewer {
+            Some(viewer) => request.header(header::COOKIE, session_cookie(viewer.id)),
+            None => request,
+        };
+        request.body(Body::empty()).unwrap()
+    }
+    
+    async fn private_author(pool: &PgPool) -> User {
+        let author = create_user(pool, "Bob", true).await;
+        let post = CreateMicropostRequest { content: "for followers only".to_string(), picture: None };
+        MicropostRepository::create(pool, author.id, &post).await.unwrap();
+        author
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn avatars_come_from_a_host_the_csp_allows(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        
+        let response = send(&app, get(&format!("/users/{}", alice.id), None)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let csp = response.headers()[header::CONTENT_SECURITY_POLICY].to_str().unwrap().to_string();
+        let html = body_text(response).await;
+        
+        let src = html.split("<img src=\"").nth(1).and_then(|rest| rest.split('"').next()).unwrap();
+        let origin = src.splitn(4, '/').take(3).collect::<Vec<_>>().join("/");
+        let img_src = csp.split(';').map(str::trim).find(|d| d.starts_with("img-src")).unwrap();
+        assert!(img_src.split_whitespace().any(|source| source == origin), "{} not allowed by {}", origin, img_src);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn profile_links_use_the_handle(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", false).await;
+        let handle: UpdateUserRequest = serde_json::from_value(serde_json::json!({ "username": "alice" })).unwrap();
+        UserRepository::update(&pool, alice.id, &handle, None, None).await.unwrap();
+        
+        let index = body_text(send(&app, get("/users", Some(&bob))).await).await;
+        assert!(index.contains(r#"href="/users/alice""#));
+        assert!(index.contains(&format!(r#"href="/users/{}""#, bob.id)));
+        
+        let profile = body_text(send(&app, get("/users/alice", Some(&alice))).await).await;
+        assert!(profile.contains(r#"href="/users/alice/followers""#));
+        assert!(profile.contains(r#"href="/users/alice/edit""#));
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn renaming_from_the_edit_form_redirects_the_old_handle(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let handle: UpdateUserRequest = serde_json::from_value(serde_json::json!({ "username": "alice" })).unwrap();
+        UserRepository::update(&pool, alice.id, &handle, None, None).await.unwrap();
+        
+        let edit = body_text(send(&app, get("/users/alice/edit", Some(&alice))).await).await;
+        for field in ["username", "bio", "location", "website", "banner"] {
+            assert!(edit.contains(&format!(r#"name="{}""#, field)), "no {} field", field);
+        }
+        
+        let body = "_method=patch&name=Alice&email=alice%40example.com&password=&password_confirmation=\
+                    &username=wonderland&bio=Down+the+rabbit+hole&location=&website=&banner=";
+        let request = form_post(&app, &format!("/users/{}", alice.id), &session_cookie(alice.id)).await;
+        let response = send(&app, request.body(Body::from(body)).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], "/users/wonderland");
+        
+        let response = send(&app, get("/users/alice", None)).await;
+        assert_eq!(response.headers()[header::LOCATION], "/users/wonderland");
+        let user = UserRepository::find_by_id(&pool, alice.id).await.unwrap().unwrap();
+        assert_eq!(user.bio.as_deref(), Some("Down the rabbit hole"));
+        assert_eq!(user.location, None);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn private_profiles_hide_microposts_from_strangers(pool: PgPool) {
+        let app = app(pool.clone());
+        let bob = private_author(&pool).await;
+        let alice = create_user(&pool, "Alice", false).await;
+        
+        for viewer in [None, Some(&alice)] {
+            let response = send(&app, get(&format!("/users/{}", bob.id), viewer)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let html = body_text(response).await;
+            assert!(!html.contains("for followers only"));
+            assert!(html.contains("This account is private"));
+        }
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn private_profiles_show_microposts_to_the_owner_and_approved_followers(pool: PgPool) {
+        let app = app(pool.clone());
+        let bob = private_author(&pool).await;
+        let alice = create_user(&pool, "Alice", false).await;
+        let request = FollowRequestRepository::follow(&pool, alice.id, &bob).await.unwrap();
+        FollowRequestRepository::approve(&pool, request.id, bob.id).await.unwrap();
+        
+        for viewer in [&bob, &alice] {
+            let html = body_text(send(&app, get(&format!("/users/{}", bob.id), Some(viewer))).await).await;
+            assert!(html.contains("for followers only"));
+        }
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn private_follower_lists_use_the_session_viewer(pool: PgPool) {
+        let app = app(pool.clone());
+        let bob = private_author(&pool).await;
+        let alice = create_user(&pool, "Alice", false).await;
+        let uri = format!("/users/{}/followers", bob.id);
+        
+        assert_eq!(send(&app, get(&uri, None)).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(send(&app, get(&uri, Some(&alice))).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(send(&app, get(&uri, Some(&bob))).await.status(), StatusCode::OK);
+    }
+}
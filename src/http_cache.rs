@@ -0,0 +1,362 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use tower_http::set_header::SetResponseHeaderLayer;
+use uuid::Uuid;
+use crate::{
+    app::AppState,
+    database::{microposts::MicropostRepository, users::UserRepository},
+    error::AppError,
+    extract::Path,
+    models::{shows_email, User},
+    versioning::ApiVersion,
+};
+
+/// Default for API responses: never stored.
+pub const NO_STORE: &str = "no-store";
+/// Per-user representations that clients may keep but must revalidate with their ETag.
+pub const REVALIDATE: &str = "private, no-cache";
+
+pub fn cache_control(policy: &'static str) -> SetResponseHeaderLayer<HeaderValue> {
+    SetResponseHeaderLayer::if_not_present(header::CACHE_CONTROL, HeaderValue::from_static(policy))
+}
+
+pub fn http_date(at: DateTime<Utc>) -> String {
+    at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Weak validators for one representation. The ETag is two hashes: the
+/// resource's own revision, and everything else the representation depends on
+/// (counts, embedded authors). `If-None-Match` compares both, `If-Match` only the
+/// revision, so an edit isn't refused because someone followed the user in the
+/// meantime. `Last-Modified` is only set where a single `updated_at` covers the
+/// whole body, so `If-Modified-Since` can't go stale.
+pub struct Validators {
+    revision: String,
+    etag: String,
+    last_modified: Option<DateTime<Utc>>,
+    /// The row's own `updated_at`, which a write under `If-Match` is pinned to.
+    updated_at: Option<DateTime<Utc>>,
+}
+
+/// Added to the request when `If-Match` named the current revision: the row's
+/// `updated_at` at that point, so the write can refuse to apply if it changed
+/// between the check and the `UPDATE`.
+#[derive(Debug, Clone, Copy)]
+pub struct IfMatched(pub DateTime<Utc>);
+
+impl Validators {
+    fn new(
+        version: ApiVersion,
+        kind: &str,
+        revision: &[&[u8]],
+        dependencies: &[&[u8]],
+        updated_at: Option<DateTime<Utc>>,
+        last_modified: Option<DateTime<Utc>>,
+    ) -> Self {
+        let revision = digest(&[&[version.number()], kind.as_bytes()], revision);
+        let content = digest(&[revision.as_bytes()], dependencies);
+        
+        Self {
+            etag: format!("W/\"{}{}\"", revision, content),
+            revision,
+            // HTTP dates have second precision
+            last_modified: last_modified.and_then(|at| DateTime::from_timestamp(at.timestamp(), 0)),
+            updated_at,
+        }
+    }
+    
+    /// Weak comparison against an `If-None-Match` list.
+    fn matches(&self, header: &str) -> bool {
+        let ours = self.etag.trim_start_matches("W/");
+        header
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == ours)
+    }
+    
+    /// Compares an `If-Match` list against the revision half of the tag. Lost-update
+    /// protection with weak tags is sound here because the revision changes with
+    /// every write to the row, and the write itself is pinned to it (`IfMatched`).
+    fn matches_revision(&self, header: &str) -> bool {
+        header.split(',').map(str::trim).any(|tag| {
+            tag == "*"
+                || tag
+                    .trim_start_matches("W/")
+                    .trim_matches('"')
+                    .get(..self.revision.len())
+                    .map_or(false, |revision| revision == self.revision)
+        })
+    }
+    
+    fn apply(&self, headers: &mut HeaderMap) {
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(header::ETAG, etag);
+        }
+        if let Some(at) = self.last_modified {
+            if let Ok(value) = HeaderValue::from_str(&http_date(at)) {
+                headers.insert(header::LAST_MODIFIED, value);
+            }
+        }
+        headers.append(header::VARY, HeaderValue::from_static("authorization"));
+    }
+}
+
+/// First 16 hex digits of a SHA-256 over `seed` and length-prefixed `parts`.
+fn digest(seed: &[&[u8]], parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in seed {
+        hasher.update(part);
+    }
+    for part in parts {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    
+    format!("{:x}", hasher.finalize())[..16].to_string()
+}
+
+/// RFC 9110 §13.2.2: `If-Match` first, then `If-None-Match`, and
+/// `If-Modified-Since` only when there is no `If-None-Match`.
+fn evaluate_preconditions(method: &Method, headers: &HeaderMap, current: &Validators) -> Result<Option<Response>, AppError> {
+    let value_of = |name: header::HeaderName| headers.get(name).and_then(|value| value.to_str().ok());
+    let safe = method == Method::GET || method == Method::HEAD;
+    
+    if let Some(if_match) = value_of(header::IF_MATCH) {
+        if !current.matches_revision(if_match) {
+            return Err(AppError::PreconditionFailed("The resource has changed; fetch it again before updating".to_string()));
+        }
+    }
+    
+    if let Some(if_none_match) = value_of(header::IF_NONE_MATCH) {
+        if current.matches(if_none_match) {
+            if safe {
+                return Ok(Some(not_modified(current)));
+            }
+            return Err(AppError::PreconditionFailed("The resource already exists".to_string()));
+        }
+    } else if let (true, Some(since), Some(last_modified)) = (safe, value_of(header::IF_MODIFIED_SINCE), current.last_modified) {
+        let fresh = DateTime::parse_from_rfc2822(since).map_or(false, |since| last_modified <= since);
+        if fresh {
+            return Ok(Some(not_modified(current)));
+        }
+    }
+    
+    Ok(None)
+}
+
+fn not_modified(current: &Validators) -> Response {
+    let mut response = StatusCode::NOT_MODIFIED.into_response();
+    current.apply(response.headers_mut());
+    response
+}
+
+/// Shared flow for the conditional middlewares: answer 304/412 from the
+/// current validators, otherwise run the handler and tag successful responses,
+/// reloading the validators after writes so clients get the new ETag.
+async fn conditional<F, Fut>(mut request: Request, next: Next, load: F) -> Result<Response, AppError>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = Result<Option<Validators>, AppError>>,
+{
+    // Missing resources fall through so the handler produces its usual 404
+    let Some(current) = load().await? else {
+        return Ok(next.run(request).await);
+    };
+    
+    let method = request.method().clone();
+    if let Some(response) = evaluate_preconditions(&method, request.headers(), &current)? {
+        return Ok(response);
+    }
+    
+    let if_match = request.headers().get(header::IF_MATCH).and_then(|value| value.to_str().ok());
+    let named_revision = if_match.map_or(false, |tags| !tags.split(',').any(|tag| tag.trim() == "*"));
+    if let (true, Some(updated_at)) = (named_revision, current.updated_at) {
+        request.extensions_mut().insert(IfMatched(updated_at));
+    }
+    
+    let mut response = next.run(request).await;
+    if response.status().is_success() {
+        let latest = if method == Method::GET || method == Method::HEAD { Some(current) } else { load().await? };
+        if let Some(latest) = latest {
+            latest.apply(response.headers_mut());
+        }
+    }
+    
+    Ok(response)
+}
+
+/// Profile representations include follower and micropost counts, so the
+/// ETag covers them too and there is no `Last-Modified`. Only the profile row
+/// itself is the revision `If-Match` checks.
+async fn user_validators(state: &AppState, version: ApiVersion, id: Uuid, shows_email: bool) -> Result<Option<Validators>, AppError> {
+    let Some(user) = UserRepository::find_by_id(&state.db, id).await? else {
+        return Ok(None);
+    };
+    let (microposts, following, followers) = UserRepository::get_stats(&state.db, id).await?;
+    
+    Ok(Some(Validators::new(
+        version,
+        "user",
+        &[user.id.as_bytes(), &user.updated_at.timestamp_micros().to_be_bytes()],
+        &[
+            &microposts.to_be_bytes(),
+            &following.to_be_bytes(),
+            &followers.to_be_bytes(),
+            &[u8::from(shows_email)],
+        ],
+        Some(user.updated_at),
+        None,
+    )))
+}
+
+/// A micropost embeds its author's name and avatar, so the author's
+/// `updated_at` is part of its validators.
+async fn micropost_validators(state: &AppState, version: ApiVersion, id: Uuid) -> Result<Option<Validators>, AppError> {
+    let revisions = MicropostRepository::revisions(&state.db, id).await?;
+    
+    Ok(revisions.map(|revisions| {
+        Validators::new(
+            version,
+            "micropost",
+            &[id.as_bytes(), &revisions.updated_at.timestamp_micros().to_be_bytes()],
+            &[&revisions.author_updated_at.timestamp_micros().to_be_bytes()],
+            Some(revisions.updated_at),
+            Some(revisions.updated_at.max(revisions.author_updated_at)),
+        )
+    }))
+}
+
+/// The feed is read-only, so all of it is the revision.
+async fn feed_validators(state: &AppState, version: ApiVersion, viewer: &User, query: &str) -> Result<Option<Validators>, AppError> {
+    let fingerprint = MicropostRepository::feed_fingerprint(&state.db, viewer.id).await?;
+    
+    let timestamp = |at: Option<DateTime<Utc>>| at.map_or(0, |at| at.timestamp_micros()).to_be_bytes();
+    Ok(Some(Validators::new(
+        version,
+        "feed",
+        &[
+            viewer.id.as_bytes(),
+            query.as_bytes(),
+            &fingerprint.count.to_be_bytes(),
+            &timestamp(fingerprint.latest_post),
+            &timestamp(fingerprint.latest_author),
+        ],
+        &[],
+        None,
+        None,
+    )))
+}
+
+/// Expects `optional_auth_middleware` outside it: v2 shows the email to the
+/// owner and admins only, so the viewer is part of the representation.
+pub async fn user_conditional(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    current_user: Option<Extension<User>>,
+    Path(id): Path<Uuid>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let shows_email = shows_email(id, current_user.as_ref().map(|Extension(user)| user));
+    conditional(request, next, || user_validators(&state, version, id, shows_email)).await
+}
+
+pub async fn micropost_conditional(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    Path(id): Path<Uuid>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    conditional(request, next, || micropost_validators(&state, version, id)).await
+}
+
+/// Expects `optional_auth_middleware` outside it; anonymous requests go
+/// straight to the handler, which rejects them.
+pub async fn feed_conditional(
+    State(state): State<AppState>,
+    version: ApiVersion,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(viewer) = request.extensions().get::<User>().cloned() else {
+        return Ok(next.run(request).await);
+    };
+    let query = request.uri().query().unwrap_or_default().to_string();
+    
+    conditional(request, next, || feed_validators(&state, version, &viewer, &query)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use super::*;
+    use crate::{
+        models::{CreateMicropostRequest, UpdateMicropostRequest, UpdateUserRequest},
+        test_support::*,
+    };
+    
+    fn rename(name: &str) -> UpdateUserRequest {
+        serde_json::from_value(json!({ "name": name })).unwrap()
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn if_match_ignores_counts_but_not_profile_edits(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", false).await;
+        let uri = format!("/api/v1/users/{}", alice.id);
+        
+        let response = send(&app, json_request(Method::GET, &uri, &alice, json!(null))).await;
+        let etag = response.headers()[header::ETAG].clone();
+        
+        let follow = json_request(Method::POST, "/api/v1/relationships", &bob, json!({ "followed_id": alice.id }));
+        assert!(send(&app, follow).await.status().is_success());
+        
+        let mut revalidate = json_request(Method::GET, &uri, &alice, json!(null));
+        revalidate.headers_mut().insert(header::IF_NONE_MATCH, etag.clone());
+        assert_eq!(send(&app, revalidate).await.status(), StatusCode::OK, "the follower count is part of the body");
+        
+        let mut edit = json_request(Method::PUT, &uri, &alice, json!({ "name": "Alice Liddell" }));
+        edit.headers_mut().insert(header::IF_MATCH, etag.clone());
+        let response = send(&app, edit).await;
+        assert_eq!(response.status(), StatusCode::OK, "an unrelated follow isn't a conflicting edit");
+        assert_ne!(response.headers()[header::ETAG], etag);
+        
+        let mut stale = json_request(Method::PUT, &uri, &alice, json!({ "name": "Alice Again" }));
+        stale.headers_mut().insert(header::IF_MATCH, etag);
+        assert_eq!(send(&app, stale).await.status(), StatusCode::PRECONDITION_FAILED);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn writes_are_pinned_to_the_matched_revision(pool: PgPool) {
+        let alice = create_user(&pool, "Alice", false).await;
+        let micropost = MicropostRepository::create(&pool, alice.id, &CreateMicropostRequest { content: "Hello".to_string(), picture: None })
+            .await
+            .unwrap();
+        
+        // Another write landed between the If-Match check and the UPDATE
+        UserRepository::update(&pool, alice.id, &rename("Alice Liddell"), None, None).await.unwrap();
+        let edit = UpdateMicropostRequest { content: Some("Edited".to_string()), picture: None };
+        MicropostRepository::update(&pool, micropost.id, alice.id, &edit, None).await.unwrap();
+        
+        let stale_user = UserRepository::update(&pool, alice.id, &rename("Alice Again"), None, Some(alice.updated_at)).await;
+        assert!(matches!(stale_user, Err(AppError::PreconditionFailed(_))));
+        let stale_micropost = MicropostRepository::update(&pool, micropost.id, alice.id, &edit, Some(micropost.updated_at)).await;
+        assert!(matches!(stale_micropost, Err(AppError::PreconditionFailed(_))));
+        
+        let current = UserRepository::find_by_id(&pool, alice.id).await.unwrap().unwrap();
+        assert_eq!(current.name, "Alice Liddell");
+        let renamed = UserRepository::update(&pool, alice.id, &rename("Alice Again"), None, Some(current.updated_at)).await.unwrap();
+        assert_eq!(renamed.name, "Alice Again");
+    }
+}
@@ -0,0 +1,59 @@
+use lettre::{
+    message::header::ContentType,
+    transport::smtp::authentication::Credentials,
+    Message, SmtpTransport, Transport,
+};
+use crate::{config::Config, error::AppError, models::User};
+
+pub async fn send_account_activation(config: &Config, user: &User, token: &str) -> Result<(), AppError> {
+    let link = format!("{}/account_activations/{}", config.server.frontend_url, token);
+    let body = format!(
+        "Hi {},\n\nWelcome to the Sample App! Click the link below to activate your account:\n\n{}\n",
+        user.name, link
+    );
+    
+    deliver(config, &user.email, "Account activation", body).await
+}
+
+pub async fn send_password_reset(config: &Config, user: &User, token: &str) -> Result<(), AppError> {
+    let link = format!("{}/password_resets/{}", config.server.frontend_url, token);
+    let body = format!(
+        "To reset your password click the link below:\n\n{}\n\nThis link will expire in two hours.\n\n\
+         If you did not request your password to be reset, please ignore this email and your password will stay as it is.\n",
+        link
+    );
+    
+    deliver(config, &user.email, "Password reset", body).await
+}
+
+/// Sends a plain-text email. A `localhost` relay (MailHog and friends in
+/// development and test) is spoken to without TLS.
+async fn deliver(config: &Config, to: &str, subject: &str, body: String) -> Result<(), AppError> {
+    let mail = &config.mail;
+    let message = Message::builder()
+        .from(mail.from_email.parse().map_err(|_| AppError::Config("Invalid mail.from_email".to_string()))?)
+        .to(to.parse().map_err(|_| AppError::BadRequest("Invalid email address".to_string()))?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body)
+        .map_err(|e| AppError::Internal(format!("Failed to build email: {}", e)))?;
+    
+    let mut transport = if mail.smtp_host == "localhost" {
+        SmtpTransport::builder_dangerous(&mail.smtp_host)
+    } else {
+        SmtpTransport::starttls_relay(&mail.smtp_host).map_err(|e| AppError::Config(format!("Invalid mail.smtp_host: {}", e)))?
+    }
+    .port(mail.smtp_port);
+    if !mail.smtp_username.is_empty() {
+        transport = transport.credentials(Credentials::new(mail.smtp_username.clone(), mail.smtp_password.clone()));
+    }
+    let transport = transport.build();
+    
+    // lettre's SMTP transport is blocking
+    tokio::task::spawn_blocking(move || transport.send(&message))
+        .await
+        .map_err(|e| AppError::Internal(format!("Email task failed: {}", e)))?
+        .map_err(|e| AppError::Internal(format!("Failed to send email: {}", e)))?;
+    
+    Ok(())
+}
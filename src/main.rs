@@ -1,70 +1,169 @@
 use axum::{
     extract::DefaultBodyLimit,
-    http::{header, Method},
-    routing::{get, post},
+    middleware,
+    routing::{delete, get, patch, post, put},
     Router,
 };
-use std::net::SocketAddr;
+use sqlx::PgPool;
+use std::{net::SocketAddr, time::Duration};
 use tower::ServiceBuilder;
 use tower_http::{
-    cors::{Any, CorsLayer},
-    services::ServeDir,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
     trace::TraceLayer,
 };
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod app;
+mod assets;
 mod auth;
+mod cli;
+mod commands;
+mod compression;
 mod config;
 mod database;
 mod error;
+mod extract;
+mod forms;
+mod graphql;
 mod handlers;
+mod http_cache;
+mod mailer;
+mod method_override;
 mod models;
+mod openapi;
+mod profiles;
+mod rate_limit;
+mod security;
+mod shutdown;
+mod telemetry;
 mod templates;
+#[cfg(test)]
+mod test_support;
+// The example provider doubles as the OIDC tests' identity provider
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "../examples/mock_oidc_provider.rs"]
+mod mock_oidc_provider;
 mod utils;
+mod versioning;
+mod workers;
 
 use app::AppState;
+use clap::Parser;
+use cli::{Cli, Command, ServeArgs};
 use config::Config;
 use error::AppError;
+use rate_limit::RateLimitLayer;
+use versioning::ApiVersion;
 
 #[tokio::main]
 async fn main() -> Result<(), AppError> {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "rust_boilerplate=debug,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
+    dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+    
     // Load configuration
-    let config = Config::from_env()?;
+    let config = Config::load(&cli.config_overrides()?)?;
+    
+    // Initialize tracing
+    telemetry::init_tracing(&config.telemetry)?;
+    tracing::debug!("Loaded configuration: {:?}", config);
     
     // Initialize database
-    let pool = database::init(&config.database_url).await?;
+    let pool = database::init(&config.database).await?;
     
+    let result = match cli.command.unwrap_or(Command::Serve(ServeArgs::default())) {
+        Command::Serve(args) => serve(pool, config, args).await,
+        Command::Manage(command) => commands::run(command, &pool, &config).await,
+    };
+    
+    telemetry::shutdown_tracing();
+    result
+}
+
+async fn serve(pool: PgPool, config: Config, args: ServeArgs) -> Result<(), AppError> {
     // Run migrations
-    sqlx::migrate!("./migrations").run(&pool).await?;
+    if !args.skip_migrations {
+        database::MIGRATOR.run(&pool).await.map_err(sqlx::Error::from)?;
+    }
     
     // Create app state
-    let state = AppState::new(pool, config.clone());
-
+    let state = AppState::new(pool.clone(), config.clone())?;
+    let shutdown = state.shutdown.clone();
+    workers::spawn(&state);
+    assets::init(&config.storage.assets_dir)?;
+    
     // Build our application with routes
     let app = create_app(state);
-
+    
     // Run the server
-    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
+    let addr: SocketAddr = format!("{}:{}", config.server.host, config.server.port)
+        .parse()
+        .map_err(|_| AppError::Config("Invalid server.host".to_string()))?;
     tracing::info!("Server listening on {}", addr);
     
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
-
+    
+    // The first signal fails readiness checks, then after the drain delay stops
+    // accepting connections; a second signal skips the delay
+    let signal_shutdown = shutdown.clone();
+    let drain_delay = Duration::from_secs(config.server.drain_delay_secs);
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        signal_shutdown.start_draining();
+        tokio::select! {
+            _ = tokio::time::sleep(drain_delay) => {}
+            _ = shutdown::signal() => {}
+        }
+        signal_shutdown.trigger();
+    });
+    
+    let server_shutdown = shutdown.clone();
+    let server = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move { server_shutdown.cancelled().await });
+    
+    // In-flight requests get the shutdown timeout to finish before they're dropped
+    let timeout = Duration::from_secs(config.server.shutdown_timeout_secs);
+    tokio::select! {
+        result = server => result?,
+        _ = async {
+            shutdown.cancelled().await;
+            tokio::time::sleep(timeout).await;
+        } => tracing::warn!("Timed out after {:?} waiting for in-flight requests", timeout),
+    }
+    
+    if !shutdown.drain(timeout).await {
+        tracing::warn!("Timed out after {:?} waiting for background workers", timeout);
+    }
+    
+    pool.close().await;
+    tracing::info!("Shutdown complete");
+    
     Ok(())
 }
 
 fn create_app(state: AppState) -> Router {
-    Router::new()
+    // Follower lists of private accounts are only visible to approved followers
+    let private_profile_routes = Router::new()
+        .route("/users/:id/following", get(handlers::users::following))
+        .route("/users/:id/followers", get(handlers::users::followers))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::private_profile_middleware))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::optional_auth_middleware));
+    
+    // Signed-in pages; the viewer comes from the session cookie
+    let require_login = middleware::from_fn_with_state(state.clone(), auth::auth_middleware);
+    let optional_login = middleware::from_fn_with_state(state.clone(), auth::optional_auth_middleware);
+    let login_throttle = middleware::from_fn_with_state(state.clone(), auth::login_throttle_middleware);
+    let micropost_limit = RateLimitLayer::new(&state, "microposts", state.config.rate_limit.microposts);
+    let relationship_limit = RateLimitLayer::new(&state, "relationships", state.config.rate_limit.relationships);
+    let track_signups = middleware::from_fn(telemetry::track_signups);
+    let track_microposts = middleware::from_fn(telemetry::track_microposts_created);
+    let track_follows = middleware::from_fn(telemetry::track_follows);
+    
+    let routes = Router::new()
+        // Health checks
+        .route("/healthz", get(handlers::health::healthz))
+        .route("/readyz", get(handlers::health::readyz))
+        .route("/metrics", get(handlers::metrics::render))
+        
         // Static pages
         .route("/", get(handlers::static_pages::home))
         .route("/about", get(handlers::static_pages::about))
@@ -72,24 +171,25 @@ fn create_app(state: AppState) -> Router {
         .route("/contact", get(handlers::static_pages::contact))
         
         // Authentication routes
-        .route("/signup", get(handlers::auth::signup_form).post(handlers::auth::signup))
-        .route("/login", get(handlers::auth::login_form).post(handlers::auth::login))
-        .route("/logout", post(handlers::auth::logout))
+        .route("/signup", get(handlers::auth::signup_form).merge(post(handlers::auth::signup).route_layer(track_signups)))
+        .route("/login", get(handlers::auth::login_form).merge(post(handlers::auth::login).route_layer(login_throttle.clone())))
+        .route("/login/mfa", post(handlers::auth::login_mfa).route_layer(login_throttle))
+        .route("/login/passkey", post(handlers::auth::login_passkey))
+        .route("/logout", delete(handlers::auth::logout).post(handlers::auth::logout))
         
         // User routes
         .route("/users", get(handlers::users::index))
-        .route("/users/:id", get(handlers::users::show))
-        .route("/users/:id/edit", get(handlers::users::edit_form).post(handlers::users::update))
-        .route("/users/:id/following", get(handlers::users::following))
-        .route("/users/:id/followers", get(handlers::users::followers))
+        .route("/users/:id", get(handlers::users::show).route_layer(optional_login).merge(patch(handlers::users::update).route_layer(require_login.clone())))
+        .route("/users/:id/edit", get(handlers::users::edit_form).post(handlers::users::update).route_layer(require_login.clone()))
+        .merge(private_profile_routes)
         
         // Micropost routes
-        .route("/microposts", post(handlers::microposts::create))
-        .route("/microposts/:id", post(handlers::microposts::delete))
+        .route("/microposts", post(handlers::microposts::create).route_layer(micropost_limit).route_layer(track_microposts).route_layer(require_login.clone()))
+        .route("/microposts/:id", delete(handlers::microposts::delete).post(handlers::microposts::delete).route_layer(require_login.clone()))
         
         // Relationship routes
-        .route("/relationships", post(handlers::relationships::create))
-        .route("/relationships/:id", post(handlers::relationships::delete))
+        .route("/relationships", post(handlers::relationships::create).route_layer(relationship_limit).route_layer(track_follows).route_layer(require_login.clone()))
+        .route("/relationships/:id", delete(handlers::relationships::delete).post(handlers::relationships::delete).route_layer(require_login))
         
         // Account activation
         .route("/account_activations/:token", get(handlers::auth::activate_account))
@@ -100,47 +200,143 @@ fn create_app(state: AppState) -> Router {
         .route("/password_resets/:token", get(handlers::auth::reset_password_form).post(handlers::auth::reset_password))
         
         // API routes
-        .nest("/api/v1", create_api_routes())
+        .nest(ApiVersion::V1.prefix(), create_api_routes(state.clone()))
+        .nest(ApiVersion::V2.prefix(), create_api_routes(state.clone()))
+        .merge(openapi::routes())
+        
+        // GraphQL; bearer-token auth like the JSON API
+        .route("/graphql", post(graphql::handler).route_layer(middleware::from_fn_with_state(state.clone(), auth::optional_auth_middleware)))
         
-        // Static file serving
-        .nest_service("/assets", ServeDir::new("assets"))
+        // Static file serving, precompressed and fingerprinted
+        .merge(assets::routes(&state.config.storage.assets_dir))
+        .fallback(handlers::static_pages::not_found)
         
+        // These need the matched route
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(
-                    CorsLayer::new()
-                        .allow_origin(Any)
-                        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
-                        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION]),
-                )
-                .layer(DefaultBodyLimit::max(10 * 1024 * 1024)) // 10MB
+                .layer(middleware::from_fn(telemetry::track_http_metrics))
+                .layer(middleware::from_fn(profiles::reject_unknown_handles))
         )
-        .with_state(state)
+        .with_state(state.clone());
+    
+    // The API version, `_method` and profile handles are resolved before routing,
+    // inside the request id, error rendering and CSRF layers like everything else
+    let routed = ServiceBuilder::new()
+        .layer(middleware::from_fn_with_state(state.clone(), versioning::negotiate_version))
+        .layer(middleware::from_fn_with_state(state.clone(), method_override::method_override))
+        .layer(middleware::from_fn_with_state(state.clone(), profiles::resolve_handles))
+        .service(routes);
+    
+    Router::new().fallback_service(routed).layer(
+        ServiceBuilder::new()
+            .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+            .layer(TraceLayer::new_for_http().make_span_with(telemetry::make_request_span))
+            .layer(PropagateRequestIdLayer::x_request_id())
+            .layer(compression::compression_layer(&state.config))
+            .layer(middleware::from_fn_with_state(state.clone(), security::security_headers_middleware))
+            .layer(security::cors_layer(&state.config))
+            .layer(middleware::from_fn_with_state(state.clone(), error::render_error_responses))
+            .layer(middleware::from_fn_with_state(state.clone(), auth::csrf_middleware))
+            .layer(DefaultBodyLimit::max(state.config.storage.max_upload_bytes))
+    )
 }
 
-fn create_api_routes() -> Router<AppState> {
+fn create_api_routes(state: AppState) -> Router<AppState> {
+    let login_throttle = middleware::from_fn_with_state(state.clone(), auth::login_throttle_middleware);
+    let api_limit = RateLimitLayer::per_instance(&state, "api", state.config.rate_limit.api);
+    let micropost_limit = RateLimitLayer::new(&state, "microposts", state.config.rate_limit.microposts);
+    let relationship_limit = RateLimitLayer::new(&state, "relationships", state.config.rate_limit.relationships);
+    let track_signups = middleware::from_fn(telemetry::track_signups);
+    let track_microposts = middleware::from_fn(telemetry::track_microposts_created);
+    let track_follows = middleware::from_fn(telemetry::track_follows);
+    let optional_auth = middleware::from_fn_with_state(state.clone(), auth::optional_auth_middleware);
+    let require_auth = middleware::from_fn_with_state(state.clone(), auth::auth_middleware);
+    let revalidate = http_cache::cache_control(http_cache::REVALIDATE);
+    let user_conditional = middleware::from_fn_with_state(state.clone(), http_cache::user_conditional);
+    let feed_conditional = middleware::from_fn_with_state(state.clone(), http_cache::feed_conditional);
+    
+    // Owner, admins and approved followers only when the account is private
+    let private_profile_routes = Router::new()
+        .route("/users/:id/following", get(handlers::api::users::following))
+        .route("/users/:id/followers", get(handlers::api::users::followers))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::private_profile_middleware))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::optional_auth_middleware));
+    
+    let private_micropost_routes = Router::new()
+        .route("/microposts/:id", get(handlers::api::microposts::show).merge(put(handlers::api::microposts::update).delete(handlers::api::microposts::delete).route_layer(require_auth.clone())))
+        .route_layer(middleware::from_fn_with_state(state.clone(), http_cache::micropost_conditional))
+        .route_layer(http_cache::cache_control(http_cache::REVALIDATE))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::private_micropost_middleware))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::optional_auth_middleware));
+    
+    let follow_request_routes = Router::new()
+        .route("/follow_requests", get(handlers::api::follow_requests::index))
+        .route("/follow_requests/:id", delete(handlers::api::follow_requests::deny))
+        .route("/follow_requests/:id/approve", post(handlers::api::follow_requests::approve))
+        .route("/account/privacy", put(handlers::api::follow_requests::update_privacy))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth_middleware));
+    
+    let message_routes = Router::new()
+        .route("/conversations", get(handlers::api::messages::index).post(handlers::api::messages::create))
+        .route("/conversations/:id/messages", get(handlers::api::messages::messages).post(handlers::api::messages::create_message))
+        .route("/conversations/:id/read", post(handlers::api::messages::mark_read))
+        .route("/blocks", post(handlers::api::blocks::create))
+        .route("/blocks/:id", delete(handlers::api::blocks::delete))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth_middleware));
+    
+    let mfa_routes = Router::new()
+        .route("/mfa", delete(handlers::api::mfa::disable))
+        .route("/mfa/enrollment", post(handlers::api::mfa::enroll))
+        .route("/mfa/qr_code", get(handlers::api::mfa::qr_code))
+        .route("/mfa/confirm", post(handlers::api::mfa::confirm))
+        .route("/webauthn/credentials", get(handlers::api::webauthn::index))
+        .route("/webauthn/credentials/:id", delete(handlers::api::webauthn::delete))
+        .route("/webauthn/register/start", post(handlers::api::webauthn::start_registration))
+        .route("/webauthn/register/finish", post(handlers::api::webauthn::finish_registration))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth_middleware));
+    
+    let admin_routes = Router::new()
+        .route("/users/:id/mfa", delete(handlers::api::mfa::admin_reset))
+        .route_layer(middleware::from_fn(auth::admin_middleware))
+        .route_layer(middleware::from_fn_with_state(state, auth::auth_middleware));
+    
     Router::new()
         // API Authentication
-        .route("/auth/login", post(handlers::api::auth::login))
-        .route("/auth/signup", post(handlers::api::auth::signup))
+        .route("/auth/login", post(handlers::api::auth::login).route_layer(login_throttle.clone()))
+        .route("/auth/signup", post(handlers::api::auth::signup).route_layer(track_signups.clone()))
         .route("/auth/refresh", post(handlers::api::auth::refresh))
         .route("/auth/revoke", post(handlers::api::auth::revoke))
+        .route("/auth/mfa", post(handlers::api::mfa::verify).route_layer(login_throttle))
+        .route("/auth/mfa/passkey", post(handlers::api::webauthn::start_mfa))
+        .route("/auth/passkey/start", post(handlers::api::webauthn::start_login))
+        .route("/auth/passkey/finish", post(handlers::api::webauthn::finish_login))
+        .route("/auth/oidc", get(handlers::api::oidc::providers))
+        .route("/auth/oidc/:provider", get(handlers::api::oidc::authorize))
+        .route("/auth/oidc/:provider/callback", get(handlers::api::oidc::callback))
+        .merge(mfa_routes)
         
         // API Users
-        .route("/users", get(handlers::api::users::index).post(handlers::api::users::create))
-        .route("/users/:id", get(handlers::api::users::show).put(handlers::api::users::update).delete(handlers::api::users::delete))
-        .route("/users/:id/following", get(handlers::api::users::following))
-        .route("/users/:id/followers", get(handlers::api::users::followers))
+        .route("/users", get(handlers::api::users::index).route_layer(optional_auth.clone()).merge(post(handlers::api::users::create).route_layer(track_signups)))
+        .route("/users/:id", get(handlers::api::users::show).merge(put(handlers::api::users::update).delete(handlers::api::users::delete).route_layer(require_auth.clone())).route_layer(user_conditional).route_layer(revalidate.clone()).route_layer(optional_auth.clone()))
+        .merge(private_profile_routes)
         
         // API Microposts
-        .route("/microposts", get(handlers::api::microposts::index).post(handlers::api::microposts::create))
-        .route("/microposts/:id", get(handlers::api::microposts::show).put(handlers::api::microposts::update).delete(handlers::api::microposts::delete))
-        .route("/feed", get(handlers::api::microposts::feed))
+        .route("/microposts", get(handlers::api::microposts::index).route_layer(optional_auth.clone()).merge(post(handlers::api::microposts::create).route_layer(micropost_limit).route_layer(track_microposts).route_layer(require_auth.clone())))
+        .merge(private_micropost_routes)
+        .route("/feed", get(handlers::api::microposts::feed).route_layer(feed_conditional).route_layer(revalidate).route_layer(optional_auth))
         
         // API Relationships
-        .route("/relationships", post(handlers::api::relationships::create))
-        .route("/relationships/:id", delete(handlers::api::relationships::delete))
+        .route("/relationships", post(handlers::api::relationships::create).route_layer(relationship_limit).route_layer(track_follows).route_layer(require_auth.clone()))
+        .route("/relationships/:id", delete(handlers::api::relationships::delete).route_layer(require_auth))
+        
+        // API Follow requests
+        .merge(follow_request_routes)
+        
+        // API Direct messages
+        .merge(message_routes)
+        
+        // API Admin
+        .merge(admin_routes)
         
         // API Account activation
         .route("/account_activations", post(handlers::api::auth::resend_activation))
@@ -149,4 +345,7 @@ fn create_api_routes() -> Router<AppState> {
         // API Password reset
         .route("/password_resets", post(handlers::api::auth::send_password_reset))
         .route("/password_resets/:token", post(handlers::api::auth::reset_password))
+        
+        .layer(http_cache::cache_control(http_cache::NO_STORE))
+        .layer(api_limit)
 }
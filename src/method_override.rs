@@ -0,0 +1,100 @@
+use axum::{
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use crate::{
+    app::AppState,
+    auth::is_api_request,
+    error::AppError,
+    forms::{buffer_body, content_type, form_field},
+};
+
+pub const METHOD_FIELD: &str = "_method";
+
+/// Lets HTML forms reach PATCH, PUT and DELETE routes by posting a `_method`
+/// field, as Rails does. Must run before routing: layers added with
+/// `Router::layer` run after it, too late to change the method.
+pub async fn method_override(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if request.method() != Method::POST || is_api_request(&request) {
+        return Ok(next.run(request).await);
+    }
+    
+    let content_type = content_type(&request);
+    if !content_type.starts_with("application/x-www-form-urlencoded") && !content_type.starts_with("multipart/form-data") {
+        return Ok(next.run(request).await);
+    }
+    
+    let (mut request, bytes) = buffer_body(request, state.config.storage.max_upload_bytes).await?;
+    let overridden = form_field(&content_type, &bytes, METHOD_FIELD)
+        .and_then(|method| match method.to_ascii_uppercase().as_str() {
+            "PATCH" => Some(Method::PATCH),
+            "PUT" => Some(Method::PUT),
+            "DELETE" => Some(Method::DELETE),
+            _ => None,
+        });
+    if let Some(method) = overridden {
+        *request.method_mut() = method;
+    }
+    
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use sqlx::PgPool;
+    use crate::{
+        app::AppState,
+        database::microposts::MicropostRepository,
+        models::CreateMicropostRequest,
+        test_support::*,
+    };
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn forms_reach_delete_routes(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let req = CreateMicropostRequest { content: "Hello".to_string(), picture: None };
+        let micropost = MicropostRepository::create(&pool, alice.id, &req).await.unwrap();
+        let uri = format!("/microposts/{}", micropost.id);
+        
+        let request = form_post(&app, &uri, &session_cookie(alice.id)).await.body(Body::from("_method=delete")).unwrap();
+        let response = send(&app, request).await;
+        
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert!(MicropostRepository::find_by_id(&pool, micropost.id).await.unwrap().is_none());
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn rejections_pass_through_the_outer_layers(pool: PgPool) {
+        let mut config = config();
+        config.storage.max_upload_bytes = 1024;
+        let app = crate::create_app(AppState::new(pool.clone(), config).unwrap());
+        let alice = create_user(&pool, "Alice", false).await;
+        let session = session_cookie(alice.id);
+        
+        // The CSRF token travels in the header, so only the override buffers the form
+        let oversized = format!("_method=delete&padding={}", "a".repeat(2048));
+        let request = form_post(&app, "/microposts/00000000-0000-0000-0000-000000000000", &session).await.body(Body::from(oversized)).unwrap();
+        let response = send(&app, request).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(response.headers().contains_key("x-request-id"));
+        
+        // An override doesn't get around the CSRF check
+        let request = Request::post("/microposts/00000000-0000-0000-0000-000000000000")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::COOKIE, session)
+            .body(Body::from("_method=delete"))
+            .unwrap();
+        assert_eq!(send(&app, request).await.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,12 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Like {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub micropost_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct MfaRecoveryCode {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub code_digest: String,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct MfaCodeRequest {
+    #[validate(length(equal = 6))]
+    #[schema(min_length = 6, max_length = 6)]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MfaLoginRequest {
+    pub mfa_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MfaEnrollmentResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+    pub qr_code_url: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MfaRecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+/// The second factors an account still has to present at login.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MfaMethodsResponse {
+    pub methods: Vec<String>,
+}
+
+pub const MFA_METHOD_TOTP: &str = "totp";
+pub const MFA_METHOD_PASSKEY: &str = "passkey";
+
+/// Returned by login instead of `AuthResponse` when the account has a TOTP
+/// authenticator or a passkey. `methods` lists which of the two can answer it.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MfaChallengeResponse {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+    pub methods: Vec<String>,
+}
+
+/// Outcome of any first-factor login: either tokens, or a pending MFA challenge.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(crate::models::AuthResponse),
+    MfaRequired(MfaChallengeResponse),
+}
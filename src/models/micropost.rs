@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::ToSchema;
 use uuid::Uuid;
 use validator::Validate;
 
@@ -14,21 +15,23 @@ pub struct Micropost {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct CreateMicropostRequest {
     #[validate(length(min = 1, max = 140))]
+    #[schema(min_length = 1, max_length = 140)]
     pub content: String,
     pub picture: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct UpdateMicropostRequest {
     #[validate(length(min = 1, max = 140))]
+    #[schema(min_length = 1, max_length = 140)]
     pub content: Option<String>,
     pub picture: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct MicropostResponse {
     pub id: Uuid,
     pub content: String,
@@ -37,7 +40,7 @@ pub struct MicropostResponse {
     pub user: MicropostUserResponse,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct MicropostUserResponse {
     pub id: Uuid,
     pub name: String,
@@ -1,7 +1,17 @@
 pub mod user;
 pub mod micropost;
 pub mod relationship;
+pub mod message;
+pub mod mfa;
+pub mod webauthn;
+pub mod identity;
+pub mod like;
 
 pub use user::*;
 pub use micropost::*;
 pub use relationship::*;
+pub use message::*;
+pub use mfa::*;
+pub use webauthn::*;
+pub use identity::*;
+pub use like::*;
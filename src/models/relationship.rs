@@ -1,35 +1,71 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+pub const RELATIONSHIP_PENDING: &str = "pending";
+pub const RELATIONSHIP_ACCEPTED: &str = "accepted";
+
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct Relationship {
     pub id: Uuid,
     pub follower_id: Uuid,
     pub followed_id: Uuid,
     pub created_at: DateTime<Utc>,
+    pub status: String, // "pending" or "accepted"
+    pub accepted_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateRelationshipRequest {
     pub followed_id: Uuid,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct RelationshipResponse {
     pub id: Uuid,
     pub follower_id: Uuid,
     pub followed_id: Uuid,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FollowRequestResponse {
+    pub id: Uuid,
+    pub follower: FollowRequestUserResponse,
     pub created_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FollowRequestUserResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub gravatar_url: String,
+}
+
 impl Relationship {
     pub fn to_response(&self) -> RelationshipResponse {
         RelationshipResponse {
             id: self.id,
             follower_id: self.follower_id,
             followed_id: self.followed_id,
+            status: self.status.clone(),
+            created_at: self.created_at,
+            accepted_at: self.accepted_at,
+        }
+    }
+    
+    pub fn to_follow_request_response(&self, follower: &crate::models::User) -> FollowRequestResponse {
+        FollowRequestResponse {
+            id: self.id,
+            follower: FollowRequestUserResponse {
+                id: follower.id,
+                name: follower.name.clone(),
+                gravatar_url: follower.gravatar_url(50),
+            },
             created_at: self.created_at,
         }
     }
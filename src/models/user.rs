@@ -1,8 +1,10 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::ToSchema;
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidateUrl, ValidationError};
+use crate::versioning::ApiVersion;
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct User {
@@ -16,56 +18,233 @@ pub struct User {
     pub activation_digest: Option<String>,
     pub reset_digest: Option<String>,
     pub reset_sent_at: Option<DateTime<Utc>>,
+    pub is_private: bool,
+    pub mfa_enabled: bool,
+    pub mfa_secret: Option<String>,
+    pub mfa_enabled_at: Option<DateTime<Utc>>,
+    pub mfa_last_used_step: Option<i64>,
+    pub failed_login_attempts: i32,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub username: Option<String>,
+    pub bio: Option<String>,
+    pub location: Option<String>,
+    pub website: Option<String>,
+    pub banner: Option<String>,
+    pub tokens_valid_after: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct CreateUserRequest {
     #[validate(length(min = 1, max = 50))]
+    #[schema(min_length = 1, max_length = 50)]
     pub name: String,
     #[validate(email)]
+    #[schema(format = "email")]
     pub email: String,
     #[validate(length(min = 6))]
+    #[schema(format = Password, min_length = 6)]
     pub password: String,
+    #[validate(must_match(other = "password"))]
     pub password_confirmation: String,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct UpdateUserRequest {
     #[validate(length(min = 1, max = 50))]
+    #[schema(min_length = 1, max_length = 50)]
     pub name: Option<String>,
     #[validate(email)]
+    #[schema(format = "email")]
     pub email: Option<String>,
     #[validate(length(min = 6))]
+    #[schema(format = Password, min_length = 6)]
     pub password: Option<String>,
     pub password_confirmation: Option<String>,
+    /// Profile handle; the previous one keeps redirecting after a rename or
+    /// after it's cleared.
+    #[validate(custom(function = "validate_username"))]
+    #[schema(min_length = 3, max_length = 30, pattern = "^[A-Za-z0-9_]{3,30}$")]
+    pub username: Option<String>,
+    /// Blank profile fields are cleared.
+    #[validate(length(max = 160))]
+    #[schema(max_length = 160)]
+    pub bio: Option<String>,
+    #[validate(length(max = 100))]
+    #[schema(max_length = 100)]
+    pub location: Option<String>,
+    #[validate(length(max = 255), custom(function = "validate_http_url"))]
+    #[schema(format = "uri", max_length = 255)]
+    pub website: Option<String>,
+    #[validate(length(max = 255), custom(function = "validate_http_url"))]
+    #[schema(format = "uri", max_length = 255)]
+    pub banner: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Handles that would shadow a route or pass for the site itself.
+pub const RESERVED_USERNAMES: &[&str] = &[
+    "about", "account", "admin", "administrator", "api", "assets", "contact", "edit", "feed",
+    "followers", "following", "graphql", "help", "home", "login", "logout", "me", "microposts",
+    "new", "null", "password_resets", "relationships", "root", "settings", "signup", "static",
+    "support", "system", "undefined", "uploads", "users",
+];
+
+pub fn is_reserved_username(username: &str) -> bool {
+    RESERVED_USERNAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(username))
+}
+
+/// Blank clears the handle.
+fn validate_username(username: &str) -> Result<(), ValidationError> {
+    if username.trim().is_empty() {
+        return Ok(());
+    }
+    let well_formed = (3..=30).contains(&username.len())
+        && username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !well_formed {
+        return Err(ValidationError::new("username_format")
+            .with_message("must be 3 to 30 letters, digits or underscores".into()));
+    }
+    if is_reserved_username(username) {
+        return Err(ValidationError::new("username_reserved").with_message("is reserved".into()));
+    }
+    Ok(())
+}
+
+/// Empty clears the field; anything else must be an absolute http(s) URL.
+fn validate_http_url(value: &str) -> Result<(), ValidationError> {
+    let value = value.trim();
+    if value.is_empty() || ((value.starts_with("https://") || value.starts_with("http://")) && value.validate_url()) {
+        return Ok(());
+    }
+    Err(ValidationError::new("url").with_message("must be an http(s) URL".into()))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdatePrivacyRequest {
+    pub is_private: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct LoginRequest {
     pub email: String,
     pub password: String,
     pub remember_me: Option<bool>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
+}
+
+/// Body for resending an activation email or requesting a password reset.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct EmailRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ResetPasswordRequest {
+    #[validate(length(min = 6))]
+    #[schema(format = Password, min_length = 6)]
+    pub password: String,
+    #[validate(must_match(other = "password"))]
+    pub password_confirmation: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct UserResponse {
     pub id: Uuid,
     pub name: String,
     pub email: String,
     pub admin: bool,
     pub activated: bool,
+    pub is_private: bool,
+    pub mfa_enabled: bool,
     pub created_at: DateTime<Utc>,
     pub gravatar_url: String,
     pub microposts_count: i64,
     pub following_count: i64,
     pub followers_count: i64,
+    /// The v1 shape is frozen, so profile fields only appear from v2 on.
+    #[serde(skip)]
+    pub profile: UserProfile,
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct UserProfile {
+    pub username: Option<String>,
+    pub bio: Option<String>,
+    pub location: Option<String>,
+    pub website: Option<String>,
+    pub banner: Option<String>,
+}
+
+/// v2 user shape: `email` is only present for the account owner and admins.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserResponseV2 {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    pub admin: bool,
+    pub activated: bool,
+    pub is_private: bool,
+    pub mfa_enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub gravatar_url: String,
+    pub microposts_count: i64,
+    pub following_count: i64,
+    pub followers_count: i64,
+    #[serde(flatten)]
+    pub profile: UserProfile,
 }
 
 #[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum VersionedUserResponse {
+    V1(UserResponse),
+    V2(UserResponseV2),
+}
+
+impl UserResponse {
+    /// Maps the v1 DTO to the shape of the negotiated API version, as seen by `viewer`.
+    pub fn for_version(self, version: ApiVersion, viewer: Option<&User>) -> VersionedUserResponse {
+        match version {
+            ApiVersion::V1 => VersionedUserResponse::V1(self),
+            ApiVersion::V2 => {
+                let show_email = shows_email(self.id, viewer);
+                VersionedUserResponse::V2(UserResponseV2 {
+                    id: self.id,
+                    name: self.name,
+                    email: show_email.then_some(self.email),
+                    admin: self.admin,
+                    activated: self.activated,
+                    is_private: self.is_private,
+                    mfa_enabled: self.mfa_enabled,
+                    created_at: self.created_at,
+                    gravatar_url: self.gravatar_url,
+                    microposts_count: self.microposts_count,
+                    following_count: self.following_count,
+                    followers_count: self.followers_count,
+                    profile: self.profile,
+                })
+            }
+        }
+    }
+}
+
+/// Whether v2 shows `user_id`'s email to `viewer`: only the owner and admins see it.
+pub fn shows_email(user_id: Uuid, viewer: Option<&User>) -> bool {
+    viewer.map_or(false, |viewer| viewer.id == user_id || viewer.admin)
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct AuthResponse {
-    pub user: UserResponse,
+    /// Shaped for the negotiated version; v2 documents swap in `UserResponseV2`.
+    #[schema(value_type = UserResponse)]
+    pub user: VersionedUserResponse,
     pub token: String,
     pub refresh_token: String,
 }
@@ -76,6 +255,19 @@ impl User {
         format!("https://www.gravatar.com/avatar/{:x}?s={}&d=identicon", hash, size)
     }
     
+    /// Whether a token issued at `issued_at` (a JWT `iat`) predates a revocation.
+    pub fn tokens_revoked(&self, issued_at: i64) -> bool {
+        self.tokens_valid_after.map_or(false, |after| issued_at < after.timestamp())
+    }
+    
+    /// Canonical profile path: the handle when there is one, else the id.
+    pub fn profile_path(&self) -> String {
+        match &self.username {
+            Some(username) => format!("/users/{}", username),
+            None => format!("/users/{}", self.id),
+        }
+    }
+    
     pub fn to_response(&self) -> UserResponse {
         UserResponse {
             id: self.id,
@@ -83,11 +275,57 @@ impl User {
             email: self.email.clone(),
             admin: self.admin,
             activated: self.activated,
+            is_private: self.is_private,
+            mfa_enabled: self.mfa_enabled,
             created_at: self.created_at,
             gravatar_url: self.gravatar_url(80),
             microposts_count: 0, // Will be populated by service
             following_count: 0,  // Will be populated by service
             followers_count: 0,  // Will be populated by service
+            profile: UserProfile {
+                username: self.username.clone(),
+                bio: self.bio.clone(),
+                location: self.location.clone(),
+                website: self.website.clone(),
+                banner: self.banner.clone(),
+            },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn profile_update(username: Option<&str>, website: Option<&str>) -> UpdateUserRequest {
+        UpdateUserRequest {
+            name: None,
+            email: None,
+            password: None,
+            password_confirmation: None,
+            username: username.map(str::to_string),
+            bio: None,
+            location: None,
+            website: website.map(str::to_string),
+            banner: None,
+        }
+    }
+    
+    #[test]
+    fn usernames_must_be_well_formed_and_unreserved() {
+        assert!(profile_update(Some("jane_doe42"), None).validate().is_ok());
+        assert!(profile_update(Some(""), None).validate().is_ok());
+        for invalid in ["jd", "jane.doe", "jane doe", "ADMIN", "Users", &"x".repeat(31)] {
+            let errors = profile_update(Some(invalid), None).validate().unwrap_err();
+            assert!(errors.field_errors().contains_key("username"), "accepted {:?}", invalid);
+        }
+    }
+    
+    #[test]
+    fn websites_must_be_http_urls_or_blank() {
+        assert!(profile_update(None, Some("https://example.com/me")).validate().is_ok());
+        assert!(profile_update(None, Some("")).validate().is_ok());
+        assert!(profile_update(None, Some("javascript:alert(1)")).validate().is_err());
+        assert!(profile_update(None, Some("example.com")).validate().is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse,
+};
+
+pub const CHALLENGE_REGISTRATION: &str = "registration";
+pub const CHALLENGE_AUTHENTICATION: &str = "authentication";
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct WebauthnCredential {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub credential_id: String,
+    pub name: String,
+    pub passkey: serde_json::Value,
+    pub sign_count: i64,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct WebauthnChallenge {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: String, // "registration" or "authentication"
+    pub state: serde_json::Value,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct FinishPasskeyRegistrationRequest {
+    pub challenge_id: Uuid,
+    #[validate(length(min = 1, max = 100))]
+    #[schema(min_length = 1, max_length = 100)]
+    pub name: String,
+    #[schema(value_type = Object)]
+    pub credential: RegisterPublicKeyCredential,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct StartPasskeyLoginRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct StartPasskeyMfaRequest {
+    pub mfa_token: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FinishPasskeyLoginRequest {
+    pub challenge_id: Uuid,
+    #[schema(value_type = Object)]
+    pub credential: PublicKeyCredential,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PasskeyRegistrationChallengeResponse {
+    pub challenge_id: Uuid,
+    #[schema(value_type = Object)]
+    pub options: CreationChallengeResponse,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PasskeyLoginChallengeResponse {
+    pub challenge_id: Uuid,
+    #[schema(value_type = Object)]
+    pub options: RequestChallengeResponse,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebauthnCredentialResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WebauthnCredential {
+    pub fn to_response(&self) -> WebauthnCredentialResponse {
+        WebauthnCredentialResponse {
+            id: self.id,
+            name: self.name.clone(),
+            last_used_at: self.last_used_at,
+            created_at: self.created_at,
+        }
+    }
+}
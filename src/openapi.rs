@@ -0,0 +1,313 @@
+use serde_json::Value;
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+use utoipa_swagger_ui::SwaggerUi;
+use crate::{handlers, models, versioning::ApiVersion};
+
+pub const DOCS_PATH: &str = "/api/v1/docs";
+
+/// Schemas a later version replaced, as `(version, v1 name, replacement)`.
+const REPLACED_SCHEMAS: &[(ApiVersion, &str, &str)] = &[(ApiVersion::V2, "UserResponse", "UserResponseV2")];
+
+/// The `/api/v1` contract. Every route in `create_api_routes` needs an entry
+/// under `paths`; the tests below fail when one is missing or the document changes.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Sample App API", version = "1"),
+    paths(
+        handlers::api::auth::login,
+        handlers::api::auth::signup,
+        handlers::api::auth::refresh,
+        handlers::api::auth::revoke,
+        handlers::api::mfa::verify,
+        handlers::api::webauthn::start_mfa,
+        handlers::api::webauthn::start_login,
+        handlers::api::webauthn::finish_login,
+        handlers::api::oidc::providers,
+        handlers::api::oidc::authorize,
+        handlers::api::oidc::callback,
+        handlers::api::mfa::disable,
+        handlers::api::mfa::enroll,
+        handlers::api::mfa::qr_code,
+        handlers::api::mfa::confirm,
+        handlers::api::webauthn::index,
+        handlers::api::webauthn::delete,
+        handlers::api::webauthn::start_registration,
+        handlers::api::webauthn::finish_registration,
+        handlers::api::users::index,
+        handlers::api::users::create,
+        handlers::api::users::show,
+        handlers::api::users::update,
+        handlers::api::users::delete,
+        handlers::api::users::following,
+        handlers::api::users::followers,
+        handlers::api::microposts::index,
+        handlers::api::microposts::create,
+        handlers::api::microposts::show,
+        handlers::api::microposts::update,
+        handlers::api::microposts::delete,
+        handlers::api::microposts::feed,
+        handlers::api::relationships::create,
+        handlers::api::relationships::delete,
+        handlers::api::follow_requests::index,
+        handlers::api::follow_requests::deny,
+        handlers::api::follow_requests::approve,
+        handlers::api::follow_requests::update_privacy,
+        handlers::api::messages::index,
+        handlers::api::messages::create,
+        handlers::api::messages::messages,
+        handlers::api::messages::create_message,
+        handlers::api::messages::mark_read,
+        handlers::api::blocks::create,
+        handlers::api::blocks::delete,
+        handlers::api::mfa::admin_reset,
+        handlers::api::auth::resend_activation,
+        handlers::api::auth::activate_account,
+        handlers::api::auth::send_password_reset,
+        handlers::api::auth::reset_password,
+    ),
+    components(schemas(
+        models::CreateUserRequest,
+        models::UpdateUserRequest,
+        models::UpdatePrivacyRequest,
+        models::LoginRequest,
+        models::UserResponse,
+        models::UserResponseV2,
+        models::UserProfile,
+        models::AuthResponse,
+        models::CreateMicropostRequest,
+        models::UpdateMicropostRequest,
+        models::MicropostResponse,
+        models::MicropostUserResponse,
+        models::CreateRelationshipRequest,
+        models::RelationshipResponse,
+        models::FollowRequestResponse,
+        models::FollowRequestUserResponse,
+        models::CreateConversationRequest,
+        models::CreateMessageRequest,
+        models::CreateBlockRequest,
+        models::MessageResponse,
+        models::ConversationResponse,
+        models::ParticipantResponse,
+        models::MfaCodeRequest,
+        models::MfaLoginRequest,
+        models::MfaEnrollmentResponse,
+        models::MfaRecoveryCodesResponse,
+        models::MfaChallengeResponse,
+        models::MfaMethodsResponse,
+        models::LoginResponse,
+        models::FinishPasskeyRegistrationRequest,
+        models::StartPasskeyLoginRequest,
+        models::StartPasskeyMfaRequest,
+        models::FinishPasskeyLoginRequest,
+        models::PasskeyRegistrationChallengeResponse,
+        models::PasskeyLoginChallengeResponse,
+        models::WebauthnCredentialResponse,
+        models::OidcProviderResponse,
+    )),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "auth", description = "Tokens, second factors and external identity providers"),
+        (name = "users"),
+        (name = "microposts"),
+        (name = "relationships", description = "Follows and follow requests"),
+        (name = "messages", description = "Direct messages and blocks"),
+        (name = "mfa", description = "TOTP and passkey management"),
+        (name = "admin"),
+    ),
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+        );
+    }
+}
+
+/// Where `version`'s document is served.
+pub fn spec_path(version: ApiVersion) -> &'static str {
+    match version {
+        ApiVersion::V1 => "/api/v1/openapi.json",
+        ApiVersion::V2 => "/api/v2/openapi.json",
+    }
+}
+
+/// The contract for `version`. Every version mounts the same handlers, which are
+/// annotated with their `/api/v1` paths, so later documents move the paths under
+/// their own prefix and point at the schemas that version replaced.
+pub fn spec(version: ApiVersion) -> Value {
+    let mut document = serde_json::to_value(ApiDoc::openapi()).expect("OpenAPI documents serialize");
+    if version == ApiVersion::V1 {
+        return document;
+    }
+    
+    document["info"]["version"] = Value::from(version.number().to_string());
+    if let Some(paths) = document["paths"].as_object_mut() {
+        *paths = std::mem::take(paths)
+            .into_iter()
+            .map(|(path, item)| (path.replacen(ApiVersion::V1.prefix(), version.prefix(), 1), item))
+            .collect();
+    }
+    
+    let replaced: Vec<(&str, &str)> = REPLACED_SCHEMAS
+        .iter()
+        .filter(|(since, _, _)| *since <= version)
+        .map(|&(_, old, new)| (old, new))
+        .collect();
+    if let Some(schemas) = document["components"]["schemas"].as_object_mut() {
+        for (old, _) in &replaced {
+            schemas.remove(*old);
+        }
+    }
+    replace_refs(&mut document, &replaced);
+    
+    document
+}
+
+fn replace_refs(value: &mut Value, replaced: &[(&str, &str)]) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match value {
+                    Value::String(reference) if key == "$ref" => {
+                        let name = reference.trim_start_matches("#/components/schemas/");
+                        if let Some((_, new)) = replaced.iter().find(|(old, _)| *old == name) {
+                            *reference = format!("#/components/schemas/{}", new);
+                        }
+                    }
+                    _ => replace_refs(value, replaced),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| replace_refs(item, replaced)),
+        _ => {}
+    }
+}
+
+/// Serves each version's spec and a bundled Swagger UI listing them.
+pub fn routes() -> SwaggerUi {
+    ApiVersion::ALL
+        .into_iter()
+        .fold(SwaggerUi::new(DOCS_PATH), |ui, version| ui.external_url_unchecked(spec_path(version), spec(version)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use super::*;
+    
+    fn snapshot(version: ApiVersion) -> String {
+        match version {
+            ApiVersion::V1 => concat!(env!("CARGO_MANIFEST_DIR"), "/docs/openapi.json").to_string(),
+            version => format!("{}/docs/openapi.v{}.json", env!("CARGO_MANIFEST_DIR"), version.number()),
+        }
+    }
+    
+    /// Regenerate with `UPDATE_OPENAPI=1 cargo test openapi` and commit the diff
+    /// together with the API change, so client generators see it in review.
+    #[test]
+    fn spec_matches_snapshot() {
+        for version in ApiVersion::ALL {
+            let generated = serde_json::to_string_pretty(&spec(version)).unwrap() + "\n";
+            
+            if std::env::var_os("UPDATE_OPENAPI").is_some() {
+                std::fs::write(snapshot(version), &generated).unwrap();
+                continue;
+            }
+            
+            let committed = std::fs::read_to_string(snapshot(version)).expect("the snapshot is committed under docs/");
+            assert!(
+                committed == generated,
+                "{} is out of date; run `UPDATE_OPENAPI=1 cargo test openapi` and commit the result",
+                snapshot(version)
+            );
+        }
+    }
+    
+    /// `(method, path)` for every route in `create_api_routes`, read from its
+    /// source since a `Router` can't be listed.
+    fn mounted_routes() -> BTreeSet<(String, String)> {
+        let source = include_str!("main.rs");
+        let start = source.find("fn create_api_routes").unwrap();
+        let body = &source[start..start + source[start..].find("\n}\n").unwrap()];
+        
+        let mut routes = BTreeSet::new();
+        for route in body.split(".route(\"").skip(1) {
+            let (path, rest) = route.split_once('"').unwrap();
+            let handlers = rest.lines().next().unwrap_or_default();
+            let path: Vec<String> = path
+                .split('/')
+                .map(|segment| match segment.strip_prefix(':') {
+                    Some(param) => format!("{{{}}}", param),
+                    None => segment.to_string(),
+                })
+                .collect();
+            
+            for method in ["get", "post", "put", "patch", "delete"] {
+                let call = format!("{}(", method);
+                let mounted = handlers
+                    .match_indices(&call)
+                    .any(|(i, _)| !handlers[..i].ends_with(|c: char| c.is_alphanumeric() || c == '_' || c == ':'));
+                if mounted {
+                    routes.insert((method.to_string(), path.join("/")));
+                }
+            }
+        }
+        routes
+    }
+    
+    #[test]
+    fn every_api_route_is_documented() {
+        let mounted = mounted_routes();
+        assert!(!mounted.is_empty());
+        
+        for version in ApiVersion::ALL {
+            let document = spec(version);
+            let documented: BTreeSet<(String, String)> = document["paths"]
+                .as_object()
+                .unwrap()
+                .iter()
+                .flat_map(|(path, item)| {
+                    let path = path.strip_prefix(version.prefix()).unwrap().to_string();
+                    item.as_object().unwrap().keys().map(move |method| (method.clone(), path.clone()))
+                })
+                .collect();
+            
+            let undocumented: Vec<_> = mounted.difference(&documented).collect();
+            let unmounted: Vec<_> = documented.difference(&mounted).collect();
+            assert!(undocumented.is_empty(), "routes missing from the {} spec: {:?}", version.prefix(), undocumented);
+            assert!(unmounted.is_empty(), "{} spec documents routes that aren't mounted: {:?}", version.prefix(), unmounted);
+        }
+    }
+    
+    #[test]
+    fn v2_documents_its_own_user_shape() {
+        let document = spec(ApiVersion::V2);
+        let schemas = &document["components"]["schemas"];
+        
+        assert_eq!(document["info"]["version"], "2");
+        assert!(schemas.get("UserResponse").is_none());
+        assert_eq!(schemas["AuthResponse"]["properties"]["user"]["$ref"], "#/components/schemas/UserResponseV2");
+        assert!(document["paths"].as_object().unwrap().keys().all(|path| path.starts_with("/api/v2/")));
+    }
+    
+    #[test]
+    fn validator_limits_are_documented() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let content = &spec["components"]["schemas"]["CreateMicropostRequest"]["properties"]["content"];
+        
+        assert_eq!(content["minLength"], 1);
+        assert_eq!(content["maxLength"], 140);
+        
+        let participants = &spec["components"]["schemas"]["CreateConversationRequest"]["properties"]["participant_ids"];
+        assert_eq!(participants["maxItems"], crate::models::MAX_CONVERSATION_PARTICIPANTS - 1);
+    }
+}
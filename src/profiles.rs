@@ -0,0 +1,100 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use uuid::Uuid;
+use crate::{
+    app::AppState,
+    database::users::UserRepository,
+    error::AppError,
+    models::is_reserved_username,
+    versioning::ApiVersion,
+};
+
+/// Marks a request for a handle nobody owns, so `reject_unknown_handles` can
+/// answer with the usual rendered 404 once routing has happened.
+#[derive(Debug, Clone, Copy)]
+struct UnknownHandle;
+
+/// Lets `/users/:handle/...` (HTML and every API version) address a profile by
+/// username as well as UUID. Runs before routing: current handles are rewritten
+/// to the owner's id so handlers keep taking `Path<Uuid>`, and retired handles
+/// redirect to the owner's current profile URL.
+pub async fn resolve_handles(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some((prefix, handle, rest)) = split_profile_path(request.uri().path()) else {
+        return Ok(next.run(request).await);
+    };
+    if Uuid::parse_str(handle).is_ok() || !looks_like_handle(handle) || is_reserved_username(handle) {
+        return Ok(next.run(request).await);
+    }
+    let (prefix, handle, rest) = (prefix.to_string(), handle.to_string(), rest.to_string());
+    let query = request.uri().query().map(|query| format!("?{}", query)).unwrap_or_default();
+    
+    if let Some(user) = UserRepository::find_by_username(&state.db, &handle).await? {
+        *request.uri_mut() = format!("{}{}{}{}", prefix, user.id, rest, query)
+            .parse::<Uri>()
+            .map_err(|_| AppError::BadRequest("Invalid request path".to_string()))?;
+        return Ok(next.run(request).await);
+    }
+    
+    if let Some(user) = UserRepository::find_by_previous_username(&state.db, &handle).await? {
+        let current = user.username.unwrap_or_else(|| user.id.to_string());
+        return Ok(redirect(request.method(), &format!("{}{}{}{}", prefix, current, rest, query)));
+    }
+    
+    request.extensions_mut().insert(UnknownHandle);
+    Ok(next.run(request).await)
+}
+
+/// Turns requests flagged by `resolve_handles` into a 404 instead of a
+/// `Path<Uuid>` rejection.
+pub async fn reject_unknown_handles(request: Request, next: Next) -> Result<Response, AppError> {
+    if request.extensions().get::<UnknownHandle>().is_some() {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+    Ok(next.run(request).await)
+}
+
+/// Splits `/users/bob/followers` into `("/users/", "bob", "/followers")`,
+/// versioned API paths included.
+fn split_profile_path(path: &str) -> Option<(&str, &str, &str)> {
+    let prefix_len = if path.starts_with("/users/") {
+        "/users/".len()
+    } else {
+        let version = ApiVersion::ALL
+            .into_iter()
+            .find(|v| path.strip_prefix(v.prefix()).map_or(false, |rest| rest.starts_with("/users/")))?;
+        version.prefix().len() + "/users/".len()
+    };
+    
+    let (prefix, remainder) = path.split_at(prefix_len);
+    let end = remainder.find('/').unwrap_or(remainder.len());
+    let (handle, rest) = remainder.split_at(end);
+    (!handle.is_empty()).then_some((prefix, handle, rest))
+}
+
+fn looks_like_handle(segment: &str) -> bool {
+    segment.len() <= 30 && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Temporary, since someone else may claim the old handle later and a
+/// cached permanent redirect would then point at the wrong profile; 307
+/// keeps the method and body for writes.
+fn redirect(method: &Method, location: &str) -> Response {
+    let status = if method == Method::GET || method == Method::HEAD {
+        StatusCode::FOUND
+    } else {
+        StatusCode::TEMPORARY_REDIRECT
+    };
+    
+    match HeaderValue::from_str(location) {
+        Ok(location) => (status, [(header::LOCATION, location)]).into_response(),
+        Err(_) => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
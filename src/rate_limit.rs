@@ -0,0 +1,358 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Instant,
+};
+use axum::{
+    extract::Request,
+    http::{header::AUTHORIZATION, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use sqlx::PgPool;
+use tower::{Layer, Service};
+use uuid::Uuid;
+use crate::{
+    app::AppState,
+    auth::{client_ip, read_cookie, JwtService, SESSION_COOKIE},
+    config::{RateLimitBackendKind, RateLimitQuota},
+    database::rate_limits::RateLimitRepository,
+    error::AppError,
+    models::User,
+};
+
+/// Outcome of taking a token from a bucket.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset_secs: u64,
+    /// Seconds until the next token, when refused.
+    pub retry_after_secs: u64,
+}
+
+impl RateLimitDecision {
+    fn new(quota: RateLimitQuota, allowed: bool, tokens: f64) -> Self {
+        let rate = quota.refill_per_sec();
+        let missing = (f64::from(quota.burst) - tokens).max(0.0);
+        
+        Self {
+            allowed,
+            limit: quota.burst,
+            remaining: tokens.floor().max(0.0) as u32,
+            reset_secs: (missing / rate).ceil() as u64,
+            retry_after_secs: ((1.0 - tokens).max(0.0) / rate).ceil().max(1.0) as u64,
+        }
+    }
+    
+    fn apply_headers(&self, headers: &mut HeaderMap) {
+        headers.insert("ratelimit-limit", HeaderValue::from(self.limit));
+        headers.insert("ratelimit-remaining", HeaderValue::from(self.remaining));
+        headers.insert("ratelimit-reset", HeaderValue::from(self.reset_secs));
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    /// Idle this long, the bucket is full again and can be dropped.
+    period_secs: u64,
+}
+
+/// Token bucket store shared by every `RateLimitLayer`. Per-instance buckets
+/// live in memory; shared buckets go to Postgres when that backend is configured,
+/// so every instance enforces the same quota at the cost of a query per request.
+pub struct RateLimiter {
+    local: Mutex<HashMap<String, Bucket>>,
+    shared: Option<PgPool>,
+    calls: AtomicU64,
+}
+
+impl RateLimiter {
+    pub fn new(kind: RateLimitBackendKind, db: PgPool) -> Self {
+        Self {
+            local: Mutex::new(HashMap::new()),
+            shared: (kind == RateLimitBackendKind::Postgres).then_some(db),
+            calls: AtomicU64::new(0),
+        }
+    }
+    
+    pub async fn take(&self, key: &str, quota: RateLimitQuota, shared: bool) -> Result<RateLimitDecision, AppError> {
+        let capacity = f64::from(quota.burst);
+        let rate = quota.refill_per_sec();
+        let prune = self.calls.fetch_add(1, Ordering::Relaxed) % 1000 == 0;
+        
+        if let Some(pool) = self.shared.as_ref().filter(|_| shared) {
+            if prune {
+                RateLimitRepository::prune(pool).await?;
+            }
+            
+            let period_secs = i64::try_from(quota.period_secs).unwrap_or(i64::MAX);
+            let (allowed, tokens) = RateLimitRepository::take(pool, key, capacity, rate, period_secs).await?;
+            return Ok(RateLimitDecision::new(quota, allowed, tokens));
+        }
+        
+        let now = Instant::now();
+        let mut buckets = self.local.lock().unwrap();
+        
+        if prune {
+            buckets.retain(|_, b| now.duration_since(b.updated_at).as_secs() < b.period_secs);
+        }
+        
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+            period_secs: quota.period_secs,
+        });
+        bucket.period_secs = quota.period_secs;
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated_at = now;
+        
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        
+        Ok(RateLimitDecision::new(quota, allowed, bucket.tokens))
+    }
+    
+    /// Spends from `user_id`'s bucket in `scope`, the one a `RateLimitLayer` for that
+    /// scope uses, for operations that can't sit behind the layer (GraphQL mutations).
+    pub async fn take_for_user(&self, scope: &str, user_id: Uuid, quota: RateLimitQuota) -> Result<RateLimitDecision, AppError> {
+        self.take(&user_key(scope, user_id), quota, true).await
+    }
+}
+
+fn user_key(scope: &str, user_id: impl std::fmt::Display) -> String {
+    format!("{}:user:{}", scope, user_id)
+}
+
+/// Tower layer applying one quota to a group of routes. Buckets are keyed by scope
+/// plus the user behind a bearer token or session cookie, falling back to the client IP.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    scope: &'static str,
+    quota: RateLimitQuota,
+    shared: bool,
+    limiter: Arc<RateLimiter>,
+    jwt_secret: Arc<str>,
+    client_ip_header: Option<Arc<str>>,
+}
+
+impl RateLimitLayer {
+    /// A quota enforced across instances when the Postgres backend is configured.
+    pub fn new(state: &AppState, scope: &'static str, quota: RateLimitQuota) -> Self {
+        Self::build(state, scope, quota, true)
+    }
+    
+    /// A quota each instance enforces on its own, without touching the database.
+    /// Meant for limits checked on every request, where a shared bucket would
+    /// cost more than it protects.
+    pub fn per_instance(state: &AppState, scope: &'static str, quota: RateLimitQuota) -> Self {
+        Self::build(state, scope, quota, false)
+    }
+    
+    fn build(state: &AppState, scope: &'static str, quota: RateLimitQuota, shared: bool) -> Self {
+        Self {
+            scope,
+            quota,
+            shared,
+            limiter: state.rate_limiter.clone(),
+            jwt_secret: Arc::from(state.config.auth.jwt_secret.as_str()),
+            client_ip_header: state.config.server.client_ip_header.as_deref().map(Arc::from),
+        }
+    }
+    
+    fn key_for(&self, request: &Request) -> String {
+        if let Some(user) = request.extensions().get::<User>() {
+            return user_key(self.scope, user.id);
+        }
+        
+        // Routes without auth middleware can still be keyed by a valid bearer token
+        // or, for browsers, the session cookie
+        let headers = request.headers();
+        let bearer = headers
+            .get(AUTHORIZATION)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .map(|token| (token, "access"));
+        let session = read_cookie(headers, SESSION_COOKIE).map(|token| (token, "session"));
+        let user_id = bearer
+            .or(session)
+            .and_then(|(token, token_type)| {
+                JwtService::new(&self.jwt_secret)
+                    .verify_token(token)
+                    .ok()
+                    .filter(|claims| claims.token_type == token_type)
+            })
+            .map(|claims| claims.sub);
+        
+        match user_id {
+            Some(user_id) => user_key(self.scope, user_id),
+            None => format!("{}:ip:{}", self.scope, client_ip(request, self.client_ip_header.as_deref())),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+    
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    layer: RateLimitLayer,
+}
+
+impl<S> Service<Request> for RateLimitService<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+    
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+    
+    fn call(&mut self, request: Request) -> Self::Future {
+        // Take the service that was driven to readiness, leaving a fresh clone behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+        
+        Box::pin(async move {
+            let key = layer.key_for(&request);
+            
+            let decision = match layer.limiter.take(&key, layer.quota, layer.shared).await {
+                Ok(decision) => decision,
+                Err(e) => {
+                    // Fail open: a rate limiter outage shouldn't take the routes down with it
+                    tracing::error!("Rate limiter unavailable: {}", e);
+                    return inner.call(request).await;
+                }
+            };
+            
+            let mut response = if decision.allowed {
+                inner.call(request).await?
+            } else {
+                AppError::TooManyRequests(decision.retry_after_secs).into_response()
+            };
+            
+            decision.apply_headers(response.headers_mut());
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use sqlx::PgPool;
+    use crate::{
+        app::AppState,
+        config::{RateLimitBackendKind, RateLimitQuota},
+        database::rate_limits::RateLimitRepository,
+        test_support::*,
+    };
+    use super::RateLimiter;
+    
+    const ONE_PER_SECOND: RateLimitQuota = RateLimitQuota { burst: 1, period_secs: 1 };
+    
+    async fn assert_refills(limiter: &RateLimiter, shared: bool) {
+        let first = limiter.take("test:refill", ONE_PER_SECOND, shared).await.unwrap();
+        assert!(first.allowed);
+        assert_eq!(first.remaining, 0);
+        
+        let refused = limiter.take("test:refill", ONE_PER_SECOND, shared).await.unwrap();
+        assert!(!refused.allowed);
+        assert_eq!(refused.retry_after_secs, 1);
+        
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(limiter.take("test:refill", ONE_PER_SECOND, shared).await.unwrap().allowed);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn memory_buckets_refill_over_time(pool: PgPool) {
+        assert_refills(&RateLimiter::new(RateLimitBackendKind::Memory, pool), true).await;
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn postgres_buckets_refill_over_time(pool: PgPool) {
+        let limiter = RateLimiter::new(RateLimitBackendKind::Postgres, pool.clone());
+        assert_refills(&limiter, true).await;
+        
+        let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM rate_limit_buckets").fetch_one(&pool).await.unwrap();
+        assert_eq!(stored, 1);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn idle_buckets_are_pruned_after_their_own_period(pool: PgPool) {
+        RateLimitRepository::take(&pool, "test:short", 1.0, 1.0, 1).await.unwrap();
+        RateLimitRepository::take(&pool, "test:long", 1.0, 1.0 / 86400.0, 86400).await.unwrap();
+        
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(RateLimitRepository::prune(&pool).await.unwrap(), 1);
+        
+        let keys: Vec<String> = sqlx::query_scalar("SELECT key FROM rate_limit_buckets").fetch_all(&pool).await.unwrap();
+        assert_eq!(keys, ["test:long"]);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn per_instance_quotas_skip_the_database(pool: PgPool) {
+        let limiter = RateLimiter::new(RateLimitBackendKind::Postgres, pool.clone());
+        assert_refills(&limiter, false).await;
+        
+        let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM rate_limit_buckets").fetch_one(&pool).await.unwrap();
+        assert_eq!(stored, 0);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn api_responses_carry_ratelimit_headers(pool: PgPool) {
+        let mut config = config();
+        config.rate_limit.api = RateLimitQuota { burst: 2, period_secs: 60 };
+        let app = crate::create_app(AppState::new(pool.clone(), config).unwrap());
+        let alice = create_user(&pool, "Alice", false).await;
+        let request = || {
+            Request::get("/api/v1/microposts")
+                .header(header::AUTHORIZATION, bearer(&alice))
+                .body(Body::empty())
+                .unwrap()
+        };
+        
+        for remaining in ["1", "0"] {
+            let response = send(&app, request()).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["ratelimit-limit"], "2");
+            assert_eq!(response.headers()["ratelimit-remaining"], remaining);
+            assert_eq!(response.headers()["ratelimit-reset"], if remaining == "1" { "30" } else { "60" });
+        }
+        
+        let response = send(&app, request()).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["ratelimit-remaining"], "0");
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+    }
+}
@@ -0,0 +1,58 @@
+use std::time::Duration;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, Method},
+    middleware::Next,
+    response::Response,
+};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use crate::{app::AppState, config::Config};
+
+/// CORS from `[cors]`. Values are checked by `Config::validate`, so anything
+/// unparseable here is skipped rather than reported twice.
+pub fn cors_layer(config: &Config) -> CorsLayer {
+    let origins: Vec<HeaderValue> = config.cors_origins().iter().filter_map(|o| o.parse().ok()).collect();
+    let methods: Vec<Method> = config.cors.allowed_methods.iter().filter_map(|m| m.parse().ok()).collect();
+    let headers: Vec<HeaderName> = config.cors.allowed_headers.iter().filter_map(|h| h.parse().ok()).collect();
+    let exposed: Vec<HeaderName> = config.cors.exposed_headers.iter().filter_map(|h| h.parse().ok()).collect();
+    
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .expose_headers(exposed)
+        .allow_credentials(config.cors.allow_credentials)
+        .max_age(Duration::from_secs(config.cors.max_age_secs))
+}
+
+/// Adds CSP, HSTS, framing, referrer and permissions headers from `[security_headers]`,
+/// leaving any a handler set itself untouched.
+pub async fn security_headers_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    let config = &state.config.security_headers;
+    let headers = response.headers_mut();
+    
+    let mut set = |name: HeaderName, value: &str| {
+        if let (false, Ok(value)) = (headers.contains_key(&name), HeaderValue::from_str(value)) {
+            headers.insert(name, value);
+        }
+    };
+    
+    set(header::CONTENT_SECURITY_POLICY, &config.content_security_policy);
+    set(header::X_FRAME_OPTIONS, &config.frame_options);
+    set(header::REFERRER_POLICY, &config.referrer_policy);
+    set(HeaderName::from_static("permissions-policy"), &config.permissions_policy);
+    set(header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+    if config.hsts_max_age_secs > 0 {
+        set(
+            header::STRICT_TRANSPORT_SECURITY,
+            &format!("max-age={}; includeSubDomains", config.hsts_max_age_secs),
+        );
+    }
+    
+    response
+}
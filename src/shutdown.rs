@@ -0,0 +1,82 @@
+use std::{future::Future, time::Duration};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+/// Coordinates graceful shutdown between the HTTP server and background workers.
+/// Draining fails readiness checks first; cancelling then closes the listener and
+/// stops the workers.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    draining: CancellationToken,
+    token: CancellationToken,
+    tasks: TaskTracker,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Spawns a tracked background task. Tasks should watch `cancelled()` and
+    /// return promptly once it resolves.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.spawn(task);
+    }
+    
+    /// Starts failing readiness checks while still serving requests.
+    pub fn start_draining(&self) {
+        self.draining.cancel();
+    }
+    
+    pub fn trigger(&self) {
+        self.draining.cancel();
+        self.token.cancel();
+    }
+    
+    pub fn is_shutting_down(&self) -> bool {
+        self.draining.is_cancelled()
+    }
+    
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+    
+    /// Waits for every tracked task to finish, returning false if `timeout` elapsed first.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.tasks.close();
+        tokio::time::timeout(timeout, self.tasks.wait()).await.is_ok()
+    }
+}
+
+/// Resolves on SIGINT (Ctrl+C) or, on Unix, SIGTERM.
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("Received SIGINT, shutting down"),
+        _ = terminate => tracing::info!("Received SIGTERM, shutting down"),
+    }
+}
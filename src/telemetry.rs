@@ -0,0 +1,249 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::OnceLock,
+    task::{Context, Poll},
+    time::Instant,
+};
+use axum::{
+    extract::{MatchedPath, Request},
+    http,
+    middleware::Next,
+    response::Response,
+};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use sqlx::PgPool;
+use tracing::Span;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use uuid::Uuid;
+use crate::{
+    auth::is_api_request,
+    config::{LogFormat, TelemetryConfig},
+    error::AppError,
+};
+
+const DEFAULT_LOG_FILTER: &str = "rust_boilerplate=debug,tower_http=debug";
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Installs the global subscriber: `RUST_LOG` filtering, pretty or JSON logs,
+/// and OTLP trace export when `telemetry.otlp_endpoint` is set.
+pub fn init_tracing(config: &TelemetryConfig) -> Result<(), AppError> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| DEFAULT_LOG_FILTER.into());
+    
+    // JSON lines carry the enclosing spans, so request_id and user_id appear on every event
+    let (json, pretty) = match config.log_format {
+        LogFormat::Json => (Some(fmt::layer().json().with_current_span(true).with_span_list(true)), None),
+        LogFormat::Pretty => (None, Some(fmt::layer())),
+    };
+    
+    let otlp = if config.otlp_endpoint.is_empty() {
+        None
+    } else {
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(&config.otlp_endpoint))
+            .with_trace_config(
+                trace::config().with_resource(Resource::new(vec![KeyValue::new("service.name", config.service_name.clone())])),
+            )
+            .install_batch(runtime::Tokio)
+            .map_err(|e| AppError::Config(format!("Failed to start OTLP exporter: {}", e)))?;
+        Some(tracing_opentelemetry::layer().with_tracer(tracer))
+    };
+    
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(json)
+        .with(pretty)
+        .with(otlp)
+        .try_init()
+        .map_err(|e| AppError::Config(format!("Failed to initialize tracing: {}", e)))
+}
+
+/// Flushes spans still buffered for export.
+pub fn shutdown_tracing() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// Root span for each request. `SetRequestIdLayer` runs first, so the header is
+/// always present; `user_id` is filled in by the auth middleware.
+pub fn make_request_span<B>(request: &http::Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    
+    tracing::info_span!(
+        "http_request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id = %request_id,
+        user_id = tracing::field::Empty,
+    )
+}
+
+/// Attaches the authenticated user to the current request span.
+pub fn record_user_id(user_id: Uuid) {
+    Span::current().record("user_id", tracing::field::display(user_id));
+}
+
+/// Latency buckets in seconds, shared by every `*_duration_seconds` histogram.
+const DURATION_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+static PROMETHEUS: OnceLock<Result<PrometheusHandle, String>> = OnceLock::new();
+
+/// Installs the global Prometheus recorder on first use and returns a handle for rendering.
+pub fn prometheus_handle() -> Result<PrometheusHandle, AppError> {
+    PROMETHEUS
+        .get_or_init(|| {
+            PrometheusBuilder::new()
+                .set_buckets_for_metric(Matcher::Suffix("duration_seconds".to_string()), DURATION_BUCKETS)
+                .and_then(|builder| builder.install_recorder())
+                .map_err(|e| e.to_string())
+        })
+        .clone()
+        .map_err(|e| AppError::Config(format!("Failed to install metrics recorder: {}", e)))
+}
+
+/// Records request counts and latency by method, matched route and status.
+/// Unmatched requests share one label so probing random URLs can't blow up cardinality.
+pub async fn track_http_metrics(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |path| path.as_str().to_string());
+    
+    let response = next.run(request).await;
+    
+    let labels = [
+        ("method", method),
+        ("path", path),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    counter!("http_requests_total", &labels).increment(1);
+    histogram!("http_request_duration_seconds", &labels).record(start.elapsed().as_secs_f64());
+    
+    response
+}
+
+/// Samples connection pool occupancy; called on every scrape.
+pub fn record_pool_metrics(pool: &PgPool) {
+    let size = pool.size();
+    let idle = pool.num_idle() as u32;
+    
+    gauge!("db_pool_connections").set(f64::from(size));
+    gauge!("db_pool_idle_connections").set(f64::from(idle));
+    gauge!("db_pool_busy_connections").set(f64::from(size.saturating_sub(idle)));
+    gauge!("db_pool_max_connections").set(f64::from(pool.options().get_max_connections()));
+}
+
+/// Times one connection checkout made by the sampling worker. It stands in for
+/// the wait real queries see (which `db_query_duration_seconds` includes), so the
+/// metric is named for what it is: a periodic probe, not every acquire.
+pub async fn probe_pool_acquire(pool: &PgPool) {
+    let start = Instant::now();
+    match pool.acquire().await {
+        Ok(_conn) => histogram!("db_pool_probe_acquire_duration_seconds").record(start.elapsed().as_secs_f64()),
+        Err(e) => {
+            counter!("db_pool_probe_acquire_errors_total").increment(1);
+            tracing::warn!("Failed to acquire a pooled connection: {}", e);
+        }
+    }
+}
+
+/// Extension for repository queries: `.fetch_one(pool).timed("users.find_by_id").await?`
+/// records `db_query_duration_seconds`, including any wait for a pooled connection.
+pub trait QueryTimer: Future + Sized {
+    fn timed(self, query: &'static str) -> Timed<Self> {
+        Timed {
+            inner: Box::pin(self),
+            query,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl<F: Future> QueryTimer for F {}
+
+pub struct Timed<F> {
+    inner: Pin<Box<F>>,
+    query: &'static str,
+    start: Instant,
+}
+
+impl<F: Future> Future for Timed<F> {
+    type Output = F::Output;
+    
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let output = match self.inner.as_mut().poll(cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => return Poll::Pending,
+        };
+        
+        histogram!("db_query_duration_seconds", "query" => self.query).record(self.start.elapsed().as_secs_f64());
+        Poll::Ready(output)
+    }
+}
+
+pub fn record_signup(method: &'static str) {
+    counter!("signups_total", "method" => method).increment(1);
+}
+
+pub fn record_login(method: &'static str, success: bool) {
+    let result = if success { "success" } else { "failure" };
+    counter!("logins_total", "method" => method, "result" => result).increment(1);
+}
+
+pub fn record_micropost_created() {
+    counter!("microposts_created_total").increment(1);
+}
+
+pub fn record_follow() {
+    counter!("follows_total").increment(1);
+}
+
+/// Counts password signups that succeeded: a 2xx from the API, a redirect from an HTML form.
+pub async fn track_signups(request: Request, next: Next) -> Response {
+    let is_api = is_api_request(&request);
+    let response = next.run(request).await;
+    
+    if succeeded(is_api, &response) {
+        record_signup("password");
+    }
+    
+    response
+}
+
+pub async fn track_microposts_created(request: Request, next: Next) -> Response {
+    let is_api = is_api_request(&request);
+    let response = next.run(request).await;
+    
+    if succeeded(is_api, &response) {
+        record_micropost_created();
+    }
+    
+    response
+}
+
+pub async fn track_follows(request: Request, next: Next) -> Response {
+    let is_api = is_api_request(&request);
+    let response = next.run(request).await;
+    
+    if succeeded(is_api, &response) {
+        record_follow();
+    }
+    
+    response
+}
+
+/// HTML forms redirect on success and re-render on failure.
+fn succeeded(is_api: bool, response: &Response) -> bool {
+    let status = response.status();
+    if is_api { status.is_success() } else { status.is_redirection() }
+}
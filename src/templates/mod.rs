@@ -1,4 +1,14 @@
 use askama::Template;
+use crate::{
+    models::{Micropost, User},
+    utils::pagination::Pagination,
+};
+// Gives every template `self.csrf_field()` and `self.csrf_token()`
+#[allow(unused_imports)]
+use crate::auth::CsrfHelper;
+// Gives every template `self.asset_path(..)` for fingerprinted asset URLs
+#[allow(unused_imports)]
+use crate::assets::AssetHelper;
 
 #[derive(Template)]
 #[template(path = "static_page.html")]
@@ -12,6 +22,8 @@ pub struct StaticPageTemplate {
 #[template(path = "users/signup.html")]
 pub struct SignupTemplate {
     pub title: String,
+    pub name: String,
+    pub email: String,
     pub errors: Vec<String>,
 }
 
@@ -19,5 +31,109 @@ pub struct SignupTemplate {
 #[template(path = "users/login.html")]
 pub struct LoginTemplate {
     pub title: String,
+    pub email: String,
     pub errors: Vec<String>,
 }
+
+/// Second login step, offering a TOTP code, a passkey or both.
+#[derive(Template)]
+#[template(path = "users/mfa.html")]
+pub struct MfaTemplate {
+    pub title: String,
+    pub mfa_token: String,
+    pub remember_me: bool,
+    pub totp: bool,
+    pub passkey: bool,
+    pub errors: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "users/index.html")]
+pub struct UsersIndexTemplate {
+    pub title: String,
+    pub users: Pagination<User>,
+}
+
+/// `microposts` is `None` when the account is private and the viewer may not see it.
+/// `follow_status` is the viewer's relationship to `user`: "accepted", "pending" or none.
+#[derive(Template)]
+#[template(path = "users/show.html")]
+pub struct UserShowTemplate {
+    pub title: String,
+    pub user: User,
+    pub microposts: Option<Pagination<Micropost>>,
+    pub following_count: i64,
+    pub followers_count: i64,
+    pub signed_in: bool,
+    pub is_current_user: bool,
+    pub follow_status: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "users/edit.html")]
+pub struct UserEditTemplate {
+    pub title: String,
+    pub user: User,
+}
+
+/// Following or followers list.
+#[derive(Template)]
+#[template(path = "users/follow.html")]
+pub struct FollowTemplate {
+    pub title: String,
+    pub user: User,
+    pub users: Pagination<User>,
+}
+
+#[derive(Template)]
+#[template(path = "account_activations/new.html")]
+pub struct ActivationFormTemplate {
+    pub title: String,
+    pub notice: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "password_resets/new.html")]
+pub struct PasswordResetFormTemplate {
+    pub title: String,
+    pub notice: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "password_resets/edit.html")]
+pub struct ResetPasswordTemplate {
+    pub title: String,
+    pub token: String,
+    pub errors: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "errors/404.html")]
+pub struct NotFoundTemplate {
+    pub title: String,
+    pub request_id: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "errors/403.html")]
+pub struct ForbiddenTemplate {
+    pub title: String,
+    pub message: String,
+    pub request_id: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "errors/422.html")]
+pub struct UnprocessableTemplate {
+    pub title: String,
+    pub message: String,
+    pub messages: Vec<String>,
+    pub request_id: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "errors/500.html")]
+pub struct ServerErrorTemplate {
+    pub title: String,
+    pub request_id: Option<String>,
+}
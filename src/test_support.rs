@@ -0,0 +1,141 @@
+//! Shared helpers for handler tests: an app on the test configuration, users,
+//! credentials and request plumbing.
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Method, Request, Response, StatusCode},
+    Router,
+};
+use serde_json::Value;
+use sqlx::PgPool;
+use totp_rs::{Algorithm, Secret, TOTP};
+use tower::ServiceExt;
+use uuid::Uuid;
+use crate::{
+    app::AppState,
+    auth::{read_cookie, start_session, JwtService, MfaService, CSRF_COOKIE, CSRF_HEADER},
+    config::{Config, ConfigOverrides, Environment},
+    database::{mfa::MfaRepository, users::UserRepository},
+    models::{CreateUserRequest, User},
+};
+
+pub const PASSWORD: &str = "password";
+
+pub fn config() -> Config {
+    Config::load(&ConfigOverrides {
+        environment: Some(Environment::Test),
+        ..Default::default()
+    })
+    .expect("test configuration loads")
+}
+
+pub fn state(pool: PgPool) -> AppState {
+    AppState::new(pool, config()).unwrap()
+}
+
+pub fn app(pool: PgPool) -> Router {
+    crate::create_app(state(pool))
+}
+
+/// An activated account whose password is `PASSWORD`.
+pub async fn create_user(pool: &PgPool, name: &str, is_private: bool) -> User {
+    let req = CreateUserRequest {
+        name: name.to_string(),
+        email: format!("{}@example.com", name.to_lowercase()),
+        password: PASSWORD.to_string(),
+        password_confirmation: PASSWORD.to_string(),
+    };
+    // The lowest bcrypt cost keeps the suite fast
+    let digest = bcrypt::hash(PASSWORD, 4).unwrap();
+    let user = UserRepository::create(pool, &req, &digest, "unused").await.unwrap();
+    UserRepository::activate(pool, user.id).await.unwrap();
+    
+    UserRepository::set_private(pool, user.id, is_private).await.unwrap()
+}
+
+/// Turns on MFA for `user` and returns the TOTP secret.
+pub async fn enable_mfa(pool: &PgPool, user: &User) -> String {
+    let secret = MfaService::generate_secret();
+    MfaRepository::set_secret(pool, user.id, &secret).await.unwrap();
+    MfaRepository::enable(pool, user.id, &[MfaService::hash_recovery_code("abcde-12345")]).await.unwrap();
+    secret
+}
+
+/// The authenticator app's current code for `secret`.
+pub fn totp_code(secret: &str, user: &User) -> String {
+    let bytes = Secret::Encoded(secret.to_string()).to_bytes().unwrap();
+    TOTP::new(Algorithm::SHA1, 6, 1, 30, bytes, Some("Sample App".to_string()), user.email.clone())
+        .unwrap()
+        .generate_current()
+        .unwrap()
+}
+
+pub fn bearer(user: &User) -> String {
+    let token = JwtService::new(&config().auth.jwt_secret).generate_access_token(user.id).unwrap();
+    format!("Bearer {}", token)
+}
+
+/// `Cookie` header value signing `user` into the HTML app.
+pub fn session_cookie(user_id: Uuid) -> String {
+    let set_cookie = start_session(&config(), user_id, false).unwrap();
+    set_cookie.to_str().unwrap().split(';').next().unwrap().to_string()
+}
+
+/// An authenticated JSON request to the API.
+pub fn json_request(method: Method, uri: &str, user: &User, body: Value) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, bearer(user))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// An anonymous JSON POST, returning the status and parsed body.
+pub async fn post_json(app: &Router, uri: &str, body: Value) -> (StatusCode, Value) {
+    let request = Request::post(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = send(app, request).await;
+    let status = response.status();
+    
+    (status, body_json(response).await)
+}
+
+pub async fn send(app: &Router, request: Request<Body>) -> Response<Body> {
+    app.clone().oneshot(request).await.unwrap()
+}
+
+pub async fn body_json(response: Response<Body>) -> Value {
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+pub async fn body_text(response: Response<Body>) -> String {
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+/// Fetches a CSRF token and returns a form POST builder carrying it, with
+/// `cookie` (e.g. a session) sent alongside the CSRF cookie. Tokens are bound to
+/// the session, so it's fetched with `cookie` too.
+pub async fn form_post(app: &Router, uri: &str, cookie: &str) -> axum::http::request::Builder {
+    let mut request = Request::get("/login");
+    if !cookie.is_empty() {
+        request = request.header(header::COOKIE, cookie);
+    }
+    let response = send(app, request.body(Body::empty()).unwrap()).await;
+    let set_cookie = response.headers().get(header::SET_COOKIE).unwrap().clone();
+    let pair = set_cookie.to_str().unwrap().split(';').next().unwrap().to_string();
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert(header::COOKIE, pair.parse().unwrap());
+    let token = read_cookie(&headers, CSRF_COOKIE).unwrap().to_string();
+    
+    let cookies = if cookie.is_empty() { pair } else { format!("{}; {}", pair, cookie) };
+    Request::post(uri)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header(header::COOKIE, cookies)
+        .header(CSRF_HEADER, token)
+}
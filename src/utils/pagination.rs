@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PER_PAGE: usize = 30;
+pub const MAX_PER_PAGE: usize = 100;
+
+/// `?page=&per_page=` query parameters. Pages are 1-based; out-of-range values
+/// are clamped rather than rejected, like will_paginate.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(from = "RawPaginationParams")]
+pub struct PaginationParams {
+    pub page: usize,
+    pub per_page: usize,
+}
+
+#[derive(Deserialize)]
+struct RawPaginationParams {
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+impl From<RawPaginationParams> for PaginationParams {
+    fn from(raw: RawPaginationParams) -> Self {
+        Self {
+            page: raw.page.unwrap_or(1).max(1),
+            per_page: raw.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE),
+        }
+    }
+}
+
+impl Default for PaginationParams {
+    fn default() -> Self {
+        Self { page: 1, per_page: DEFAULT_PER_PAGE }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Pagination<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+    pub total_pages: usize,
+}
+
+impl<T> Pagination<T> {
+    pub fn new(items: Vec<T>, total: usize, page: usize, per_page: usize) -> Self {
+        Self {
+            items,
+            total,
+            page,
+            per_page,
+            total_pages: total.div_ceil(per_page.max(1)),
+        }
+    }
+    
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Pagination<U> {
+        Pagination {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            page: self.page,
+            per_page: self.per_page,
+            total_pages: self.total_pages,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn params_are_clamped() {
+        let params: PaginationParams = serde_qs::from_str("page=0&per_page=1000").unwrap();
+        assert_eq!((params.page, params.per_page), (1, MAX_PER_PAGE));
+        
+        let params: PaginationParams = serde_qs::from_str("").unwrap();
+        assert_eq!((params.page, params.per_page), (1, DEFAULT_PER_PAGE));
+    }
+    
+    #[test]
+    fn total_pages_rounds_up() {
+        assert_eq!(Pagination::new(vec![1, 2], 61, 1, 30).total_pages, 3);
+        assert_eq!(Pagination::<i32>::new(Vec::new(), 0, 1, 30).total_pages, 0);
+    }
+}
@@ -0,0 +1,349 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, Uri},
+    middleware::Next,
+    response::Response,
+};
+use serde::Deserialize;
+use crate::{app::AppState, error::AppError, http_cache::http_date};
+
+/// Vendor media type prefix, e.g. `application/vnd.app.v2+json`.
+const VND_PREFIX: &str = "application/vnd.app.v";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "u8")]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub const ALL: [ApiVersion; 2] = [ApiVersion::V1, ApiVersion::V2];
+    pub const LATEST: ApiVersion = ApiVersion::V2;
+    
+    pub fn number(self) -> u8 {
+        match self {
+            ApiVersion::V1 => 1,
+            ApiVersion::V2 => 2,
+        }
+    }
+    
+    /// Mount point of this version's routes.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/api/v1",
+            ApiVersion::V2 => "/api/v2",
+        }
+    }
+    
+    /// The version named by a `/api/vN/...` path, if any.
+    fn from_path(path: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|v| path.strip_prefix(v.prefix()).map_or(false, |rest| rest.is_empty() || rest.starts_with('/')))
+    }
+    
+    /// The version requested through a vendor `Accept` type. Unknown versions are an
+    /// error so clients notice instead of silently getting another shape.
+    fn from_accept(headers: &HeaderMap) -> Result<Option<Self>, AppError> {
+        let requested = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|media_type| media_type.trim().strip_prefix(VND_PREFIX))
+            .map(|rest| rest.split(['+', ';']).next().unwrap_or_default())
+            .next();
+        
+        match requested {
+            None => Ok(None),
+            Some(number) => number
+                .parse::<u8>()
+                .ok()
+                .and_then(|n| Self::try_from(n).ok())
+                .map(Some)
+                .ok_or_else(|| AppError::NotAcceptable(format!("Unsupported API version '{}'", number))),
+        }
+    }
+}
+
+impl TryFrom<u8> for ApiVersion {
+    type Error = String;
+    
+    fn try_from(number: u8) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|v| v.number() == number)
+            .ok_or_else(|| format!("unknown API version {}", number))
+    }
+}
+
+/// Handlers take `ApiVersion` to pick the response DTO for the negotiated version.
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ApiVersion {
+    type Rejection = std::convert::Infallible;
+    
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<ApiVersion>().copied().unwrap_or(ApiVersion::V1))
+    }
+}
+
+/// Resolves the API version before routing: `/api/vN/...` paths name it directly,
+/// unversioned `/api/...` paths are rewritten to the version from the vendor
+/// `Accept` type or `api.default_version`. Deprecated versions answer with
+/// `Deprecation`, `Sunset` and a `successor-version` link.
+pub async fn negotiate_version(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let path = request.uri().path().to_string();
+    if !path.starts_with("/api/") {
+        return Ok(next.run(request).await);
+    }
+    
+    let (version, negotiated) = match ApiVersion::from_path(&path) {
+        Some(version) => (version, false),
+        None => {
+            let version = ApiVersion::from_accept(request.headers())?.unwrap_or(state.config.api.default_version);
+            *request.uri_mut() = rewrite(request.uri(), version)?;
+            (version, true)
+        }
+    };
+    request.extensions_mut().insert(version);
+    
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    
+    if negotiated {
+        headers.append(header::VARY, HeaderValue::from_static("accept"));
+    }
+    
+    if let Some(deprecation) = state.config.api.deprecation(version) {
+        headers.insert("deprecation", header_value(format!("@{}", deprecation.deprecated_at.timestamp())));
+        if let Some(sunset) = deprecation.sunset_at {
+            headers.insert("sunset", header_value(http_date(sunset)));
+        }
+        let successor = successor_path(&path, version);
+        headers.append(header::LINK, header_value(format!("<{}>; rel=\"successor-version\"", successor)));
+    }
+    
+    Ok(response)
+}
+
+fn rewrite(uri: &Uri, version: ApiVersion) -> Result<Uri, AppError> {
+    let rest = uri.path().trim_start_matches("/api");
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}{}?{}", version.prefix(), rest, query),
+        None => format!("{}{}", version.prefix(), rest),
+    };
+    
+    path_and_query
+        .parse()
+        .map_err(|_| AppError::BadRequest("Invalid request path".to_string()))
+}
+
+fn successor_path(path: &str, version: ApiVersion) -> String {
+    let rest = path
+        .strip_prefix(version.prefix())
+        .or_else(|| path.strip_prefix("/api"))
+        .unwrap_or_default();
+    format!("{}{}", ApiVersion::LATEST.prefix(), rest)
+}
+
+fn header_value(value: String) -> HeaderValue {
+    HeaderValue::from_str(&value).unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use chrono::Utc;
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+    use super::*;
+    use crate::{
+        database::users::UserRepository,
+        models::{UpdateUserRequest, User},
+        test_support::*,
+    };
+    
+    fn user() -> User {
+        User {
+            id: Uuid::nil(),
+            name: "Example User".to_string(),
+            email: "user@example.com".to_string(),
+            password_digest: "digest".to_string(),
+            admin: false,
+            activated: true,
+            activated_at: None,
+            activation_digest: None,
+            reset_digest: None,
+            reset_sent_at: None,
+            is_private: false,
+            mfa_enabled: false,
+            mfa_secret: None,
+            mfa_enabled_at: None,
+            mfa_last_used_step: None,
+            failed_login_attempts: 0,
+            locked_until: None,
+            username: Some("example".to_string()),
+            bio: None,
+            location: None,
+            website: None,
+            banner: None,
+            tokens_valid_after: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+    
+    fn keys(value: &Value) -> Vec<&str> {
+        let mut keys: Vec<&str> = value.as_object().unwrap().keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        keys
+    }
+    
+    #[test]
+    fn v1_user_response_shape_is_pinned() {
+        let owner = user();
+        let mut stranger = user();
+        stranger.id = Uuid::new_v4();
+        
+        let response = serde_json::to_value(owner.to_response().for_version(ApiVersion::V1, Some(&stranger))).unwrap();
+        
+        assert_eq!(
+            keys(&response),
+            [
+                "activated", "admin", "created_at", "email", "followers_count", "following_count",
+                "gravatar_url", "id", "is_private", "mfa_enabled", "microposts_count", "name",
+            ]
+        );
+        assert_eq!(response["email"], json!("user@example.com"));
+    }
+    
+    #[test]
+    fn v2_user_response_hides_email_from_non_owners() {
+        let owner = user();
+        let mut stranger = user();
+        stranger.id = Uuid::new_v4();
+        
+        let to_stranger = serde_json::to_value(owner.to_response().for_version(ApiVersion::V2, Some(&stranger))).unwrap();
+        let anonymous = serde_json::to_value(owner.to_response().for_version(ApiVersion::V2, None)).unwrap();
+        let to_owner = serde_json::to_value(owner.to_response().for_version(ApiVersion::V2, Some(&owner))).unwrap();
+        
+        assert!(to_stranger.get("email").is_none());
+        assert!(anonymous.get("email").is_none());
+        assert_eq!(to_owner["email"], json!("user@example.com"));
+    }
+    
+    #[test]
+    fn versions_resolve_from_path_and_accept() {
+        assert_eq!(ApiVersion::from_path("/api/v2/users"), Some(ApiVersion::V2));
+        assert_eq!(ApiVersion::from_path("/api/v1"), Some(ApiVersion::V1));
+        assert_eq!(ApiVersion::from_path("/api/v10/users"), None);
+        assert_eq!(ApiVersion::from_path("/api/users"), None);
+        
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static("text/html, application/vnd.app.v2+json"));
+        assert_eq!(ApiVersion::from_accept(&headers).unwrap(), Some(ApiVersion::V2));
+        
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/vnd.app.v9+json"));
+        assert!(ApiVersion::from_accept(&headers).is_err());
+        
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+        assert_eq!(ApiVersion::from_accept(&headers).unwrap(), None);
+    }
+    
+    #[test]
+    fn unversioned_paths_are_rewritten() {
+        let uri: Uri = "/api/users/1?page=2".parse().unwrap();
+        assert_eq!(rewrite(&uri, ApiVersion::V2).unwrap(), "/api/v2/users/1?page=2");
+        assert_eq!(successor_path("/api/v1/users/1", ApiVersion::V1), "/api/v2/users/1");
+    }
+    
+    fn get(uri: &str, user: Option<&User>) -> axum::http::Request<axum::body::Body> {
+        let mut request = axum::http::Request::get(uri);
+        if let Some(user) = user {
+            request = request.header(header::AUTHORIZATION, bearer(user));
+        }
+        request.body(axum::body::Body::empty()).unwrap()
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn v2_routes_hide_email_from_non_owners(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let bob = create_user(&pool, "Bob", false).await;
+        let profile = format!("/api/v2/users/{}", alice.id);
+        
+        let anonymous = send(&app, get(&profile, None)).await;
+        let to_stranger = send(&app, get(&profile, Some(&bob))).await;
+        let to_owner = send(&app, get(&profile, Some(&alice))).await;
+        assert_ne!(to_stranger.headers()[header::ETAG], to_owner.headers()[header::ETAG]);
+        assert!(body_json(anonymous).await.get("email").is_none());
+        assert!(body_json(to_stranger).await.get("email").is_none());
+        assert_eq!(body_json(to_owner).await["email"], json!("alice@example.com"));
+        
+        let page = body_json(send(&app, get("/api/v2/users", Some(&bob))).await).await;
+        let email_of = |user: &User| {
+            let item = page["items"].as_array().unwrap().iter().find(|item| item["id"] == json!(user.id)).unwrap();
+            item.get("email").cloned()
+        };
+        assert_eq!(email_of(&alice), None);
+        assert_eq!(email_of(&bob), Some(json!("bob@example.com")));
+        
+        let followers = body_json(send(&app, get(&format!("{}/followers", profile), None)).await).await;
+        assert!(followers["items"].as_array().unwrap().is_empty());
+        
+        // v1 is frozen
+        let v1 = body_json(send(&app, get(&format!("/api/v1/users/{}", alice.id), None)).await).await;
+        assert_eq!(v1["email"], json!("alice@example.com"));
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn profile_fields_are_v2_only(pool: PgPool) {
+        let app = app(pool.clone());
+        let alice = create_user(&pool, "Alice", false).await;
+        let profile: UpdateUserRequest = serde_json::from_value(json!({ "username": "alice", "bio": "Down the rabbit hole" })).unwrap();
+        UserRepository::update(&pool, alice.id, &profile, None, None).await.unwrap();
+        
+        let v2 = body_json(send(&app, get("/api/v2/users/alice", None)).await).await;
+        assert_eq!(v2["username"], json!("alice"));
+        assert_eq!(v2["bio"], json!("Down the rabbit hole"));
+        assert_eq!(v2["website"], json!(null));
+        
+        let v1 = body_json(send(&app, get(&format!("/api/v1/users/{}", alice.id), None)).await).await;
+        assert!(v1.get("username").is_none() && v1.get("bio").is_none());
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn v2_auth_responses_are_shaped_for_the_owner(pool: PgPool) {
+        let app = app(pool.clone());
+        create_user(&pool, "Alice", false).await;
+        
+        let (status, body) = post_json(&app, "/api/v2/auth/login", json!({ "email": "alice@example.com", "password": PASSWORD })).await;
+        
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["user"]["email"], json!("alice@example.com"));
+        assert_eq!(body["user"]["username"], Value::Null);
+    }
+    
+    #[sqlx::test(migrator = "crate::database::MIGRATOR")]
+    async fn unsupported_versions_are_reported_like_other_errors(pool: PgPool) {
+        let app = app(pool);
+        let request = axum::http::Request::get("/api/users")
+            .header(header::ACCEPT, "application/vnd.app.v9+json, application/problem+json")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        
+        let response = send(&app, request).await;
+        
+        assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/problem+json");
+        let request_id = response.headers()["x-request-id"].to_str().unwrap().to_string();
+        assert_eq!(body_json(response).await["request_id"], json!(request_id));
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::Duration;
+use crate::{
+    app::AppState,
+    database::{identities::IdentityRepository, webauthn::WebauthnRepository},
+    error::AppError,
+    telemetry,
+};
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(300);
+const POOL_SAMPLE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Starts the background workers. They are tracked by `state.shutdown` so
+/// shutdown waits for a pass in progress to finish.
+pub fn spawn(state: &AppState) {
+    let worker_state = state.clone();
+    state.shutdown.spawn(async move { cleanup_expired(worker_state).await });
+    
+    let worker_state = state.clone();
+    state.shutdown.spawn(async move { sample_pool(worker_state).await });
+}
+
+/// Periodically records pool occupancy and probes how long a connection checkout takes.
+async fn sample_pool(state: AppState) {
+    let mut interval = tokio::time::interval(POOL_SAMPLE_INTERVAL);
+    
+    loop {
+        tokio::select! {
+            _ = state.shutdown.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+        
+        telemetry::record_pool_metrics(&state.db);
+        telemetry::probe_pool_acquire(&state.db).await;
+    }
+}
+
+/// Periodically removes expired WebAuthn challenges and OIDC login states.
+async fn cleanup_expired(state: AppState) {
+    let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+    
+    loop {
+        tokio::select! {
+            _ = state.shutdown.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+        
+        if let Err(e) = cleanup_pass(&state).await {
+            tracing::warn!("Expired state cleanup failed: {}", e);
+        }
+    }
+    
+    tracing::debug!("Cleanup worker stopped");
+}
+
+async fn cleanup_pass(state: &AppState) -> Result<(), AppError> {
+    let challenges = WebauthnRepository::delete_expired_challenges(&state.db).await?;
+    let login_states = IdentityRepository::delete_expired_login_states(&state.db).await?;
+    
+    if challenges + login_states > 0 {
+        tracing::debug!("Removed {} expired WebAuthn challenges and {} OIDC login states", challenges, login_states);
+    }
+    
+    Ok(())
+}